@@ -0,0 +1,102 @@
+/*
+
+    MIT License
+
+    Copyright (c) 2022 Siandfrance
+
+    Permission is hereby granted, free of charge, to any person obtaining a copy
+    of this software and associated documentation files (the "Software"), to deal
+    in the Software without restriction, including without limitation the rights
+    to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+    copies of the Software, and to permit persons to whom the Software is
+    furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in all
+    copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+    OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+    SOFTWARE.
+
+*/
+
+
+/// Characters used to draw a box with the box-drawing glyphs of the terminal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BoxChars {
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+    pub horizontal: char,
+    pub vertical: char
+}
+
+
+/// ┌─┐ │ └─┘
+pub const SINGLE: BoxChars = BoxChars {
+    top_left: '┌',
+    top_right: '┐',
+    bottom_left: '└',
+    bottom_right: '┘',
+    horizontal: '─',
+    vertical: '│'
+};
+
+
+/// ╔═╗ ║ ╚═╝
+pub const DOUBLE: BoxChars = BoxChars {
+    top_left: '╔',
+    top_right: '╗',
+    bottom_left: '╚',
+    bottom_right: '╝',
+    horizontal: '═',
+    vertical: '║'
+};
+
+
+/// ╭─╮ │ ╰─╯
+pub const ROUNDED: BoxChars = BoxChars {
+    top_left: '╭',
+    top_right: '╮',
+    bottom_left: '╰',
+    bottom_right: '╯',
+    horizontal: '─',
+    vertical: '│'
+};
+
+
+/// Style of the frames drawn by `Renderer::draw_box_frame`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BoxStyle {
+    Single,
+    Double,
+    Rounded
+}
+
+
+impl BoxStyle {
+
+    /// Returns the characters of the style.
+    pub fn chars(&self) -> BoxChars {
+        match self {
+            BoxStyle::Single => SINGLE,
+            BoxStyle::Double => DOUBLE,
+            BoxStyle::Rounded => ROUNDED
+        }
+    }
+}
+
+
+/// Frames of the Braille spinner, for indeterminate progress (see `Renderer::draw_spinner`).
+pub const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+
+/// Returns the character of the spinner for the frame counter `frame`.
+pub fn spinner_char(frame: u32) -> char {
+    SPINNER[frame as usize % SPINNER.len()]
+}
@@ -25,15 +25,21 @@
 */
 
 
-use crate::math::Vec2;
+use crate::math::{Rect, Vec2};
 
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
 use std::path::Path;
+use std::io;
 
 
 use image::io::Reader as ImageReader;
 use image::{Pixel, RgbImage};
+use image::error::{ParameterError, ParameterErrorKind};
+
+pub use image::ImageError;
 
 
 /// RGB Color struct.
@@ -220,6 +226,288 @@ impl Color {
             b: ((h & 0x000000FF) / 0x00000001) as u8
         }
     }
+
+
+    /// Parses a color from a CSS-style hex string (eg. `"#ff8800"`).
+    /// 
+    /// The leading `#` is optional and the 3 (`"#f80"`), 6 (`"#ff8800"`) and 8 (`"#ff8800cc"`) digit
+    /// forms are accepted. As colors do not have an alpha channel, the alpha of the 8 digit form is
+    /// checked but discarded.
+    pub fn from_str_hex(s: &str) -> Result<Self, ColorParseError> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+
+        let mut values = Vec::with_capacity(8);
+        for ch in digits.chars() {
+            match ch.to_digit(16) {
+                Some(v) => values.push(v),
+                None => return Err(ColorParseError::InvalidCharacter(ch))
+            }
+        }
+
+        let h = match values.len() {
+            3 => values.iter().fold(0, |h, v| (h << 8) | (v << 4) | v),
+            6 => values.iter().fold(0, |h, v| (h << 4) | v),
+            8 => values[..6].iter().fold(0, |h, v| (h << 4) | v),
+            n => return Err(ColorParseError::InvalidLength(n))
+        };
+        Ok(Color::hex(h))
+    }
+}
+
+
+/// Converts a point to floating point coordinates for the Bezier curves.
+fn bezier_point(p: Vec2) -> (f64, f64) {
+    (p.x as f64, p.y as f64)
+}
+
+
+/// Returns the top left corner and the horizontal span (relative to the corner) of each row of the
+/// rounded rectangle at `p` of size `s`.
+fn rounded_rect_spans(mut p: Vec2, mut s: Vec2, radius: i32) -> (Vec2, Vec<(i32, i32)>) {
+    // same convention as `Image::rect` for negative sizes
+    if s.x < 0 {
+        s.x = -s.x;
+        p.x -= s.x - 1;
+    }
+    if s.y < 0 {
+        s.y = -s.y;
+        p.y -= s.y - 1;
+    }
+
+    let r = radius.clamp(0, s.x.min(s.y) / 2);
+    let spans = (0..s.y).map(|y| {
+        // vertical distance between the center of the row and the center of the corner
+        let dy = if y < r {
+            r as f64 - y as f64 - 0.5
+        } else if y >= s.y - r {
+            (y - (s.y - r)) as f64 + 0.5
+        } else {
+            0.0
+        };
+        let inset = if dy > 0.0 {
+            (r as f64 - (r as f64 * r as f64 - dy * dy).max(0.0).sqrt()).round() as i32
+        } else {
+            0
+        };
+        (inset, s.x - 1 - inset)
+    }).collect();
+    (p, spans)
+}
+
+
+/// Xterm's default values for the 16 standard colors.
+const ANSI16_PALETTE: [Color; 16] = [
+    Color::rgb(  0,   0,   0), Color::rgb(205,   0,   0), Color::rgb(  0, 205,   0), Color::rgb(205, 205,   0),
+    Color::rgb(  0,   0, 238), Color::rgb(205,   0, 205), Color::rgb(  0, 205, 205), Color::rgb(229, 229, 229),
+    Color::rgb(127, 127, 127), Color::rgb(255,   0,   0), Color::rgb(  0, 255,   0), Color::rgb(255, 255,   0),
+    Color::rgb( 92,  92, 255), Color::rgb(255,   0, 255), Color::rgb(  0, 255, 255), Color::rgb(255, 255, 255)
+];
+
+
+/// Channel values of the 6x6x6 color cube of the 256 color palette.
+const ANSI256_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+
+impl Color {
+
+    /// Linear interpolation between `self` (when `t` is 0) and `other` (when `t` is 1).
+    /// `t` is clamped to [0, 1].
+    pub fn lerp(&self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::rgb(mix(self.r, other.r), mix(self.g, other.g), mix(self.b, other.b))
+    }
+
+
+    /// Warms (positive `kelvin_shift`, toward orange) or cools (negative `kelvin_shift`, toward blue) the color.
+    /// 
+    /// Every 100K of shift moves the red and blue channels by 2 levels in opposite directions, and the green
+    /// channel by half that amount, so that warm colors lean toward orange rather than magenta.
+    pub fn adjust_temperature(&self, kelvin_shift: i32) -> Color {
+        let delta = kelvin_shift / 50;
+        let shift = |v: u8, d: i32| (v as i32 + d).clamp(0, 255) as u8;
+        Color::rgb(shift(self.r, delta), shift(self.g, delta / 2), shift(self.b, -delta))
+    }
+
+
+    /// Squared euclidean distance between two colors.
+    pub(crate) fn distance2(&self, other: Color) -> i32 {
+        let dr = self.r as i32 - other.r as i32;
+        let dg = self.g as i32 - other.g as i32;
+        let db = self.b as i32 - other.b as i32;
+        dr * dr + dg * dg + db * db
+    }
+
+
+    /// Returns the index of the nearest color in the 256 color palette.
+    /// 
+    /// Only the 6x6x6 color cube (16 to 231) and the grayscale ramp (232 to 255) are considered,
+    /// as the first 16 colors depend on the terminal theme.
+    pub fn to_ansi256(&self) -> u8 {
+        let cube_index = |v: u8| match v {
+            0..=47 => 0,
+            48..=114 => 1,
+            v => (v - 35) / 40
+        };
+        let (ri, gi, bi) = (cube_index(self.r), cube_index(self.g), cube_index(self.b));
+        let cube = Color::rgb(ANSI256_CUBE_LEVELS[ri as usize], ANSI256_CUBE_LEVELS[gi as usize], ANSI256_CUBE_LEVELS[bi as usize]);
+
+        let average = (self.r as i32 + self.g as i32 + self.b as i32) / 3;
+        let gray_index = ((average - 3) / 10).clamp(0, 23) as u8;
+        let gray_level = 8 + 10 * gray_index;
+        let gray = Color::rgb(gray_level, gray_level, gray_level);
+
+        if self.distance2(gray) < self.distance2(cube) {
+            232 + gray_index
+        } else {
+            16 + 36 * ri + 6 * gi + bi
+        }
+    }
+
+
+    /// Returns the color of the 256 color palette at `index`, using xterm's default values for the first 16 colors.
+    pub fn from_ansi256(index: u8) -> Color {
+        match index {
+            0..=15 => ANSI16_PALETTE[index as usize],
+            16..=231 => {
+                let i = index - 16;
+                Color::rgb(
+                    ANSI256_CUBE_LEVELS[(i / 36) as usize],
+                    ANSI256_CUBE_LEVELS[(i / 6 % 6) as usize],
+                    ANSI256_CUBE_LEVELS[(i % 6) as usize]
+                )
+            }
+            _ => {
+                let level = 8 + 10 * (index - 232);
+                Color::rgb(level, level, level)
+            }
+        }
+    }
+
+
+    /// Returns the index of the nearest color in the 16 standard colors, using xterm's default values.
+    pub fn to_ansi16(&self) -> u8 {
+        (0..16).min_by_key(|&i| self.distance2(ANSI16_PALETTE[i])).unwrap() as u8
+    }
+}
+
+
+impl FromStr for Color {
+    type Err = ColorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::from_str_hex(s)
+    }
+}
+
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Color {
+
+    /// Serializes the color as a hex string (eg. `"#ff8800"`).
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.collect_str(&format_args!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b))
+    }
+}
+
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Color {
+
+    /// Deserializes a color from a hex string, in any of the forms accepted by `Color::from_str_hex`.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        let s = String::deserialize(deserializer)?;
+        Color::from_str_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+
+/// Error returned when parsing a Color from a string fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorParseError {
+    /// The string contains a character that is not an hexadecimal digit.
+    InvalidCharacter(char),
+    /// The string does not have 3, 6 or 8 digits.
+    InvalidLength(usize)
+}
+
+
+impl fmt::Display for ColorParseError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorParseError::InvalidCharacter(c) => write!(f, "invalid hexadecimal digit {:?} in color", c),
+            ColorParseError::InvalidLength(n) => write!(f, "color has {} digits, expected 3, 6 or 8", n)
+        }
+    }
+}
+
+
+impl Error for ColorParseError {}
+
+
+/// Error returned when loading an Image fails.
+#[derive(Debug)]
+pub enum ImageLoadError {
+    /// The file does not exist.
+    NotFound,
+    /// The file could not be read.
+    Io(io::Error),
+    /// The format of the file is not supported.
+    UnsupportedFormat(String),
+    /// The content of the file is invalid.
+    Corrupt(String)
+}
+
+
+impl fmt::Display for ImageLoadError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageLoadError::NotFound => write!(f, "image file not found"),
+            ImageLoadError::Io(e) => write!(f, "could not read image: {}", e),
+            ImageLoadError::UnsupportedFormat(e) => write!(f, "unsupported image format: {}", e),
+            ImageLoadError::Corrupt(e) => write!(f, "invalid image data: {}", e)
+        }
+    }
+}
+
+
+impl Error for ImageLoadError {
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImageLoadError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+
+impl From<io::Error> for ImageLoadError {
+
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => ImageLoadError::NotFound,
+            _ => ImageLoadError::Io(e)
+        }
+    }
+}
+
+
+impl From<ImageError> for ImageLoadError {
+
+    fn from(e: ImageError) -> Self {
+        match e {
+            ImageError::IoError(e) => e.into(),
+            ImageError::Unsupported(e) => ImageLoadError::UnsupportedFormat(e.to_string()),
+            e => ImageLoadError::Corrupt(e.to_string())
+        }
+    }
 }
 
 
@@ -236,12 +524,180 @@ impl fmt::Display for Color {
 }
 
 
-#[derive(Clone)]
+/// Color escape sequences used to display colors in the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 24-bit colors.
+    TrueColor,
+    /// Nearest color of the 256 color palette (see `Color::to_ansi256`).
+    Ansi256,
+    /// Nearest color of the 16 standard colors (see `Color::to_ansi16`).
+    Ansi16
+}
+
+
+/// Width of a glyph of the built-in font, in pixels.
+pub const GLYPH_WIDTH: i32 = 5;
+/// Height of a glyph of the built-in font, in pixels.
+pub const GLYPH_HEIGHT: i32 = 7;
+
+
+/// Built-in 5x7 font for the printable ASCII characters (from ' ' to '~').
+/// Each glyph is stored as 5 columns, the least significant bit being the top row.
+const FONT_5X7: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // 'f'
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08], // '~'
+];
+
+
+/// Glyph drawn for the characters missing from the font.
+const PLACEHOLDER_GLYPH: [u8; 5] = [0x7F, 0x41, 0x41, 0x41, 0x7F];
+
+
+/// 4x4 Bayer matrix used for ordered dithering.
+const BAYER_MATRIX: [[u8; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5]
+];
+
+
+impl ColorMode {
+
+    /// Returns the color that is displayed when showing `c` in this mode.
+    pub fn nearest(&self, c: Color) -> Color {
+        match self {
+            ColorMode::TrueColor => c,
+            ColorMode::Ansi256 => Color::from_ansi256(c.to_ansi256()),
+            ColorMode::Ansi16 => ANSI16_PALETTE[c.to_ansi16() as usize]
+        }
+    }
+
+
+    /// Writes the CSI setting the foreground (or background if `background` is true) to `c` to `out`.
+    pub fn write_escape<W>(&self, out: &mut W, c: Color, background: bool) -> std::io::Result<()>
+        where W: std::io::Write
+    {
+        match self {
+            ColorMode::TrueColor => if background { write!(out, "{:-}", c) } else { write!(out, "{:+}", c) },
+            ColorMode::Ansi256 => write!(out, "\x1b[{};5;{}m", if background {48} else {38}, c.to_ansi256()),
+            ColorMode::Ansi16 => {
+                let i = c.to_ansi16();
+                let base = match (background, i < 8) {
+                    (false, true) => 30,
+                    (false, false) => 90 - 8,
+                    (true, true) => 40,
+                    (true, false) => 100 - 8
+                };
+                write!(out, "\x1b[{}m", base + i)
+            }
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Eq)]
 /// Image struct. This is a Color buffer.
 /// Pixels can be accessed by indexing with the pixel coordinates.
 pub struct Image {
     data: Vec<Color>,
-    size: Vec2
+    size: Vec2,
+    /// rectangle outside of which the drawing functions leave the pixels unchanged
+    clip: Option<Rect>
 }
 
 
@@ -251,21 +707,16 @@ impl Image {
     pub fn new(w: usize, h: usize) -> Self {
         Self {
             data: vec![Color::BLACK; w * h],
-            size: vec2!(w as i32, h as i32)
+            size: vec2!(w as i32, h as i32),
+            clip: None
         }
     }
 
 
-    /// Loads an image from a file.
-    pub fn load<P>(path: P) -> Result<Self, String>
+    /// Loads an image from a file. The format is deduced from the extension of `path`.
+    pub fn load<P>(path: P) -> Result<Self, ImageLoadError>
             where P: AsRef<Path> {
-        let img = match ImageReader::open(path) {
-            Ok(s) => match s.decode() {
-                Ok(img) => img,
-                Err(e) => return Err(format!("{}", e))
-            }
-            Err(e) => return Err(format!("{}", e))
-        }.to_rgb8();
+        let img = ImageReader::open(path).map_err(ImageLoadError::from)?.decode()?.to_rgb8();
         let mut result = Image::new(img.width() as usize, img.height() as usize);
         for i in 0..img.width() {
             for j in 0..img.height() {
@@ -277,21 +728,26 @@ impl Image {
     }
 
 
-    /// Saves an image to a file.
-    pub fn save<P>(&self, path: P) -> Result<(), String>
+    /// Saves an image to a file. The format is deduced from the extension of `path` (eg. `.png`).
+    /// 
+    /// Images without pixels cannot be saved.
+    pub fn save<P>(&self, path: P) -> Result<(), ImageError>
             where P: AsRef<Path> {
+        if self.size.x == 0 || self.size.y == 0 {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::Generic("cannot save an empty image".to_string())
+            )));
+        }
+
         let mut img = RgbImage::new(self.size.x as u32, self.size.y as u32);
         for i in 0..self.size.x {
             for j in 0..self.size.y {
-                let mut pix = img.get_pixel_mut(i as u32, j as u32);
+                let pix = img.get_pixel_mut(i as u32, j as u32);
                 let c = self[vec2!(i, j)];
                 pix.0 = [c.r, c.g, c.b];
             }
         }
-        match img.save(path) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("{}", e))
-        }
+        img.save(path)
     }
 
 
@@ -302,6 +758,223 @@ impl Image {
     }
 
 
+    /// Returns the pixels of the rows `rows`, one row after the other. The rows out of the image are ignored.
+    pub(crate) fn rows(&self, rows: Range<i32>) -> &[Color] {
+        let start = rows.start.clamp(0, self.size.y);
+        let end = rows.end.clamp(start, self.size.y);
+        &self.data[(start * self.size.x) as usize..(end * self.size.x) as usize]
+    }
+
+
+    /// Returns the image with colors that can be displayed in `mode`, using ordered (Bayer) dithering
+    /// to render the colors that are not available.
+    /// 
+    /// The threshold only depends on the position of the pixel, so the result is stable between frames.
+    pub fn dither(&self, mode: ColorMode) -> Image {
+        let mut result = Image::new(0, 0);
+        self.dither_into(mode, &mut result);
+        result
+    }
+
+
+    /// Same as `dither` but writes the result in `result`, reusing its buffer.
+    pub fn dither_into(&self, mode: ColorMode, result: &mut Image) {
+        result.copy_from(self);
+        let spread = match mode {
+            ColorMode::TrueColor => return,
+            ColorMode::Ansi256 => 48.0,
+            ColorMode::Ansi16 => 128.0
+        };
+
+        for j in 0..self.size.y {
+            for i in 0..self.size.x {
+                let threshold = (BAYER_MATRIX[(j % 4) as usize][(i % 4) as usize] as f32 + 0.5) / 16.0 - 0.5;
+                let offset = |v: u8| (v as f32 + spread * threshold).round().clamp(0.0, 255.0) as u8;
+                let c = self[(i, j)];
+                result[(i, j)] = mode.nearest(Color::rgb(offset(c.r), offset(c.g), offset(c.b)));
+            }
+        }
+    }
+
+
+    /// Reduces the image to at most `n` colors with the median cut algorithm. Returns the quantized image
+    /// and its palette.
+    /// 
+    /// The distinct colors of the image are split in two halves along the channel with the largest range
+    /// until there are `n` groups, each group giving the average of its colors (weighted by their number of
+    /// pixels). Each pixel is then replaced by the nearest color of the palette.
+    pub fn quantize(&self, n: usize) -> (Image, Vec<Color>) {
+        let mut counts = std::collections::HashMap::new();
+        for &c in &self.data {
+            *counts.entry((c.r, c.g, c.b)).or_insert(0u64) += 1;
+        }
+        let mut colors: Vec<(Color, u64)> = counts.into_iter().map(|((r, g, b), n)| (Color::rgb(r, g, b), n)).collect();
+        colors.sort_by_key(|(c, _)| (c.r, c.g, c.b));
+
+        let channels: [fn(&Color) -> u8; 3] = [|c| c.r, |c| c.g, |c| c.b];
+        let range = |group: &[(Color, u64)], channel: fn(&Color) -> u8| {
+            let values = group.iter().map(|(c, _)| channel(c));
+            values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+        };
+
+        let mut groups = vec![colors];
+        while groups.len() < n.max(1) {
+            // split the group with the largest channel range
+            let widest = groups.iter().enumerate()
+                .filter(|(_, group)| group.len() > 1)
+                .map(|(i, group)| (i, channels.iter().map(|&ch| (range(group, ch), ch)).max_by_key(|(r, _)| *r).unwrap()))
+                .max_by_key(|(_, (r, _))| *r);
+            let (i, channel) = match widest {
+                Some((i, (_, channel))) => (i, channel),
+                None => break
+            };
+            let mut group = groups.swap_remove(i);
+            group.sort_by_key(|(c, _)| channel(c));
+            let half = group.split_off(group.len() / 2);
+            groups.push(group);
+            groups.push(half);
+        }
+
+        let palette: Vec<Color> = groups.iter()
+            .filter(|group| !group.is_empty())
+            .map(|group| {
+                let total: u64 = group.iter().map(|(_, n)| n).sum();
+                let mean = |channel: fn(&Color) -> u8| {
+                    ((group.iter().map(|(c, n)| channel(c) as u64 * n).sum::<u64>() + total / 2) / total) as u8
+                };
+                Color::rgb(mean(channels[0]), mean(channels[1]), mean(channels[2]))
+            })
+            .collect();
+
+        let mut result = self.clone();
+        for c in result.data.iter_mut() {
+            *c = *palette.iter().min_by_key(|p| p.distance2(*c)).unwrap();
+        }
+        (result, palette)
+    }
+
+
+    /// Makes `self` a copy of `other`, reusing the buffer of `self` if it is large enough.
+    pub fn copy_from(&mut self, other: &Image) {
+        self.data.clone_from(&other.data);
+        self.size = other.size;
+    }
+
+
+    /// Returns the region of size `size` starting at `offset`. The offset is clamped so that the region
+    /// stays inside the image, and the region is padded with black if the image is smaller than `size`.
+    pub fn view<A, B>(&self, offset: A, size: B) -> Image
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let size = size.into();
+        let offset = offset.into();
+        let offset = vec2!(
+            offset.x.min(self.size.x - size.x).max(0),
+            offset.y.min(self.size.y - size.y).max(0)
+        );
+
+        let mut region = Image::new(size.x.max(0) as usize, size.y.max(0) as usize);
+        region.blit(self, (offset, size), Vec2::ZERO, None);
+        region
+    }
+
+
+    /// Returns the region of size `s` whose top left corner is `p`. The region is clamped to the image,
+    /// so the result can be smaller than `s`.
+    pub fn crop<A, B>(&self, p: A, s: B) -> Image
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let p = p.into();
+        let end = p + s.into();
+        let start = vec2!(p.x.clamp(0, self.size.x), p.y.clamp(0, self.size.y));
+        let end = vec2!(end.x.clamp(start.x, self.size.x), end.y.clamp(start.y, self.size.y));
+
+        let size = end - start;
+        let mut result = Image::new(size.x as usize, size.y as usize);
+        result.blit(self, (start, size), Vec2::ZERO, None);
+        result
+    }
+
+
+    /// Returns the image mirrored left to right.
+    pub fn flip_horizontal(&self) -> Image {
+        let mut result = Image::new(self.size.x as usize, self.size.y as usize);
+        for j in 0..self.size.y {
+            for i in 0..self.size.x {
+                result[(self.size.x - 1 - i, j)] = self[(i, j)];
+            }
+        }
+        result
+    }
+
+
+    /// Returns the image mirrored top to bottom.
+    pub fn flip_vertical(&self) -> Image {
+        let mut result = Image::new(self.size.x as usize, self.size.y as usize);
+        for j in 0..self.size.y {
+            for i in 0..self.size.x {
+                result[(i, self.size.y - 1 - j)] = self[(i, j)];
+            }
+        }
+        result
+    }
+
+
+    /// Returns the image rotated clockwise by `times` quarter turns.
+    pub fn rotate90(&self, times: u32) -> Image {
+        match times % 4 {
+            0 => self.clone(),
+            1 => self.transpose().flip_horizontal(),
+            2 => self.flip_horizontal().flip_vertical(),
+            _ => self.transpose().flip_vertical()
+        }
+    }
+
+
+    /// Returns the image flipped along its diagonal: the pixel at (x, y) is moved to (y, x).
+    pub fn transpose(&self) -> Image {
+        let mut result = Image::new(self.size.y as usize, self.size.x as usize);
+        for j in 0..self.size.y {
+            for i in 0..self.size.x {
+                result[(j, i)] = self[(i, j)];
+            }
+        }
+        result
+    }
+
+
+    /// Stacks images from left to right. Images shorter than the tallest one are padded with `fill`.
+    pub fn concat_h(imgs: &[&Image], fill: Color) -> Image {
+        let w = imgs.iter().map(|img| img.size.x).sum::<i32>();
+        let h = imgs.iter().map(|img| img.size.y).max().unwrap_or(0);
+
+        let mut result = Image::new(w as usize, h as usize);
+        result.clear(fill);
+        let mut x = 0;
+        for img in imgs {
+            result.whole_image(img, (x, 0));
+            x += img.size.x;
+        }
+        result
+    }
+
+
+    /// Stacks images from top to bottom. Images narrower than the widest one are padded with `fill`.
+    pub fn concat_v(imgs: &[&Image], fill: Color) -> Image {
+        let w = imgs.iter().map(|img| img.size.x).max().unwrap_or(0);
+        let h = imgs.iter().map(|img| img.size.y).sum::<i32>();
+
+        let mut result = Image::new(w as usize, h as usize);
+        result.clear(fill);
+        let mut y = 0;
+        for img in imgs {
+            result.whole_image(img, (0, y));
+            y += img.size.y;
+        }
+        result
+    }
+
+
     /// Resizes the image. New pixels are set to black.
     /// 
     /// TODO: pixels are not reset, though they should be moved to keep the start of the image.
@@ -313,27 +986,150 @@ impl Image {
 
 
     fn is_out_of_range<A>(&self, p: A) -> bool
-        where A: AsRef<Vec2> 
+        where A: Into<Vec2> 
     {
-        let p = p.as_ref();
+        let p: Vec2 = p.into();
         p.x < 0 || p.y < 0 || p.x >= self.size.x || p.y >= self.size.y
     }
 
 
-    /// Sets the pixel color at `p` to `c`.
+    /// Restricts the drawing functions to the pixels of `clip`, or lets them draw on the whole image if
+    /// `clip` is None. Indexing the image is not restricted.
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+
+    /// Returns the rectangle the drawing functions are restricted to, if any.
+    pub fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+
+    /// Returns the rectangle of pixels that can be drawn on: the image intersected with the clip rectangle.
+    fn drawable(&self) -> Rect {
+        let image = Rect::new(Vec2::ZERO, self.size);
+        match self.clip {
+            Some(clip) => image.intersection(clip).unwrap_or(Rect::new(Vec2::ZERO, Vec2::ZERO)),
+            None => image
+        }
+    }
+
+
+    /// Returns the color of the pixel at `p`, None if `p` is out of range.
+    pub fn get_pixel<A>(&self, p: A) -> Option<Color>
+        where A: Into<Vec2>
+    {
+        let p: Vec2 = p.into();
+        if self.is_out_of_range(p) {
+            return None;
+        }
+        Some(self.data[(p.x + p.y * self.size.x) as usize])
+    }
+
+
+    /// Sets the pixel color at `p` to `c`. Returns false, leaving the image unchanged, if `p` is out of range
+    /// or outside of the clip rectangle.
+    pub fn set_pixel<A>(&mut self, p: A, c: Color) -> bool
+        where A: Into<Vec2>
+    {
+        let p: Vec2 = p.into();
+        if self.is_out_of_range(p) || self.clip.is_some_and(|clip| !clip.contains(p)) {
+            return false;
+        }
+        self.data[(p.x + p.y * self.size.x) as usize] = c;
+        true
+    }
+
+
+    /// Sets the pixel color at `p` to `c`. Pixels out of range are ignored.
     pub fn point<A>(&mut self, p: A, c: Color)
-        where A: AsRef<Vec2>
+        where A: Into<Vec2>
     {
-        self[*p.as_ref()] = c;
+        self.set_pixel(p, c);
     }
 
 
     /// Draws a line of color `c` between `p1` and `p2`.
     pub fn line<A, B>(&mut self, p1: A, p2: B, c: Color)
-        where A: AsRef<Vec2>, B: AsRef<Vec2> 
+        where A: Into<Vec2>, B: Into<Vec2> 
+    {
+        let mut p1 = p1.into();
+        let p2: Vec2 = p2.into();
+
+        let dx = (p2.x - p1.x).abs();
+        let sx = if p1.x < p2.x {1} else {-1};
+        let dy = -(p2.y - p1.y).abs();
+        let sy = if p1.y < p2.y {1} else {-1};
+
+        let mut err = dx + dy;
+
+        self.set_pixel(p1, c);
+
+        while (p1.x != p2.x || p1.y != p2.y)
+             && ((p1.x < self.size.x && sx > 0) || (p1.x >= 0 && sx < 0))
+             && ((p1.y < self.size.y && sy > 0) || (p1.y >= 0 && sy < 0))
+        {
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                p1.x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                p1.y += sy;
+            }
+
+            self.set_pixel(p1, c);
+        }
+    }
+
+
+    /// Draws an anti-aliased line of color `c` between `p1` and `p2` with Xiaolin Wu's algorithm.
+    /// The pixels on the edges of the line are blended with the color already in the image.
+    /// 
+    /// Horizontal, vertical and 45 degrees lines are the same as with `line`.
+    pub fn line_aa<A, B>(&mut self, p1: A, p2: B, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
     {
-        let mut p1 = *p1.as_ref();
-        let p2 = p2.as_ref();
+        let (p1, p2) = (p1.into(), p2.into());
+        let steep = (p2.y - p1.y).abs() > (p2.x - p1.x).abs();
+
+        // work with a line that is mostly horizontal and goes to the right
+        let (mut a, mut b) = if steep {(vec2!(p1.y, p1.x), vec2!(p2.y, p2.x))} else {(p1, p2)};
+        if a.x > b.x {
+            std::mem::swap(&mut a, &mut b);
+        }
+
+        let dx = (b.x - a.x) as f32;
+        let gradient = if dx == 0.0 {0.0} else {(b.y - a.y) as f32 / dx};
+
+        let mut plot = |x: i32, y: i32, coverage: f32| {
+            let p = if steep {vec2!(y, x)} else {vec2!(x, y)};
+            if let Some(back) = self.get_pixel(p) {
+                self.set_pixel(p, back.lerp(c, coverage));
+            }
+        };
+
+        let mut y = a.y as f32;
+        for x in a.x..=b.x {
+            let base = y.floor();
+            let fract = y - base;
+            plot(x, base as i32, 1.0 - fract);
+            if fract > 0.0 {
+                plot(x, base as i32 + 1, fract);
+            }
+            y += gradient;
+        }
+    }
+
+
+    /// Draws a line between `p1` and `p2` whose color goes from `c1` at `p1` to `c2` at `p2`.
+    pub fn line_gradient<A, B>(&mut self, p1: A, p2: B, c1: Color, c2: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let mut p1 = p1.into();
+        let p2: Vec2 = p2.into();
 
         let dx = (p2.x - p1.x).abs();
         let sx = if p1.x < p2.x {1} else {-1};
@@ -342,7 +1138,10 @@ impl Image {
 
         let mut err = dx + dy;
 
-        self[p1] = c;
+        let steps = dx.max(-dy).max(1) as f32;
+        let mut step = 0;
+
+        self.set_pixel(p1, c1);
 
         while (p1.x != p2.x || p1.y != p2.y)
              && ((p1.x < self.size.x && sx > 0) || (p1.x >= 0 && sx < 0))
@@ -358,17 +1157,71 @@ impl Image {
                 p1.y += sy;
             }
 
-            self[p1] = c;
+            step += 1;
+            self.set_pixel(p1, c1.lerp(c2, step as f32 / steps));
         }
     }
 
 
+    /// Draws a quadratic Bezier curve of color `c` from `p0` to `p2` with control point `p1`.
+    pub fn bezier_quad<A, B, C>(&mut self, p0: A, p1: B, p2: C, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>, C: Into<Vec2>
+    {
+        // elevate the curve to a cubic one with the same shape
+        let (p0, p1, p2) = (bezier_point(p0.into()), bezier_point(p1.into()), bezier_point(p2.into()));
+        let q1 = (p0.0 + 2.0 * (p1.0 - p0.0) / 3.0, p0.1 + 2.0 * (p1.1 - p0.1) / 3.0);
+        let q2 = (p2.0 + 2.0 * (p1.0 - p2.0) / 3.0, p2.1 + 2.0 * (p1.1 - p2.1) / 3.0);
+        self.bezier_subdivide([p0, q1, q2, p2], c, 0);
+    }
+
+
+    /// Draws a cubic Bezier curve of color `c` from `p0` to `p3` with control points `p1` and `p2`.
+    pub fn bezier_cubic<A, B, C, D>(&mut self, p0: A, p1: B, p2: C, p3: D, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>, C: Into<Vec2>, D: Into<Vec2>
+    {
+        let points = [
+            bezier_point(p0.into()), bezier_point(p1.into()),
+            bezier_point(p2.into()), bezier_point(p3.into())
+        ];
+        self.bezier_subdivide(points, c, 0);
+    }
+
+
+    /// Splits the cubic curve in two halves (de Casteljau) until it is flat enough to be drawn as a line.
+    fn bezier_subdivide(&mut self, p: [(f64, f64); 4], c: Color, depth: u32) {
+        // distance of the control points to the chord, the curve is flat when it is below half a pixel
+        let (dx, dy) = (p[3].0 - p[0].0, p[3].1 - p[0].1);
+        let d1 = ((p[1].0 - p[3].0) * dy - (p[1].1 - p[3].1) * dx).abs();
+        let d2 = ((p[2].0 - p[3].0) * dy - (p[2].1 - p[3].1) * dx).abs();
+        let flat = (d1 + d2) * (d1 + d2) <= 0.25 * (dx * dx + dy * dy)
+            && (dx != 0.0 || dy != 0.0 || (p[1] == p[0] && p[2] == p[0]));
+
+        if flat || depth >= 16 {
+            let start = vec2!(p[0].0.round() as i32, p[0].1.round() as i32);
+            let end   = vec2!(p[3].0.round() as i32, p[3].1.round() as i32);
+            self.line(start, end, c);
+            return;
+        }
+
+        let mid = |a: (f64, f64), b: (f64, f64)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        let p01 = mid(p[0], p[1]);
+        let p12 = mid(p[1], p[2]);
+        let p23 = mid(p[2], p[3]);
+        let p012 = mid(p01, p12);
+        let p123 = mid(p12, p23);
+        let center = mid(p012, p123);
+
+        self.bezier_subdivide([p[0], p01, p012, center], c, depth + 1);
+        self.bezier_subdivide([center, p123, p23, p[3]], c, depth + 1);
+    }
+
+
     /// Same as `rect` but draws only the four sides of the rectangle.
     pub fn rect_boudary<A, B>(&mut self, p: A, s: B, c: Color)
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
     {
-        let p = p.as_ref();
-        let s = s.as_ref();
+        let p: Vec2 = p.into();
+        let s: Vec2 = s.into();
         self.line((p.x      , p.y      ), (p.x + s.x, p.y      ), c);
         self.line((p.x + s.x, p.y      ), (p.x + s.x, p.y + s.y), c);
         self.line((p.x + s.x, p.y + s.y), (p.x      , p.y + s.y), c);
@@ -379,17 +1232,17 @@ impl Image {
     /// Draws a rectangle of color `c` and of size `s`. 
     /// `p` is the coordinate of the top left corner of the rectangle.
     pub fn rect<A, B>(&mut self, p: A, s: B, c: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
     {
-        let mut p = *p.as_ref();
-        let mut s = *s.as_ref();
+        let mut p = p.into();
+        let mut s = s.into();
 
         if p.x < 0 {
-            s.x += p.x - 1;
+            s.x += p.x;
             p.x = 0;
         }
         if p.y < 0 {
-            s.y += p.y - 1;
+            s.y += p.y;
             p.y = 0;
         }
 
@@ -403,90 +1256,304 @@ impl Image {
                 let x = p.x + i * dx;
                 if x >= self.size.x {break}
 
-                self[(x, y)] = c;
+                self.set_pixel((x, y), c);
             }
         }
     }
 
 
+    /// Draws a rectangle of color `c` and of size `s` with corners rounded by quarter ellipses of radius `radius`.
+    /// `p` is the coordinate of the top left corner of the rectangle.
+    /// 
+    /// The radius is clamped to half the smaller dimension, so a large radius gives a pill shape.
+    pub fn rounded_rect<A, B>(&mut self, p: A, s: B, radius: i32, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let (p, spans) = rounded_rect_spans(p.into(), s.into(), radius);
+        for (y, &(x1, x2)) in spans.iter().enumerate() {
+            self.hspan(p.x + x1, p.x + x2, p.y + y as i32, c);
+        }
+    }
+
+
+    /// Same as `rounded_rect` but draws only the outline of the rectangle.
+    pub fn rounded_rect_boundary<A, B>(&mut self, p: A, s: B, radius: i32, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let (p, spans) = rounded_rect_spans(p.into(), s.into(), radius);
+        for (y, &(x1, x2)) in spans.iter().enumerate() {
+            // a pixel is on the outline if one of the rows above or below does not cover it
+            let (above, below) = match (y.checked_sub(1).map(|i| spans[i]), spans.get(y + 1)) {
+                (Some(above), Some(&below)) => (above, below),
+                _ => {
+                    self.hspan(p.x + x1, p.x + x2, p.y + y as i32, c);
+                    continue;
+                }
+            };
+            let left  = above.0.max(below.0);
+            let right = above.1.min(below.1);
+            self.hspan(p.x + x1, p.x + left.max(x1 + 1) - 1, p.y + y as i32, c);
+            self.hspan(p.x + right.min(x2 - 1) + 1, p.x + x2, p.y + y as i32, c);
+        }
+    }
+
+
     /// Sets all the pixels' color in the screen to `c`.
     pub fn clear(&mut self, c: Color) {
-        for i in 0..self.data.len() {
-            self.data[i] = c;
+        if self.clip.is_none() {
+            self.data.fill(c);
+            return;
+        }
+        let area = self.drawable();
+        for y in area.pos.y..area.end().y {
+            self.hspan(area.pos.x, area.end().x - 1, y, c);
+        }
+    }
+
+
+    /// Fills the whole image with a gradient going from `a` at `start` to `b` at `end`.
+    /// 
+    /// The color of a pixel is given by its projection on the `start`-`end` axis, pixels before `start`
+    /// are of color `a` and pixels after `end` of color `b`.
+    pub fn gradient_linear<A, B>(&mut self, start: A, end: B, a: Color, b: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let start = start.into();
+        let axis = end.into() - start;
+        let len2 = axis.x as f32 * axis.x as f32 + axis.y as f32 * axis.y as f32;
+
+        let area = self.drawable();
+        for y in area.pos.y..area.end().y {
+            for x in area.pos.x..area.end().x {
+                let t = if len2 > 0.0 {
+                    ((x - start.x) as f32 * axis.x as f32 + (y - start.y) as f32 * axis.y as f32) / len2
+                } else {
+                    0.0
+                };
+                self.data[(y * self.size.x + x) as usize] = a.lerp(b, t);
+            }
+        }
+    }
+
+
+    /// Fills the whole image with a gradient going from `inner` at `center` to `outer` at distance
+    /// `radius` of the center and beyond.
+    pub fn gradient_radial<A>(&mut self, center: A, radius: i32, inner: Color, outer: Color)
+        where A: Into<Vec2>
+    {
+        let center = center.into();
+
+        let area = self.drawable();
+        for y in area.pos.y..area.end().y {
+            for x in area.pos.x..area.end().x {
+                let dx = (x - center.x) as f32;
+                let dy = (y - center.y) as f32;
+                let t = if radius > 0 {
+                    (dx * dx + dy * dy).sqrt() / radius as f32
+                } else {
+                    1.0
+                };
+                self.data[(y * self.size.x + x) as usize] = inner.lerp(outer, t);
+            }
+        }
+    }
+
+
+    /// Draws `text` with the built-in 5x7 font, `p` being the top left corner of the first character.
+    /// 
+    /// Characters are separated by one pixel and lines by one pixel. Characters that are not in
+    /// the font are drawn as a box.
+    pub fn draw_text<A>(&mut self, p: A, text: &str, c: Color)
+        where A: Into<Vec2>
+    {
+        let p = p.into();
+        let mut pos = p;
+        for ch in text.chars() {
+            if ch == '\n' {
+                pos = vec2!(p.x, pos.y + GLYPH_HEIGHT + 1);
+                continue;
+            }
+
+            let glyph = match ch {
+                ' '..='~' => &FONT_5X7[ch as usize - ' ' as usize],
+                _ => &PLACEHOLDER_GLYPH
+            };
+            for (i, column) in glyph.iter().enumerate() {
+                for j in 0..GLYPH_HEIGHT {
+                    if column & (1 << j) != 0 {
+                        self.set_pixel(vec2!(pos.x + i as i32, pos.y + j), c);
+                    }
+                }
+            }
+            pos.x += GLYPH_WIDTH + 1;
+        }
+    }
+
+
+    /// Draws `text` with `font`, `p` being the top left corner of the first character. The pixels of the
+    /// glyphs that are not of the colorkey of the font are drawn with the color `c`.
+    pub fn draw_text_font<A>(&mut self, p: A, text: &str, font: &Font, c: Color)
+        where A: Into<Vec2>
+    {
+        let p = p.into();
+        let mut pos = p;
+        for ch in text.chars() {
+            if ch == '\n' {
+                pos = vec2!(p.x, pos.y + font.glyph_size.y);
+                continue;
+            }
+
+            if let Some(origin) = font.glyph_origin(ch) {
+                for j in 0..font.glyph_size.y {
+                    for i in 0..font.glyph_size.x {
+                        if Some(font.sheet[origin + vec2!(i, j)]) != font.colorkey {
+                            self.set_pixel(pos + vec2!(i, j), c);
+                        }
+                    }
+                }
+            }
+            pos.x += font.glyph_size.x;
         }
     }
 
 
     fn plot_ellipse_points<A, B>(&mut self, center: A, pos: B, c: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
     {
-        let center = center.as_ref();
-        let pos    = pos.as_ref();
-        self[(center.x + pos.x, center.y + pos.y)] = c;
-        self[(center.x + pos.x, center.y - pos.y)] = c;
-        self[(center.x - pos.x, center.y + pos.y)] = c;
-        self[(center.x - pos.x, center.y - pos.y)] = c;
+        let center: Vec2 = center.into();
+        let pos: Vec2    = pos.into();
+        self.set_pixel((center.x + pos.x, center.y + pos.y), c);
+        self.set_pixel((center.x + pos.x, center.y - pos.y), c);
+        self.set_pixel((center.x - pos.x, center.y + pos.y), c);
+        self.set_pixel((center.x - pos.x, center.y - pos.y), c);
     }
 
 
     /// Draws an ellipse of color `col`. `c` is the center of the ellipse and `s` is the size of the rectangle
     /// in which the ellipse is inscribed.
     pub fn ellipse_boundary<A, B>(&mut self, center: A, size: B, c: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
     {
-        let center = center.as_ref();
-        let size   = size.as_ref();
+        let center: Vec2 = center.into();
+        ellipse_quadrant(size.into(), |x, y| self.plot_ellipse_points(center, (x, y), c));
+    }
 
-        let a = size.x / 2;
-        let b = size.y / 2;
 
-        //prepare to plot in the first region
-        let mut x = 0;
-        let mut y = b;
-        let mut p    = b * b + (a * a * (1 - 4*b) - 2) / 4;
-        let mut dpe  = 3 * b * b;
-        let mut dpse  = dpe - 2 * a * a * (b - 1);
-        let d2pe  = 2 * b * b;
-        let d2pse = d2pe + 2 * a * a;
-
-        //plot in the first region
-        self.plot_ellipse_points(center, (x, y), c);
-        while dpse < 2 * a * a + 3 * b * b {
-            if p < 0 { //east
-                p    += dpe;
-                dpe  += d2pe;
-                dpse += d2pe;
-            } else {     //south-east
-                p    += dpse;
-                dpe  += d2pe;
-                dpse += d2pse;
-                y -= 1;
+    /// Draws the part of the outline of an ellipse between the angles `start_deg` and `end_deg`, in degrees.
+    /// `center` and `size` are the same as for `ellipse_boundary`.
+    /// 
+    /// Angles are measured from the center, counterclockwise on the screen from the right (90 is the
+    /// top of the ellipse). The arc goes counterclockwise from `start_deg` to `end_deg`: angles are taken
+    /// modulo 360, so when `end_deg` is less than `start_deg` the arc goes the long way around, eg. from
+    /// 90 to 0 draws three quarters of the ellipse. The whole ellipse is drawn if `end_deg - start_deg`
+    /// is at least 360.
+    pub fn arc<A, B>(&mut self, center: A, size: B, start_deg: f32, end_deg: f32, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let center = center.into();
+        let sweep = end_deg - start_deg;
+        if sweep >= 360.0 {
+            return self.ellipse_boundary(center, size, c);
+        }
+        let sweep = sweep.rem_euclid(360.0);
+
+        ellipse_quadrant(size.into(), |x, y| {
+            for (dx, dy) in [(x, y), (x, -y), (-x, y), (-x, -y)] {
+                // the y axis goes down on the screen
+                let angle = (-dy as f32).atan2(dx as f32).to_degrees();
+                if (angle - start_deg).rem_euclid(360.0) <= sweep {
+                    self.set_pixel((center.x + dx, center.y + dy), c);
+                }
             }
-            x += 1;
-            self.plot_ellipse_points(center, (x, y), c);
-        }
-
-        //prepare to plot in the second region
-        let mut p    = p - (a * a * (4 * y - 3) + b * b * (4 * x + 3) + 2) / 4;
-        let mut dpse = 2 * b * b + 3 * a * a;
-        let dps  = a * a * (3 - 2 * y);
-        let d2ps = 2 * a * a;
-
-        //plot in the second region
-        while y > 0 {
-            if p > 0 { //south
-                p    += dps;
-                dpe  += d2ps;
-                dpse += d2ps;
-            } else {     //south-east
-                p    += dpse;
-                dpe  += d2ps;
-                dpse += d2pse;
-                x += 1;
+        });
+    }
+
+
+    /// Draws a filled ellipse of color `c`. `center` is the center of the ellipse and `size` is the size of
+    /// the rectangle in which the ellipse is inscribed (as for `ellipse_boundary`, odd sizes are rounded down).
+    pub fn ellipse<A, B>(&mut self, center: A, size: B, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        let center: Vec2 = center.into();
+        let size: Vec2   = size.into();
+
+        let a = (size.x / 2).abs() as i64;
+        let b = (size.y / 2).abs() as i64;
+
+        for dy in -b..=b {
+            // largest dx such that (dx/a)^2 + (dy/b)^2 <= 1
+            let half_width = if b == 0 {
+                a
+            } else {
+                let rhs = a * a * (b * b - dy * dy);
+                let mut dx = ((rhs as f64).sqrt() / b as f64) as i64;
+                while (dx + 1) * (dx + 1) * b * b <= rhs { dx += 1; }
+                while dx > 0 && dx * dx * b * b > rhs { dx -= 1; }
+                dx
+            };
+            self.hspan(center.x - half_width as i32, center.x + half_width as i32, center.y + dy as i32, c);
+        }
+    }
+
+
+    /// Draws the outline of the polygon with vertices `points`, the last point is connected to the first.
+    pub fn polygon_boundary(&mut self, points: &[Vec2], c: Color) {
+        for (i, p) in points.iter().enumerate() {
+            self.line(p, points[(i + 1) % points.len()], c);
+        }
+    }
+
+
+    /// Draws the polygon with vertices `points` filled with `c`.
+    /// 
+    /// Pixels are filled when their center is inside the polygon according to the even-odd rule,
+    /// so concave and self-intersecting polygons are supported.
+    pub fn polygon(&mut self, points: &[Vec2], c: Color) {
+        if points.len() < 3 {
+            return self.polygon_boundary(points, c);
+        }
+
+        let min_y = points.iter().map(|p| p.y).min().unwrap().max(0);
+        let max_y = points.iter().map(|p| p.y).max().unwrap().min(self.size.y);
+
+        let mut crossings = Vec::new();
+        for y in min_y..max_y {
+            let yc = y as f64 + 0.5;
+
+            crossings.clear();
+            for (i, p1) in points.iter().enumerate() {
+                let p2 = points[(i + 1) % points.len()];
+                let (y1, y2) = (p1.y as f64, p2.y as f64);
+                if (y1 <= yc) != (y2 <= yc) {
+                    crossings.push(p1.x as f64 + (yc - y1) * (p2.x - p1.x) as f64 / (y2 - y1));
+                }
             }
-            y -= 1;
-            self.plot_ellipse_points(center, (x, y), c);
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            for span in crossings.chunks_exact(2) {
+                // pixels whose center is in [span[0], span[1])
+                let x1 = (span[0] - 0.5).ceil() as i32;
+                let x2 = (span[1] - 0.5).ceil() as i32 - 1;
+                self.hspan(x1, x2, y, c);
+            }
+        }
+    }
+
+
+    /// Sets the pixels from `x1` to `x2` (included) on line `y` to `c`, clipping to the image and to the
+    /// clip rectangle.
+    fn hspan(&mut self, x1: i32, x2: i32, y: i32, c: Color) {
+        let area = self.drawable();
+        if y < area.pos.y || y >= area.end().y {
+            return;
         }
+        let x1 = x1.max(area.pos.x);
+        let x2 = x2.min(area.end().x - 1);
+        if x1 > x2 {
+            return;
+        }
+        let line = (y * self.size.x) as usize;
+        self.data[line + x1 as usize..=line + x2 as usize].fill(c);
     }
 
 
@@ -494,35 +1561,26 @@ impl Image {
     /// 
     /// Negative size results in flipped image. Alpha is used to ignore a given color while drawing.
     pub fn image<A, B, C>(&mut self, img: &Image, pos: A, size: B, offset: C, alpha: Option<Color>) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>, C: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>, C: Into<Vec2>
     {
-        let offset = offset.as_ref();
-        let mut p = *pos.as_ref();
-        let mut s = *size.as_ref();
-
-        if p.x < 0 {
-            s.x += p.x - 1;
-            p.x = 0;
-        }
-        if p.y < 0 {
-            s.y += p.y - 1;
-            p.y = 0;
-        }
-
+        let offset: Vec2 = offset.into();
+        let p = pos.into();
+        let s = size.into();
 
         let dx = if s.x > 0 {1} else {-1};
         let dy = if s.y > 0 {1} else {-1};
 
 
+        // pixels outside of the screen are clipped by `set_pixel`
         for j in 0..(s.y.abs()) {
             let y = p.y + j * dy;
             let src_y = offset.y + j;
-            if y >= self.size.y {break}
+            if src_y < 0 || y < 0 || y >= self.size.y {continue}
             if src_y >= img.size.y {break}
             for i in 0..(s.x.abs()) {
                 let x = p.x + i * dx;
                 let src_x = offset.x + i;
-                if x >= self.size.x {break}
+                if src_x < 0 {continue}
                 if src_x >= img.size.x {break}
 
                 let pos = vec2!(x, y);
@@ -533,7 +1591,66 @@ impl Image {
                         continue;
                     }
                 }
-                self[pos] = img[src_pos];
+                self.set_pixel(pos, img[src_pos]);
+            }
+        }
+    }
+
+
+    /// Draws the whole image `src` at `dest`, scaled up by the integer `factor` with nearest-neighbor sampling.
+    /// Each pixel of `src` becomes a `factor`x`factor` square, which keeps pixel art crisp.
+    /// 
+    /// Panics if `factor` is less than 1.
+    pub fn blit_scaled_int<A>(&mut self, src: &Image, dest: A, factor: i32)
+        where A: Into<Vec2>
+    {
+        assert!(factor >= 1, "Cannot scale an image by a factor of {}", factor);
+        let dest = dest.into();
+
+        for j in 0..src.size.y {
+            for i in 0..src.size.x {
+                let c = src[(i, j)];
+                let x = dest.x + i * factor;
+                for y in (dest.y + j * factor)..(dest.y + (j + 1) * factor) {
+                    self.hspan(x, x + factor - 1, y, c);
+                }
+            }
+        }
+    }
+
+
+    /// Copies the region `src_rect` (position and size) of `src` to `dst`. Pixels of color `colorkey`
+    /// are not copied.
+    /// 
+    /// The region is clipped to the bounds of both images.
+    pub fn blit<A>(&mut self, src: &Image, src_rect: (Vec2, Vec2), dst: A, colorkey: Option<Color>)
+        where A: Into<Vec2>
+    {
+        let (mut src_pos, mut size) = src_rect;
+        let mut dst = dst.into();
+
+        // clip to the source
+        let start = vec2!((-src_pos.x).max(0), (-src_pos.y).max(0));
+        src_pos += start;
+        dst += start;
+        size -= start;
+        size.x = size.x.min(src.size.x - src_pos.x);
+        size.y = size.y.min(src.size.y - src_pos.y);
+
+        // clip to the destination
+        let start = vec2!((-dst.x).max(0), (-dst.y).max(0));
+        src_pos += start;
+        dst += start;
+        size -= start;
+        size.x = size.x.min(self.size.x - dst.x);
+        size.y = size.y.min(self.size.y - dst.y);
+
+        for j in 0..size.y {
+            for i in 0..size.x {
+                let c = src[(src_pos.x + i, src_pos.y + j)];
+                if Some(c) != colorkey {
+                    self.set_pixel((dst.x + i, dst.y + j), c);
+                }
             }
         }
     }
@@ -546,7 +1663,7 @@ impl Image {
     /// <image>.image(img, pos, img.size(), Vec2::ZERO, Some(alpha));
     /// ```
     pub fn whole_image_alpha<A>(&mut self, img: &Image, pos: A, alpha: Color) 
-        where A: AsRef<Vec2>
+        where A: Into<Vec2>
     {
         self.image(img, pos, img.size(), Vec2::ZERO, Some(alpha));
     }
@@ -559,18 +1676,199 @@ impl Image {
     /// <image>.image(img, pos, img.size(), Vec2::ZERO, None);
     /// ```
     pub fn whole_image<A>(&mut self, img: &Image, pos: A) 
-        where A: AsRef<Vec2>
+        where A: Into<Vec2>
     {
         self.image(img, pos, img.size(), Vec2::ZERO, None);
     }
 }
 
 
-impl<A: AsRef<Vec2>> Index<A> for Image {
+/// Bitmap font made from a sprite sheet, to be used with `Image::draw_text_font`.
+/// 
+/// The glyphs are laid out in a grid, from left to right and top to bottom, starting with `first_char`
+/// and following the order of the unicode code points.
+/// 
+/// ```no_run
+/// # use termkan::vec2;
+/// # use termkan::math::Vec2;
+/// # use termkan::img::{Font, Image};
+/// // 8x8 glyphs from ' ' to '~', with a black background
+/// let font = Font::from_sheet(Image::load("font.png").unwrap(), vec2!(8, 8), ' ');
+/// ```
+#[derive(Clone, Debug)]
+pub struct Font {
+    sheet: Image,
+    glyph_size: Vec2,
+    first_char: char,
+    colorkey: Option<Color>
+}
+
+
+impl Font {
+
+    /// Creates a font from the sprite sheet `img`, whose glyphs are of size `glyph_size`.
+    /// 
+    /// The background of the glyphs is black by default (see `with_colorkey`).
+    pub fn from_sheet<A>(img: Image, glyph_size: A, first_char: char) -> Font
+        where A: Into<Vec2>
+    {
+        Font {
+            sheet: img,
+            glyph_size: glyph_size.into(),
+            first_char,
+            colorkey: Some(Color::BLACK)
+        }
+    }
+
+
+    /// Sets the color of the background of the glyphs, that is not drawn. With None, the whole glyph is drawn.
+    pub fn with_colorkey(mut self, colorkey: Option<Color>) -> Font {
+        self.colorkey = colorkey;
+        self
+    }
+
+
+    /// Returns the size of the glyphs.
+    pub fn glyph_size(&self) -> Vec2 {
+        self.glyph_size
+    }
+
+
+    /// Returns the position of the glyph of `ch` in the sheet, None if the sheet does not contain it.
+    fn glyph_origin(&self, ch: char) -> Option<Vec2> {
+        if self.glyph_size.x <= 0 || self.glyph_size.y <= 0 {
+            return None;
+        }
+        let columns = self.sheet.size().x / self.glyph_size.x;
+        let rows = self.sheet.size().y / self.glyph_size.y;
+        let index = (ch as u32).checked_sub(self.first_char as u32)? as i64;
+        if columns == 0 || index >= columns as i64 * rows as i64 {
+            return None;
+        }
+        let index = index as i32;
+        Some(vec2!(index % columns * self.glyph_size.x, index / columns * self.glyph_size.y))
+    }
+}
+
+
+/// Traces the outline of an ellipse inscribed in a rectangle of size `size` (odd sizes are rounded down),
+/// calling `plot` with the offsets from the center of the points of the quarter where both are positive.
+fn ellipse_quadrant<F>(size: Vec2, mut plot: F)
+    where F: FnMut(i32, i32)
+{
+    let a = size.x / 2;
+    let b = size.y / 2;
+
+    //prepare to plot in the first region
+    let mut x = 0;
+    let mut y = b;
+    let mut p    = b * b + (a * a * (1 - 4*b) - 2) / 4;
+    let mut dpe  = 3 * b * b;
+    let mut dpse  = dpe - 2 * a * a * (b - 1);
+    let d2pe  = 2 * b * b;
+    let d2pse = d2pe + 2 * a * a;
+
+    //plot in the first region
+    plot(x, y);
+    while dpse < 2 * a * a + 3 * b * b {
+        if p < 0 { //east
+            p    += dpe;
+            dpe  += d2pe;
+            dpse += d2pe;
+        } else {     //south-east
+            p    += dpse;
+            dpe  += d2pe;
+            dpse += d2pse;
+            y -= 1;
+        }
+        x += 1;
+        plot(x, y);
+    }
+
+    //prepare to plot in the second region
+    let mut p    = p - (a * a * (4 * y - 3) + b * b * (4 * x + 3) + 2) / 4;
+    let mut dpse = 2 * b * b + 3 * a * a;
+    let dps  = a * a * (3 - 2 * y);
+    let d2ps = 2 * a * a;
+
+    //plot in the second region
+    while y > 0 {
+        if p > 0 { //south
+            p    += dps;
+            dpe  += d2ps;
+            dpse += d2ps;
+        } else {     //south-east
+            p    += dpse;
+            dpe  += d2ps;
+            dpse += d2pse;
+            x += 1;
+        }
+        y -= 1;
+        plot(x, y);
+    }
+}
+
+
+/// A surface that can be drawn on, such as an Image or the Renderer.
+/// 
+/// This allows to write drawing code (eg. widgets) that works with any of them.
+pub trait Canvas {
+
+    /// Returns the size of the canvas.
+    fn size(&self) -> Vec2;
+
+    /// Sets the color of the pixel at `p` to `c`.
+    fn draw_point(&mut self, p: Vec2, c: Color);
+
+    /// Draws a line of color `c` between `p1` and `p2`.
+    fn draw_line(&mut self, p1: Vec2, p2: Vec2, c: Color);
+
+    /// Draws a rectangle of color `c` and of size `s`, `p` is the coordinate of its top left corner.
+    fn draw_rect(&mut self, p: Vec2, s: Vec2, c: Color);
+
+    /// Same as `draw_rect` but draws only the four sides of the rectangle.
+    fn draw_rect_boundary(&mut self, p: Vec2, s: Vec2, c: Color);
+}
+
+
+impl PartialEq for Image {
+
+    /// Images are equal if they have the same pixels, whatever their clip rectangles.
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && self.data == other.data
+    }
+}
+
+
+impl Canvas for Image {
+
+    fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    fn draw_point(&mut self, p: Vec2, c: Color) {
+        self.point(p, c);
+    }
+
+    fn draw_line(&mut self, p1: Vec2, p2: Vec2, c: Color) {
+        self.line(p1, p2, c);
+    }
+
+    fn draw_rect(&mut self, p: Vec2, s: Vec2, c: Color) {
+        self.rect(p, s, c);
+    }
+
+    fn draw_rect_boundary(&mut self, p: Vec2, s: Vec2, c: Color) {
+        self.rect_boudary(p, s, c);
+    }
+}
+
+
+impl<A: Into<Vec2>> Index<A> for Image {
     type Output = Color;
 
     fn index(&self, p: A) -> &Self::Output {
-        let p = p.as_ref();
+        let p: Vec2 = p.into();
         if !self.is_out_of_range(p) {
             &self.data[(p.x + p.y * self.size.x) as usize]
         } else {
@@ -580,16 +1878,15 @@ impl<A: AsRef<Vec2>> Index<A> for Image {
 }
 
 
-impl<A: AsRef<Vec2>> IndexMut<A> for Image {
+impl<A: Into<Vec2>> IndexMut<A> for Image {
 
+    /// Panics if `p` is out of range, use `set_pixel` to ignore writes out of the image.
     fn index_mut(&mut self, p: A) -> &mut Self::Output {
-        static mut TEMP: Color = Color::BLACK;
-        let p = p.as_ref();
+        let p: Vec2 = p.into();
 
-        if !self.is_out_of_range(p) {
-            &mut self.data[(p.x + p.y * self.size.x) as usize]
-        } else {
-            unsafe { &mut TEMP } // NOT GOOD, ignore index out of range
+        if self.is_out_of_range(p) {
+            panic!("pixel {:?} out of range of image of size {:?}", p, self.size);
         }
+        &mut self.data[(p.x + p.y * self.size.x) as usize]
     }
 }
\ No newline at end of file
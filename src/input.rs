@@ -27,26 +27,251 @@
 // HEAVELY inspired by the termion library
 
 
-use std::io::{Error, ErrorKind};
+use std::io::{self, Error, ErrorKind};
 use std::str;
+use std::cell::Cell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
 
 // use std::{fs, io};
-use std::io::{Read, Write, stdin, stdout};
+use std::io::{BufReader, Read, Write, stdin, stdout};
 
 use std::thread;
-use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+#[cfg(unix)]
+use std::sync::{OnceLock, Weak};
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
 
 use crate::math::Vec2;
+use crate::rds::{lock_singleton, PlatformTerminal, TerminalBackend};
+#[cfg(unix)]
+use crate::rds::Renderer;
 
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InputEvent {
     Key(KeyEvent),
+    /// A special key (arrows, Home, Delete, function keys...) pressed while holding modifiers.
+    ModifiedKey(KeyEvent, Modifiers),
+    /// A key was released, with the modifiers held at that time. Only reported by terminals supporting
+    /// the kitty keyboard protocol (see `Input::enable_kitty_keyboard`).
+    KeyReleased(KeyEvent, Modifiers),
     Mouse(MouseEvent),
+    /// The terminal was resized, carries the new size of the screen (see `Renderer::get_size`).
+    Resize(Vec2),
+    /// Reply of the terminal to a DECRQM query (see `Input::query_mode`).
+    ModeReport(u16, ModeStatus),
+    /// Reply of the terminal to a cursor position query (see `Input::query_cursor_position`): the
+    /// column and the row of the cursor, starting from 0.
+    CursorPosition(Vec2),
+    /// Text pasted while bracketed paste is enabled (see `Input::enable_bracketed_paste`). Line breaks
+    /// are normalized to `\n`.
+    Paste(String),
+    /// The terminal window gained the focus (see `Input::enable_focus_events`).
+    FocusGained,
+    /// The terminal window lost the focus.
+    FocusLost,
+    /// Bytes the parser does not understand, like an unknown escape sequence. Lets applications
+    /// recognize sequences specific to their terminal.
     Unsupported(Vec<u8>)
 }
 
 
+/// Modifier keys held while a key was pressed.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool
+}
+
+
+impl Modifiers {
+
+    /// Decodes the modifier parameter of xterm sequences (`ESC [ 1 ; 5 C` for Ctrl+Right): 1 plus
+    /// 1 for Shift, 2 for Alt, 4 for Ctrl and 8 for Meta (reported as Alt).
+    fn from_xterm(param: u16) -> Self {
+        let bits = param.saturating_sub(1);
+        Self {
+            shift: bits & 1 != 0,
+            alt: bits & (2 | 8) != 0,
+            ctrl: bits & 4 != 0
+        }
+    }
+
+
+    /// Decodes the modifier parameter of the kitty keyboard protocol: 1 plus 1 for Shift, 2 for Alt,
+    /// 4 for Ctrl and 32 for Meta (reported as Alt). Super, Hyper and the lock keys are ignored.
+    fn from_kitty(param: u16) -> Self {
+        let bits = param.saturating_sub(1);
+        Self {
+            shift: bits & 1 != 0,
+            alt: bits & (2 | 32) != 0,
+            ctrl: bits & 4 != 0
+        }
+    }
+
+
+    /// Decodes the modifiers of the button byte of mouse sequences: 4 for Shift, 8 for Meta
+    /// (reported as Alt) and 16 for Ctrl.
+    fn from_mouse(cb: u16) -> Self {
+        Self {
+            shift: cb & 4 != 0,
+            alt: cb & 8 != 0,
+            ctrl: cb & 16 != 0
+        }
+    }
+
+
+    /// Returns true if no modifier is held.
+    pub fn is_empty(&self) -> bool {
+        !(self.shift || self.ctrl || self.alt)
+    }
+}
+
+
+/// Bits of the button byte of mouse sequences used by the modifiers (see `Modifiers::from_mouse`).
+const MOUSE_MODIFIER_BITS: u16 = 4 | 8 | 16;
+
+
+/// Makes the event of `key` pressed with the modifiers encoded by `param` (see `Modifiers::from_xterm`).
+fn modified_key(key: KeyEvent, param: Option<u16>) -> InputEvent {
+    match param.map(Modifiers::from_xterm) {
+        Some(modifiers) if !modifiers.is_empty() => InputEvent::ModifiedKey(key, modifiers),
+        _ => InputEvent::Key(key)
+    }
+}
+
+
+/// Function and editing keys of the `ESC [ nn ~` encoding, shared by xterm, the linux console and rxvt:
+///
+/// - 1 or 7: Home, 2: Insert, 3: Delete, 4 or 8: End, 5: PageUp, 6: PageDown
+/// - 11-15: F1-F5, 17-21: F6-F10, 23-24: F11-F12
+/// - 25-26: F13-F14, 28-29: F15-F16, 31-34: F17-F20
+///
+/// xterm only sends 15-24 (it uses `ESC O P..S` for F1-F4), rxvt sends 11-14 for F1-F4 and the
+/// linux console sends `ESC [ [ A..E` for F1-F5. F13-F20 are the shifted F3-F10 of rxvt and the
+/// shifted F1-F8 of the linux console.
+fn tilde_key(n: u16) -> Option<KeyEvent> {
+    Some(match n {
+        1 | 7 => KeyEvent::Home,
+        2 => KeyEvent::Insert,
+        3 => KeyEvent::Delete,
+        4 | 8 => KeyEvent::End,
+        5 => KeyEvent::PageUp,
+        6 => KeyEvent::PageDown,
+        v @ 11..=15 => KeyEvent::F(v as u8 - 10),
+        v @ 17..=21 => KeyEvent::F(v as u8 - 11),
+        v @ 23..=26 => KeyEvent::F(v as u8 - 12),
+        v @ 28..=29 => KeyEvent::F(v as u8 - 13),
+        v @ 31..=34 => KeyEvent::F(v as u8 - 14),
+        _ => return None,
+    })
+}
+
+
+/// Makes the event of `key` pressed or released with the modifiers encoded by `param`.
+fn key_event(key: KeyEvent, param: Option<u16>, released: bool) -> InputEvent {
+    if released {
+        InputEvent::KeyReleased(key, Modifiers::from_xterm(param.unwrap_or(1)))
+    } else {
+        modified_key(key, param)
+    }
+}
+
+
+/// Parses the semicolon-separated numbers of a key sequence. The second one, the modifiers, can be
+/// followed by the event type (`Pm:Pe`) with the kitty keyboard protocol, 3 meaning that the key was
+/// released.
+///
+/// Returns the numbers and true if the key was released.
+fn key_params(s: &str) -> Option<(Vec<u16>, bool)> {
+    let mut released = false;
+    let nums = s.split(';').enumerate().map(|(i, n)| match n.split_once(':') {
+        Some((n, event)) if i == 1 => {
+            released = event.parse::<u16>().ok()? == 3;
+            n.parse().ok()
+        }
+        _ => n.parse().ok()
+    }).collect::<Option<_>>()?;
+    Some((nums, released))
+}
+
+
+/// Decodes a kitty keyboard protocol sequence: ESC [ code[:alternates] ; modifiers[:event] [; text] u
+fn parse_kitty_key(s: &str) -> Option<InputEvent> {
+    let mut fields = s.split(';');
+    let code = fields.next()?.split(':').next()?.parse::<u32>().ok()?;
+    let (param, event) = match fields.next() {
+        Some(mods) => match mods.split_once(':') {
+            Some((m, e)) => (m.parse::<u16>().ok()?, e.parse::<u16>().ok()?),
+            None => (mods.parse::<u16>().ok()?, 1),
+        },
+        None => (1, 1),
+    };
+
+    let key = match code {
+        9 => KeyEvent::Char('\t'),
+        13 => KeyEvent::Char('\n'),
+        27 => KeyEvent::Esc,
+        127 => KeyEvent::Backspace,
+        // F13-F35, from the private use area
+        c @ 57376..=57398 => KeyEvent::F((c - 57376 + 13) as u8),
+        c => KeyEvent::Char(char::from_u32(c)?),
+    };
+    let mut modifiers = Modifiers::from_kitty(param);
+
+    // the keys the legacy encoding reports without modifiers are reported the same way
+    let key = match key {
+        KeyEvent::Char(c) if modifiers == (Modifiers { ctrl: true, ..Modifiers::default() }) => {
+            modifiers.ctrl = false;
+            KeyEvent::Ctrl(c)
+        }
+        KeyEvent::Char(c) if modifiers == (Modifiers { alt: true, ..Modifiers::default() }) => {
+            modifiers.alt = false;
+            KeyEvent::Alt(c)
+        }
+        KeyEvent::Char(c) if modifiers == (Modifiers { shift: true, ..Modifiers::default() }) && c.is_ascii_lowercase() => {
+            modifiers.shift = false;
+            KeyEvent::Char(c.to_ascii_uppercase())
+        }
+        key => key
+    };
+
+    Some(match event {
+        3 => InputEvent::KeyReleased(key, modifiers),
+        _ if modifiers.is_empty() => InputEvent::Key(key),
+        _ => InputEvent::ModifiedKey(key, modifiers),
+    })
+}
+
+
+/// State of a terminal mode, as reported by the terminal in reply to a DECRQM query.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ModeStatus {
+    NotRecognized,
+    Set,
+    Reset,
+    PermanentlySet,
+    PermanentlyReset
+}
+
+
+impl ModeStatus {
+
+    /// Returns true if the mode is enabled.
+    pub fn is_set(&self) -> bool {
+        matches!(self, ModeStatus::Set | ModeStatus::PermanentlySet)
+    }
+}
+
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum KeyEvent {
     Backspace,
@@ -61,7 +286,7 @@ pub enum KeyEvent {
     BackTab, // Shift + Tab
     Delete,
     Insert,
-    F(u8),   // Only some function keys are reachable
+    F(u8),   // F1-F20 with the legacy encodings, up to F35 with the kitty keyboard protocol
     Char(char),
     Alt(char),
     Ctrl(char),
@@ -70,12 +295,133 @@ pub enum KeyEvent {
 }
 
 
-// TODO: add modifiers (shift, ctrl, meta) to MouseEvent
+/// Names of the keys without parameters, as used by the Display impl and `KeyEvent::parse`.
+const KEY_NAMES: [(KeyEvent, &str); 16] = [
+    (KeyEvent::Backspace, "Backspace"),
+    (KeyEvent::Left,      "Left"),
+    (KeyEvent::Right,     "Right"),
+    (KeyEvent::Up,        "Up"),
+    (KeyEvent::Down,      "Down"),
+    (KeyEvent::Home,      "Home"),
+    (KeyEvent::End,       "End"),
+    (KeyEvent::PageUp,    "PageUp"),
+    (KeyEvent::PageDown,  "PageDown"),
+    (KeyEvent::BackTab,   "BackTab"),
+    (KeyEvent::Delete,    "Delete"),
+    (KeyEvent::Insert,    "Insert"),
+    (KeyEvent::Null,      "Null"),
+    (KeyEvent::Esc,       "Esc"),
+    (KeyEvent::Char('\n'), "Enter"),
+    (KeyEvent::Char('\t'), "Tab")
+];
+
+
+impl KeyEvent {
+
+    /// Parses a key from a textual spec, such as `"Ctrl+c"`, `"Alt+x"`, `"Up"`, `"F5"` or `"a"`.
+    /// 
+    /// Key names and modifiers are case insensitive, characters are not. `"Space"`, `"Enter"` and `"Tab"`
+    /// can be used for the corresponding characters. This is the inverse of the Display impl.
+    pub fn parse(spec: &str) -> Result<KeyEvent, KeyParseError> {
+        let unknown = || KeyParseError::UnknownKey(spec.to_string());
+
+        if let Some((modifier, key)) = spec.split_once('+').filter(|(m, k)| !m.is_empty() && !k.is_empty()) {
+            let ch = match parse_key_char(key) {
+                Some(ch) => ch,
+                None => return Err(unknown())
+            };
+            return match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => Ok(KeyEvent::Ctrl(ch)),
+                "alt"  => Ok(KeyEvent::Alt(ch)),
+                _ => Err(KeyParseError::UnknownModifier(modifier.to_string()))
+            };
+        }
+
+        if let Some(&(key, _)) = KEY_NAMES.iter().find(|(_, name)| name.eq_ignore_ascii_case(spec)) {
+            return Ok(key);
+        }
+
+        if let Some(n) = spec.strip_prefix(['F', 'f']).and_then(|n| n.parse::<u8>().ok()) {
+            if n > 0 {
+                return Ok(KeyEvent::F(n));
+            }
+        }
+
+        parse_key_char(spec).map(KeyEvent::Char).ok_or_else(unknown)
+    }
+}
+
+
+/// Parses the character part of a key spec.
+fn parse_key_char(s: &str) -> Option<char> {
+    if s.eq_ignore_ascii_case("space") {
+        return Some(' ');
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None
+    }
+}
+
+
+impl fmt::Display for KeyEvent {
+
+    /// Writes the key spec of the key, that can be parsed back with `KeyEvent::parse`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key_char = |ch: char| if ch == ' ' { "Space".to_string() } else { ch.to_string() };
+
+        if let Some((_, name)) = KEY_NAMES.iter().find(|(key, _)| key == self) {
+            return write!(f, "{}", name);
+        }
+        match self {
+            KeyEvent::F(n)     => write!(f, "F{}", n),
+            KeyEvent::Char(ch) => write!(f, "{}", key_char(*ch)),
+            KeyEvent::Alt(ch)  => write!(f, "Alt+{}", key_char(*ch)),
+            KeyEvent::Ctrl(ch) => write!(f, "Ctrl+{}", key_char(*ch)),
+            _ => unreachable!()
+        }
+    }
+}
+
+
+/// Error returned by `KeyEvent::parse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyParseError {
+    /// The spec does not name a known key.
+    UnknownKey(String),
+    /// The modifier before `+` is neither `Ctrl` nor `Alt`.
+    UnknownModifier(String)
+}
+
+
+impl fmt::Display for KeyParseError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyParseError::UnknownKey(spec) => write!(f, "unknown key {:?}", spec),
+            KeyParseError::UnknownModifier(m) => write!(f, "unknown key modifier {:?}", m)
+        }
+    }
+}
+
+
+impl std::error::Error for KeyParseError {}
+
+
+/// Mouse events, with the modifier keys held at the time of the event.
+/// 
+/// The positions are the column and the row of the cell under the mouse, starting at 0. The pixel
+/// at the top left corner of the cell depends on the render mode, see `Renderer::cell_to_pixel`.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum MouseEvent {
-    ButtonPressed(MouseButton, Vec2),
-    ButtonReleased(MouseButton, Vec2),
-    Hold(MouseButton, Vec2)
+    ButtonPressed(MouseButton, Vec2, Modifiers),
+    ButtonReleased(MouseButton, Vec2, Modifiers),
+    Hold(MouseButton, Vec2, Modifiers),
+    /// Movement without any button pressed (see `Input::enable_mouse_motion`).
+    Move(Vec2, Modifiers),
+    /// A notch of the mouse wheel. Unlike buttons, it is never followed by a release.
+    Scroll(ScrollDirection, Vec2, Modifiers)
 }
 
 
@@ -83,61 +429,101 @@ pub enum MouseEvent {
 pub enum MouseButton {
     Left,
     Right,
-    Middle,
-    WheelUp,
-    WheelDown
+    Middle
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ScrollDirection {
+    Up,
+    Down
+}
+
+
+
+/// Returns the cell of the 1-based column `cx` and row `cy` reported by the terminal.
+fn get_mouse_cell(cx: u16, cy: u16) -> Vec2 {
+    vec2!(cx as i32 - 1, cy as i32 - 1)
+}
+
+
+/// Iterator adapter keeping a copy of the bytes read through it.
+struct Recorder<'a, I> {
+    iter: &'a mut I,
+    bytes: Vec<u8>
 }
 
 
+impl<'a, I> Iterator for Recorder<'a, I>
+    where I: Iterator<Item = Result<u8, Error>>
+{
+    type Item = Result<u8, Error>;
 
-fn get_real_mouse_pos(cx: u16, cy: u16) -> Vec2 {
-    vec2!(cx as i32 - 1, 2 * (cy as i32) - 2)
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next();
+        if let Some(Ok(b)) = item {
+            self.bytes.push(b);
+        }
+        item
+    }
 }
 
 
 /// Parse an Event from `item` and possibly subsequent bytes through `iter`.
-fn parse_event<I>(item: u8, iter: &mut I) -> Result<InputEvent, Error>
+///
+/// Bytes that cannot be parsed, like an unknown or truncated escape sequence or an invalid UTF-8
+/// character, are returned as `InputEvent::Unsupported` with all the bytes consumed while trying
+/// to parse them.
+fn parse_event<I>(item: u8, iter: &mut I) -> InputEvent
     where I: Iterator<Item = Result<u8, Error>>
 {
-    let error = Error::new(ErrorKind::Other, "Could not parse an event");
     match item {
         b'\x1B' => {
             // This is an escape character, leading a control sequence.
-            Ok(match iter.next() {
-                Some(Ok(b'O')) => {
-                    match iter.next() {
-                        // F1-F4
-                        Some(Ok(val @ b'P'..=b'S')) => InputEvent::Key(KeyEvent::F(1 + val - b'P')),
-                        _ => return Err(error),
-                    }
-                }
-                Some(Ok(b'[')) => {
-                    // This is a CSI sequence.
-                    parse_csi(iter).ok_or(error)?
-                }
-                Some(Ok(c)) => {
-                    let ch = parse_utf8_char(c, iter)?;
-                    InputEvent::Key(KeyEvent::Alt(ch))
-                }
-                Some(Err(_)) | None => return Err(error),
-            })
+            let mut recorder = Recorder { iter, bytes: vec![item] };
+            parse_escape(&mut recorder).unwrap_or(InputEvent::Unsupported(recorder.bytes))
         }
-        b'\n' | b'\r' => Ok(InputEvent::Key(KeyEvent::Char('\n'))),
-        b'\t' => Ok(InputEvent::Key(KeyEvent::Char('\t'))),
-        b'\x7F' => Ok(InputEvent::Key(KeyEvent::Backspace)),
-        c @ b'\x01'..=b'\x1A' => Ok(InputEvent::Key(KeyEvent::Ctrl((c as u8 - 0x1 + b'a') as char))),
-        c @ b'\x1C'..=b'\x1F' => Ok(InputEvent::Key(KeyEvent::Ctrl((c as u8 - 0x1C + b'4') as char))),
-        b'\0' => Ok(InputEvent::Key(KeyEvent::Null)),
+        b'\n' | b'\r' => InputEvent::Key(KeyEvent::Char('\n')),
+        b'\t' => InputEvent::Key(KeyEvent::Char('\t')),
+        b'\x7F' => InputEvent::Key(KeyEvent::Backspace),
+        c @ b'\x01'..=b'\x1A' => InputEvent::Key(KeyEvent::Ctrl((c as u8 - 0x1 + b'a') as char)),
+        c @ b'\x1C'..=b'\x1F' => InputEvent::Key(KeyEvent::Ctrl((c as u8 - 0x1C + b'4') as char)),
+        b'\0' => InputEvent::Key(KeyEvent::Null),
         c => {
-            Ok({
-                let ch = parse_utf8_char(c, iter)?;
-                InputEvent::Key(KeyEvent::Char(ch))
-            })
+            let mut recorder = Recorder { iter, bytes: vec![c] };
+            match parse_utf8_char(c, &mut recorder) {
+                Ok(ch) => InputEvent::Key(KeyEvent::Char(ch)),
+                Err(_) => InputEvent::Unsupported(recorder.bytes),
+            }
         }
     }
 }
 
 
+/// Parses an escape sequence, just after reading ^[
+///
+/// Returns None if the sequence is unrecognized or malformed.
+fn parse_escape<I>(iter: &mut I) -> Option<InputEvent>
+    where I: Iterator<Item = Result<u8, Error>>
+{
+    Some(match iter.next()?.ok()? {
+        b'O' => match iter.next()?.ok()? {
+            // F1-F4 (xterm, rxvt)
+            val @ b'P'..=b'S' => InputEvent::Key(KeyEvent::F(1 + val - b'P')),
+            // F1-F4 with modifiers, as sent by older xterms and konsole: ESC O Pm (P-S)
+            m @ b'2'..=b'8' => match iter.next()?.ok()? {
+                val @ b'P'..=b'S' => modified_key(KeyEvent::F(1 + val - b'P'), Some((m - b'0') as u16)),
+                _ => return None,
+            },
+            _ => return None,
+        },
+        // This is a CSI sequence.
+        b'[' => parse_csi(iter)?,
+        c => InputEvent::Key(KeyEvent::Alt(parse_utf8_char(c, iter).ok()?)),
+    })
+}
+
+
 /// Parses a CSI sequence, just after reading ^[
 ///
 /// Returns None if an unrecognized sequence is found.
@@ -145,6 +531,7 @@ fn parse_csi<I>(iter: &mut I) -> Option<InputEvent>
     where I: Iterator<Item = Result<u8, Error>>
 {
     Some(match iter.next() {
+        // F1-F5 (linux console)
         Some(Ok(b'[')) => match iter.next() {
             Some(Ok(val @ b'A'..=b'E')) => InputEvent::Key(KeyEvent::F(1 + val - b'A')),
             _ => return None,
@@ -156,32 +543,43 @@ fn parse_csi<I>(iter: &mut I) -> Option<InputEvent>
         Some(Ok(b'H')) => InputEvent::Key(KeyEvent::Home),
         Some(Ok(b'F')) => InputEvent::Key(KeyEvent::End),
         Some(Ok(b'Z')) => InputEvent::Key(KeyEvent::BackTab),
+        // F1, F2 and F4 with the kitty keyboard protocol (F3 is sent as ESC [ 13 ~)
+        Some(Ok(b'P')) => InputEvent::Key(KeyEvent::F(1)),
+        Some(Ok(b'Q')) => InputEvent::Key(KeyEvent::F(2)),
+        Some(Ok(b'S')) => InputEvent::Key(KeyEvent::F(4)),
+        Some(Ok(b'I')) => InputEvent::FocusGained,
+        Some(Ok(b'O')) => InputEvent::FocusLost,
         Some(Ok(b'M')) => {
             // X10 emulation mouse encoding: ESC [ CB Cx Cy (6 characters only).
-            let mut next = || iter.next().unwrap().unwrap();
+            let mut next = || iter.next()?.ok();
 
-            let cb = next() as i8 - 32;
+            let cb = next()?;
             // (0, 0) are the coords for upper left.
-            let cx = next().saturating_sub(32) as u16;
-            let cy = next().saturating_sub(32) as u16;
+            let cx = next()?.saturating_sub(32) as u16;
+            let cy = next()?.saturating_sub(32) as u16;
+            let cb = cb.checked_sub(32)?;
+            let mods = Modifiers::from_mouse(cb as u16);
             InputEvent::Mouse(match cb & 0b11 {
+                // buttons 8-11, and the wheel with the motion bit, are not sent by terminals
+                _ if cb & 0x80 != 0 || cb & 0x60 == 0x60 => return None,
                 0 => {
                     if cb & 0x40 != 0 {
-                        MouseEvent::ButtonPressed(MouseButton::WheelUp, get_real_mouse_pos(cx, cy))
+                        MouseEvent::Scroll(ScrollDirection::Up, get_mouse_cell(cx, cy), mods)
                     } else {
-                        MouseEvent::ButtonPressed(MouseButton::Left, get_real_mouse_pos(cx, cy))
+                        MouseEvent::ButtonPressed(MouseButton::Left, get_mouse_cell(cx, cy), mods)
                     }
                 }
                 1 => {
                     if cb & 0x40 != 0 {
-                        MouseEvent::ButtonPressed(MouseButton::WheelDown, get_real_mouse_pos(cx, cy))
+                        MouseEvent::Scroll(ScrollDirection::Down, get_mouse_cell(cx, cy), mods)
                     } else {
-                        MouseEvent::ButtonPressed(MouseButton::Middle, get_real_mouse_pos(cx, cy))
+                        MouseEvent::ButtonPressed(MouseButton::Middle, get_mouse_cell(cx, cy), mods)
                     }
                 }
-                2 => MouseEvent::ButtonPressed(MouseButton::Right, get_real_mouse_pos(cx, cy)),
+                2 => MouseEvent::ButtonPressed(MouseButton::Right, get_mouse_cell(cx, cy), mods),
+                3 if cb & 0x20 != 0 => MouseEvent::Move(get_mouse_cell(cx, cy), mods),
                 // default to Left button, will be modified down the line
-                3 => MouseEvent::ButtonReleased(MouseButton::Left, get_real_mouse_pos(cx, cy)),
+                3 => MouseEvent::ButtonReleased(MouseButton::Left, get_mouse_cell(cx, cy), mods),
                 _ => return None,
             })
         }
@@ -189,89 +587,116 @@ fn parse_csi<I>(iter: &mut I) -> Option<InputEvent>
             // xterm mouse encoding:
             // ESC [ < Cb ; Cx ; Cy (;) (M or m)
             let mut buf = Vec::new();
-            let mut c = iter.next().unwrap().unwrap();
+            let mut c = iter.next()?.ok()?;
             while match c {
                       b'm' | b'M' => false,
                       _ => true,
                   } {
                 buf.push(c);
-                c = iter.next().unwrap().unwrap();
+                c = iter.next()?.ok()?;
             }
-            let str_buf = String::from_utf8(buf).unwrap();
+            let str_buf = String::from_utf8(buf).ok()?;
             let nums = &mut str_buf.split(';');
 
-            let cb = nums.next()
-                .unwrap()
+            let cb = nums.next()?
                 .parse::<u16>()
-                .unwrap();
-            let cx = nums.next()
-                .unwrap()
+                .ok()?;
+            let cx = nums.next()?
                 .parse::<u16>()
-                .unwrap();
-            let cy = nums.next()
-                .unwrap()
+                .ok()?;
+            let cy = nums.next()?
                 .parse::<u16>()
-                .unwrap();
+                .ok()?;
 
+            let mods = Modifiers::from_mouse(cb);
+            let cb = cb & !MOUSE_MODIFIER_BITS;
             let event = match cb {
-                0..=2 | 64..=65 => {
+                0..=2 => {
                     let button = match cb {
                         0 => MouseButton::Left,
                         1 => MouseButton::Middle,
-                        2 => MouseButton::Right,
-                        64 => MouseButton::WheelUp,
-                        65 => MouseButton::WheelDown,
-                        _ => unreachable!(),
+                        _ => MouseButton::Right,
                     };
                     match c {
-                        b'M' => MouseEvent::ButtonPressed(button, get_real_mouse_pos(cx, cy)),
+                        b'M' => MouseEvent::ButtonPressed(button, get_mouse_cell(cx, cy), mods),
                         // default to Left button, will be modified down the line
-                        b'm' => MouseEvent::ButtonReleased(MouseButton::Left, get_real_mouse_pos(cx, cy)),
+                        b'm' => MouseEvent::ButtonReleased(MouseButton::Left, get_mouse_cell(cx, cy), mods),
                         _ => return None,
                     }
                 }
+                64 if c == b'M' => MouseEvent::Scroll(ScrollDirection::Up, get_mouse_cell(cx, cy), mods),
+                65 if c == b'M' => MouseEvent::Scroll(ScrollDirection::Down, get_mouse_cell(cx, cy), mods),
                 // default to Left button, will be modified down the line
-                32 => MouseEvent::Hold(MouseButton::Left, get_real_mouse_pos(cx, cy)),
+                32 => MouseEvent::Hold(MouseButton::Left, get_mouse_cell(cx, cy), mods),
+                35 => MouseEvent::Move(get_mouse_cell(cx, cy), mods),
                 // default to Left button, will be modified down the line
-                3 => MouseEvent::ButtonReleased(MouseButton::Left, get_real_mouse_pos(cx, cy)),
+                3 => MouseEvent::ButtonReleased(MouseButton::Left, get_mouse_cell(cx, cy), mods),
                 _ => return None,
             };
 
             InputEvent::Mouse(event)
         }
+        Some(Ok(b'?')) => {
+            // DECRQM reply: ESC [ ? Pd ; Ps $ y
+            let mut buf = Vec::new();
+            let mut c = iter.next()?.ok()?;
+            while !(64..=126).contains(&c) {
+                buf.push(c);
+                c = iter.next()?.ok()?;
+            }
+            if c != b'y' || buf.pop() != Some(b'$') {
+                return None;
+            }
+
+            let str_buf = String::from_utf8(buf).ok()?;
+            let (mode, status) = str_buf.split_once(';')?;
+            let status = match status.parse::<u8>().ok()? {
+                0 => ModeStatus::NotRecognized,
+                1 => ModeStatus::Set,
+                2 => ModeStatus::Reset,
+                3 => ModeStatus::PermanentlySet,
+                4 => ModeStatus::PermanentlyReset,
+                _ => return None,
+            };
+            InputEvent::ModeReport(mode.parse().ok()?, status)
+        }
         Some(Ok(c @ b'0'..=b'9')) => {
             // Numbered escape code.
             let mut buf = Vec::new();
             buf.push(c);
-            let mut c = iter.next().unwrap().unwrap();
+            let mut c = iter.next()?.ok()?;
             // The final byte of a CSI sequence can be in the range 64-126, so
-            // let's keep reading anything else.
-            while c < 64 || c > 126 {
+            // let's keep reading anything else. rxvt also ends shifted keys with '$'.
+            while !((64..=126).contains(&c) || (c == b'$' && buf.iter().all(u8::is_ascii_digit))) {
                 buf.push(c);
-                c = iter.next().unwrap().unwrap();
+                c = iter.next()?.ok()?;
             }
 
             match c {
                 // rxvt mouse encoding:
                 // ESC [ Cb ; Cx ; Cy ; M
                 b'M' => {
-                    let str_buf = String::from_utf8(buf).unwrap();
+                    let str_buf = String::from_utf8(buf).ok()?;
 
-                    let nums: Vec<u16> = str_buf.split(';').map(|n| n.parse().unwrap()).collect();
+                    let nums: Vec<u16> = str_buf.split(';').map(|n| n.parse().ok()).collect::<Option<_>>()?;
 
-                    let cb = nums[0];
-                    let cx = nums[1];
-                    let cy = nums[2];
+                    let (cb, cx, cy) = match nums[..] {
+                        [cb, cx, cy, ..] => (cb, cx, cy),
+                        _ => return None,
+                    };
 
-                    let event = match cb {
-                        32 => MouseEvent::ButtonPressed(MouseButton::Left, get_real_mouse_pos(cx, cy)),
-                        33 => MouseEvent::ButtonPressed(MouseButton::Middle, get_real_mouse_pos(cx, cy)),
-                        34 => MouseEvent::ButtonPressed(MouseButton::Right, get_real_mouse_pos(cx, cy)),
+                    let mods = Modifiers::from_mouse(cb);
+                    let event = match cb & !MOUSE_MODIFIER_BITS {
+                        32 => MouseEvent::ButtonPressed(MouseButton::Left, get_mouse_cell(cx, cy), mods),
+                        33 => MouseEvent::ButtonPressed(MouseButton::Middle, get_mouse_cell(cx, cy), mods),
+                        34 => MouseEvent::ButtonPressed(MouseButton::Right, get_mouse_cell(cx, cy), mods),
                         // default to Left button, will be modified down the line
-                        35 => MouseEvent::ButtonReleased(MouseButton::Left, get_real_mouse_pos(cx, cy)),
+                        35 => MouseEvent::ButtonReleased(MouseButton::Left, get_mouse_cell(cx, cy), mods),
                         // default to Left button, will be modified down the line
-                        64 => MouseEvent::Hold(MouseButton::Left, get_real_mouse_pos(cx, cy)),
-                        96 | 97 => MouseEvent::ButtonPressed(MouseButton::WheelUp, get_real_mouse_pos(cx, cy)),
+                        64 => MouseEvent::Hold(MouseButton::Left, get_mouse_cell(cx, cy), mods),
+                        67 => MouseEvent::Move(get_mouse_cell(cx, cy), mods),
+                        96 => MouseEvent::Scroll(ScrollDirection::Up, get_mouse_cell(cx, cy), mods),
+                        97 => MouseEvent::Scroll(ScrollDirection::Down, get_mouse_cell(cx, cy), mods),
                         _ => return None,
                     };
 
@@ -279,35 +704,60 @@ fn parse_csi<I>(iter: &mut I) -> Option<InputEvent>
                 }
                 // Special key code.
                 b'~' => {
-                    let str_buf = String::from_utf8(buf).unwrap();
+                    let str_buf = String::from_utf8(buf).ok()?;
 
                     // This CSI sequence can be a list of semicolon-separated
-                    // numbers.
-                    let nums: Vec<u8> = str_buf.split(';').map(|n| n.parse().unwrap()).collect();
+                    // numbers, the second one being the modifiers.
+                    let (nums, released) = key_params(&str_buf)?;
 
-                    if nums.is_empty() {
+                    if nums.is_empty() || nums.len() > 2 {
                         return None;
                     }
+                    if nums[..] == [200] {
+                        return parse_paste(iter);
+                    }
 
-                    // TODO: handle multiple values for key modififiers (ex: values
-                    // [3, 2] means Shift+Delete)
-                    if nums.len() > 1 {
+                    key_event(tilde_key(nums[0])?, nums.get(1).copied(), released)
+                }
+                // rxvt function and editing keys with Shift ($), Ctrl (^) or both (@): ESC [ nn ($, ^ or @)
+                c @ (b'$' | b'^' | b'@') => {
+                    let key = tilde_key(String::from_utf8(buf).ok()?.parse().ok()?)?;
+                    let param = match c {
+                        b'$' => 2,
+                        b'^' => 5,
+                        _ => 6,
+                    };
+                    modified_key(key, Some(param))
+                }
+                // Cursor position report: ESC [ row ; col R. It can also be F3 with modifiers when the
+                // cursor is on the first row, so it is only decoded as a report during a query.
+                b'R' if CURSOR_QUERY_PENDING.load(Ordering::SeqCst) || !buf.starts_with(b"1;") => {
+                    let str_buf = String::from_utf8(buf).ok()?;
+                    let (row, col) = str_buf.split_once(';')?;
+                    let (row, col) = (row.parse::<u16>().ok()?, col.parse::<u16>().ok()?);
+                    InputEvent::CursorPosition(vec2!(col as i32 - 1, row as i32 - 1))
+                }
+                // Cursor keys and F1-F4 with modifiers: ESC [ 1 ; Pm (A-D, H, F or P-S)
+                c @ (b'A'..=b'D' | b'H' | b'F' | b'P'..=b'S') => {
+                    let str_buf = String::from_utf8(buf).ok()?;
+                    let (nums, released) = key_params(&str_buf)?;
+                    if nums.len() != 2 || nums[0] != 1 {
                         return None;
                     }
 
-                    match nums[0] {
-                        1 | 7 => InputEvent::Key(KeyEvent::Home),
-                        2 => InputEvent::Key(KeyEvent::Insert),
-                        3 => InputEvent::Key(KeyEvent::Delete),
-                        4 | 8 => InputEvent::Key(KeyEvent::End),
-                        5 => InputEvent::Key(KeyEvent::PageUp),
-                        6 => InputEvent::Key(KeyEvent::PageDown),
-                        v @ 11..=15 => InputEvent::Key(KeyEvent::F(v - 10)),
-                        v @ 17..=21 => InputEvent::Key(KeyEvent::F(v - 11)),
-                        v @ 23..=24 => InputEvent::Key(KeyEvent::F(v - 12)),
-                        _ => return None,
-                    }
+                    let key = match c {
+                        b'A' => KeyEvent::Up,
+                        b'B' => KeyEvent::Down,
+                        b'C' => KeyEvent::Right,
+                        b'D' => KeyEvent::Left,
+                        b'H' => KeyEvent::Home,
+                        b'F' => KeyEvent::End,
+                        _ => KeyEvent::F(1 + c - b'P'),
+                    };
+                    key_event(key, Some(nums[1]), released)
                 }
+                // kitty keyboard protocol
+                b'u' => parse_kitty_key(&String::from_utf8(buf).ok()?)?,
                 _ => return None,
             }
         }
@@ -316,6 +766,26 @@ fn parse_csi<I>(iter: &mut I) -> Option<InputEvent>
 }
 
 
+/// End of a bracketed paste.
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+
+/// Reads the text of a bracketed paste, just after reading ^[[200~. The text is taken literally up
+/// to ^[[201~, escape sequences included.
+fn parse_paste<I>(iter: &mut I) -> Option<InputEvent>
+    where I: Iterator<Item = Result<u8, Error>>
+{
+    let mut buf = Vec::new();
+    while !buf.ends_with(PASTE_END) {
+        buf.push(iter.next()?.ok()?);
+    }
+    buf.truncate(buf.len() - PASTE_END.len());
+
+    let text = String::from_utf8_lossy(&buf).replace("\r\n", "\n").replace('\r', "\n");
+    Some(InputEvent::Paste(text))
+}
+
+
 /// Parse `c` as either a single byte ASCII char or a variable size UTF-8 char.
 fn parse_utf8_char<I>(c: u8, iter: &mut I) -> Result<char, Error>
     where I: Iterator<Item = Result<u8, Error>>
@@ -345,109 +815,1104 @@ fn parse_utf8_char<I>(c: u8, iter: &mut I) -> Result<char, Error>
 }
 
 
-/// Input Server Singleton instance
-static mut INPUT_SERVER: Option<Input> = None;
+/// Ends of the pipe the SIGWINCH handler writes a byte to (self-pipe), read by the resize watcher.
+#[cfg(unix)]
+static RESIZE_PIPE: OnceLock<(RawFd, RawFd)> = OnceLock::new();
 
 
-/// The Input is a singleton that handles async io operations
-/// 
-/// # Usage
-/// 
-/// To get events from keyboard and mouse, there are two functions:
-/// 
-/// get_event - returns Some(InputEvent) or None depending on weather there was an input
-/// 
-/// get_event_blocking - waits for an event and returns it
-/// 
-/// # Mouse
-/// 
-/// To have mouse input or not use enable_mouse or disable_mouse
-/// by default, there is no mouse input
-pub struct Input {
-    _server_handle: Option<thread::JoinHandle<()>>,
-    input_recv: mpsc::Receiver<InputEvent>
+/// Write end of `RESIZE_PIPE`, -1 until it is created. An atomic can be read in the signal handler.
+#[cfg(unix)]
+static RESIZE_PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+
+/// Set while waiting for a cursor position report (see `Input::query_cursor_position`).
+static CURSOR_QUERY_PENDING: AtomicBool = AtomicBool::new(false);
+
+
+#[cfg(unix)]
+extern "C" fn on_sigwinch(_: libc::c_int) {
+    let fd = RESIZE_PIPE_WRITE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        // write is async-signal-safe, the byte is dropped if the pipe is full
+        unsafe { libc::write(fd, [0u8].as_ptr() as *const libc::c_void, 1); }
+    }
 }
 
 
-impl Input {
+/// Delay without resize after which a Resize event is sent.
+#[cfg(unix)]
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
 
-    /// Creates the Input singleton, will only be called once
-    fn init() -> Self {
-        let (input_send, input_recv) = mpsc::channel();
 
-        let handle = thread::spawn(move || {
-            let mut mb = MouseButton::Left;
-            loop {
-                let mut stdin = stdin().bytes();
-
-                if let Some(Ok(item)) = stdin.next() {
-                    match parse_event(item, &mut stdin) {
-                        Ok(evt) => {
-                            let event = match evt {
-                                InputEvent::Mouse(MouseEvent::ButtonPressed(button, _)) => {
-                                    mb = button;
-                                    evt
-                                }
-                                InputEvent::Mouse(MouseEvent::ButtonReleased(_, pos)) =>
-                                    InputEvent::Mouse(MouseEvent::ButtonReleased(mb, pos)),
-                                InputEvent::Mouse(MouseEvent::Hold(_, pos)) =>
-                                    InputEvent::Mouse(MouseEvent::Hold(mb, pos)),
-                                _ => evt
-                            };
-                            input_send.send(event).expect("input recv dropped")
-                        }
-                        Err(_) => {}
-                    }
-                };
-            }
-        });
+/// Coalesces the resize signals received in quick succession (eg. while the user drags the border of the window)
+/// into a single event.
+#[cfg(unix)]
+pub(crate) struct ResizeDebouncer {
+    delay: Duration,
+    last_signal: Option<Instant>
+}
+
+
+#[cfg(unix)]
+impl ResizeDebouncer {
 
+    pub(crate) fn new(delay: Duration) -> Self {
         Self {
-            _server_handle: Some(handle),
-            input_recv: input_recv
+            delay,
+            last_signal: None
         }
     }
 
 
-    /// Returns the Input singleton.
-    /// If no call to Input::get() is made, the server never starts;
-    /// this can be usefull when custom input handling is needed.
-    pub fn get() -> &'static mut Input {
-        unsafe {
-            match &mut INPUT_SERVER {
-                None => {
-                    INPUT_SERVER = Some(Input::init());
-                    Input::get()
-                },
-                Some(i) => i
+    /// Records a resize signal received at `now`.
+    pub(crate) fn signal(&mut self, now: Instant) {
+        self.last_signal = Some(now);
+    }
+
+
+    /// Returns how long to wait after `now` before calling `poll`, None if there is no signal waiting.
+    pub(crate) fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.last_signal.map(|last| self.delay.saturating_sub(now.saturating_duration_since(last)))
+    }
+
+
+    /// Returns true if an event should be sent at `now`: there was a signal, and no other signal since `delay`.
+    pub(crate) fn poll(&mut self, now: Instant) -> bool {
+        match self.last_signal {
+            Some(last) if now.saturating_duration_since(last) >= self.delay => {
+                self.last_signal = None;
+                true
             }
+            _ => false
         }
     }
+}
 
 
-    /// If there was an event, return it.
-    /// Never blocks the current thread.
-    pub fn get_event(&mut self) -> Option<InputEvent> {
-        self.input_recv.try_recv().ok()
+/// Drops the key events identical to the last one let through less than `window` before, so that a held
+/// key gives at most one event per `window`.
+pub(crate) struct RepeatFilter {
+    window: Duration,
+    last: Option<(InputEvent, Instant)>
+}
+
+
+impl RepeatFilter {
+
+    /// Creates a filter. A zero window lets every event through.
+    pub(crate) fn new(window: Duration) -> Self {
+        Self { window, last: None }
     }
 
 
-    /// Wait for an InputEvent to occur and return it.
-    pub fn get_event_blocking(&mut self) -> InputEvent {
-        self.input_recv.recv().ok().expect("Input thread was killed")
+    /// Changes the window, the events already let through still count.
+    pub(crate) fn set_window(&mut self, window: Duration) {
+        self.window = window;
     }
 
 
-    /// Enable MouseEvent.
-    pub fn enable_mouse() {
-        print!("\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h");
-        stdout().flush().expect("Could not write to stdout");
+    /// Returns true if `event`, received at `time`, should be sent.
+    pub(crate) fn accept(&mut self, event: &InputEvent, time: Instant) -> bool {
+        if !matches!(event, InputEvent::Key(_) | InputEvent::ModifiedKey(..)) {
+            self.last = None;
+            return true;
+        }
+
+        match &self.last {
+            Some((last, last_time)) if last == event && time.saturating_duration_since(*last_time) < self.window => false,
+            _ => {
+                self.last = Some((event.clone(), time));
+                true
+            }
+        }
     }
+}
 
 
-    /// Disable MouseEvent.
-    pub fn disable_mouse() {
-        print!("\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l");
-        stdout().flush().expect("Could not write to stdout");
+/// Installs the SIGWINCH handler and starts the thread sending the Resize events to `input_send`.
+/// The thread stops once the receiver or the other senders are dropped.
+#[cfg(unix)]
+fn watch_resize(input_send: WeakEventSender) -> io::Result<()> {
+    let &(read, _) = match RESIZE_PIPE.get() {
+        Some(pipe) => pipe,
+        None => {
+            let created = create_resize_pipe()?;
+            let pipe = RESIZE_PIPE.get_or_init(|| created);
+            if *pipe != created {
+                // another Input created the pipe first
+                unsafe {
+                    libc::close(created.0);
+                    libc::close(created.1);
+                }
+            }
+            RESIZE_PIPE_WRITE.store(pipe.1, Ordering::Relaxed);
+            pipe
+        }
+    };
+    unsafe {
+        libc::signal(libc::SIGWINCH, on_sigwinch as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+
+    thread::spawn(move || {
+        let mut debouncer = ResizeDebouncer::new(RESIZE_DEBOUNCE);
+        loop {
+            let signaled = wait_readable(read, debouncer.timeout(Instant::now()));
+            let now = Instant::now();
+            if signaled {
+                debouncer.signal(now);
+            }
+            if debouncer.poll(now) && input_send.send((InputEvent::Resize(Renderer::get_size()), now)).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+
+/// Creates a pipe whose ends never block, so that the signal handler cannot hang on a full pipe.
+#[cfg(unix)]
+fn create_resize_pipe() -> io::Result<(RawFd, RawFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    for fd in fds {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK | libc::O_CLOEXEC);
+        }
+    }
+    Ok((fds[0], fds[1]))
+}
+
+
+/// Waits at most `timeout` (forever if None) for bytes on `fd`, and reads all of them.
+/// Returns true if there were bytes.
+#[cfg(unix)]
+fn wait_readable(fd: RawFd, timeout: Option<Duration>) -> bool {
+    let timeout = timeout.map_or(-1, |t| t.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as libc::c_int);
+    let mut pollfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    // interrupted by a signal: the bytes are read anyway
+    unsafe { libc::poll(&mut pollfd, 1, timeout); }
+
+    let mut buffer = [0u8; 64];
+    let mut read = false;
+    while unsafe { libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) } > 0 {
+        read = true;
+    }
+    read
+}
+
+
+/// What to do with a new event when the buffer of the events not read yet is full (see `Input::set_buffer_policy`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BufferPolicy {
+    /// The oldest event is dropped, the buffer keeps the most recent events.
+    DropOldest,
+    /// The new event is dropped, the buffer keeps the events that were not read in time.
+    DropNewest
+}
+
+
+/// Number of events kept by default while the application does not read them.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 1024;
+
+
+/// Events not read yet, shared by the threads sending events and the Input.
+struct EventBuffer {
+    state: Mutex<EventBufferState>,
+    ready: Condvar
+}
+
+
+struct EventBufferState {
+    events: VecDeque<(InputEvent, Instant)>,
+    capacity: usize,
+    policy: BufferPolicy,
+    /// number of EventSender alive
+    senders: usize,
+    /// set when the EventReceiver is dropped
+    closed: bool
+}
+
+
+impl EventBuffer {
+
+    fn lock(&self) -> MutexGuard<'_, EventBufferState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+
+/// Bounded channel of events: unlike a `sync_channel`, a sender never waits, the event dropped when the
+/// buffer is full depends on the `BufferPolicy`.
+fn event_channel() -> (EventSender, EventReceiver) {
+    let buffer = Arc::new(EventBuffer {
+        state: Mutex::new(EventBufferState {
+            events: VecDeque::new(),
+            capacity: DEFAULT_BUFFER_CAPACITY,
+            policy: BufferPolicy::DropOldest,
+            senders: 1,
+            closed: false
+        }),
+        ready: Condvar::new()
+    });
+    (EventSender(Arc::clone(&buffer)), EventReceiver(buffer))
+}
+
+
+pub(crate) struct EventSender(Arc<EventBuffer>);
+
+
+impl EventSender {
+
+    /// Adds `event` to the buffer. Returns an error if the receiver was dropped.
+    pub(crate) fn send(&self, event: (InputEvent, Instant)) -> Result<(), ()> {
+        self.0.push(event)
+    }
+
+
+    /// Returns a sender that does not keep the channel open: the receiver stops waiting once the
+    /// other senders are dropped.
+    #[cfg(unix)]
+    pub(crate) fn downgrade(&self) -> WeakEventSender {
+        WeakEventSender(Arc::downgrade(&self.0))
+    }
+}
+
+
+/// Sender that is not counted in the senders of the channel, see `EventSender::downgrade`.
+#[cfg(unix)]
+pub(crate) struct WeakEventSender(Weak<EventBuffer>);
+
+
+#[cfg(unix)]
+impl WeakEventSender {
+
+    /// Adds `event` to the buffer. Returns an error if the receiver or all the other senders were dropped.
+    pub(crate) fn send(&self, event: (InputEvent, Instant)) -> Result<(), ()> {
+        let buffer = self.0.upgrade().ok_or(())?;
+        if buffer.lock().senders == 0 {
+            return Err(());
+        }
+        buffer.push(event)
+    }
+}
+
+
+impl EventBuffer {
+
+    /// Adds `event` to the buffer. Returns an error if the receiver was dropped.
+    fn push(&self, event: (InputEvent, Instant)) -> Result<(), ()> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(());
+        }
+        if state.events.len() >= state.capacity {
+            match state.policy {
+                BufferPolicy::DropOldest => { state.events.pop_front(); }
+                BufferPolicy::DropNewest => return Ok(())
+            }
+        }
+        if state.capacity > 0 {
+            state.events.push_back(event);
+        }
+        self.ready.notify_one();
+        Ok(())
+    }
+}
+
+
+impl Clone for EventSender {
+
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
+        EventSender(Arc::clone(&self.0))
+    }
+}
+
+
+impl Drop for EventSender {
+
+    fn drop(&mut self) {
+        self.0.lock().senders -= 1;
+        self.0.ready.notify_all();
+    }
+}
+
+
+struct EventReceiver(Arc<EventBuffer>);
+
+
+impl EventReceiver {
+
+    /// Returns the oldest event, or an error if there is none.
+    fn try_recv(&self) -> Result<(InputEvent, Instant), ()> {
+        self.0.lock().events.pop_front().ok_or(())
+    }
+
+
+    /// Waits for an event. Returns an error if there are no events and no senders.
+    fn recv(&self) -> Result<(InputEvent, Instant), ()> {
+        let mut state = self.0.lock();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Ok(event);
+            }
+            if state.senders == 0 {
+                return Err(());
+            }
+            state = self.0.ready.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+
+    /// Waits at most `timeout` for an event.
+    fn recv_timeout(&self, timeout: Duration) -> Result<(InputEvent, Instant), ()> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.0.lock();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Ok(event);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if state.senders == 0 || remaining.is_zero() {
+                return Err(());
+            }
+            state = self.0.ready.wait_timeout(state, remaining).unwrap_or_else(PoisonError::into_inner).0;
+        }
+    }
+
+
+    /// Sets the number of events kept and what happens when there are more. The oldest events are dropped
+    /// if the buffer already has more than `capacity` events.
+    fn set_policy(&self, capacity: usize, policy: BufferPolicy) {
+        let mut state = self.0.lock();
+        state.capacity = capacity;
+        state.policy = policy;
+        let excess = state.events.len().saturating_sub(capacity);
+        state.events.drain(..excess);
+    }
+}
+
+
+impl Drop for EventReceiver {
+
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+    }
+}
+
+
+/// Input Server Singleton instance.
+static INPUT_SERVER: Mutex<Option<Input>> = Mutex::new(None);
+
+
+thread_local! {
+    /// True while the current thread holds an `InputGuard`.
+    static INPUT_BORROWED: Cell<bool> = const { Cell::new(false) };
+}
+
+
+/// Exclusive access to the Input singleton, returned by `Input::get`. The singleton stays locked until the
+/// guard is dropped: calling `Input::get` again on the same thread before that panics.
+pub struct InputGuard(MutexGuard<'static, Option<Input>>);
+
+
+impl Drop for InputGuard {
+
+    fn drop(&mut self) {
+        INPUT_BORROWED.with(|b| b.set(false));
+    }
+}
+
+
+impl Deref for InputGuard {
+
+    type Target = Input;
+
+    fn deref(&self) -> &Input {
+        self.0.as_ref().expect("Input was just created")
+    }
+}
+
+
+impl DerefMut for InputGuard {
+
+    fn deref_mut(&mut self) -> &mut Input {
+        self.0.as_mut().expect("Input was just created")
+    }
+}
+
+
+/// The Input is a singleton that handles async io operations
+/// 
+/// # Usage
+/// 
+/// To get events from keyboard and mouse, there are two functions:
+/// 
+/// get_event - returns Some(InputEvent) or None depending on weather there was an input
+/// 
+/// get_event_blocking - waits for an event and returns it
+/// 
+/// # Mouse
+/// 
+/// To have mouse input or not use enable_mouse or disable_mouse
+/// by default, there is no mouse input
+pub struct Input {
+    _server_handle: Option<thread::JoinHandle<()>>,
+    input_recv: EventReceiver,
+    /// key repeat coalescing, applied when the events are read so that `read_line` gets all the keys
+    repeats: RepeatFilter,
+
+    /// events received while waiting for a reply of the terminal
+    pending: VecDeque<(InputEvent, Instant)>
+}
+
+
+impl Input {
+
+    /// Creates an Input reading the events from stdin, independent from the singleton (see `get`).
+    /// 
+    /// Only one Input should read stdin at a time, or each of them gets part of the events.
+    pub fn new() -> Self {
+        let (input, input_send) = Input::start(stdin());
+        // the resize events are only reported on Unix for now, and are lost if the pipe cannot be created
+        #[cfg(unix)]
+        let _ = watch_resize(input_send.downgrade());
+        drop(input_send);
+        input
+    }
+
+
+    /// Creates an Input parsing the events read from `reader`.
+    #[cfg(test)]
+    pub(crate) fn with_reader<R>(reader: R) -> Self
+        where R: Read + Send + 'static
+    {
+        Input::start(reader).0
+    }
+
+
+    /// Starts the thread parsing the events read from `reader`. Also returns the sender of the events,
+    /// to send events that do not come from `reader`.
+    pub(crate) fn start<R>(reader: R) -> (Self, EventSender)
+        where R: Read + Send + 'static
+    {
+        let (input_send, input_recv) = event_channel();
+        let sender = input_send.clone();
+
+        let handle = thread::spawn(move || {
+            let mut mb = MouseButton::Left;
+            let mut bytes = BufReader::new(reader).bytes();
+
+            while let Some(item) = bytes.next() {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(_) => continue
+                };
+                let evt = parse_event(item, &mut bytes);
+                let time = Instant::now();
+                let event = match evt {
+                    InputEvent::Mouse(MouseEvent::ButtonPressed(button, _, _)) => {
+                        mb = button;
+                        evt
+                    }
+                    InputEvent::Mouse(MouseEvent::ButtonReleased(_, pos, mods)) =>
+                        InputEvent::Mouse(MouseEvent::ButtonReleased(mb, pos, mods)),
+                    InputEvent::Mouse(MouseEvent::Hold(_, pos, mods)) =>
+                        InputEvent::Mouse(MouseEvent::Hold(mb, pos, mods)),
+                    _ => evt
+                };
+                if input_send.send((event, time)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (Self {
+            _server_handle: Some(handle),
+            input_recv,
+            repeats: RepeatFilter::new(Duration::ZERO),
+
+            pending: VecDeque::new()
+        }, sender)
+    }
+
+
+    /// Returns the Input singleton, locked until the returned guard is dropped.
+    /// If no call to Input::get() is made, the server never starts;
+    /// this can be usefull when custom input handling is needed.
+    /// 
+    /// Panics if the current thread already holds the guard.
+    pub fn get() -> InputGuard {
+        let mut input = lock_singleton(&INPUT_SERVER, &INPUT_BORROWED, "Input");
+        if input.is_none() {
+            *input = Some(Input::new());
+        }
+        InputGuard(input)
+    }
+
+
+    /// If there was an event, return it.
+    /// Never blocks the current thread.
+    pub fn get_event(&mut self) -> Option<InputEvent> {
+        self.get_event_timed().map(|(event, _)| event)
+    }
+
+
+    /// Same as `get_event`, but also returns the instant at which the event was parsed.
+    pub fn get_event_timed(&mut self) -> Option<(InputEvent, Instant)> {
+        self.next_event(false)
+    }
+
+
+    /// Wait for an InputEvent to occur and return it.
+    pub fn get_event_blocking(&mut self) -> InputEvent {
+        self.get_event_blocking_timed().0
+    }
+
+
+    /// Same as `get_event_blocking`, but also returns the instant at which the event was parsed.
+    pub fn get_event_blocking_timed(&mut self) -> (InputEvent, Instant) {
+        self.next_event(true).expect("Input thread was killed")
+    }
+
+
+    /// Returns the next event, waiting for it if `wait` is set. The repeated keys are skipped
+    /// (see `set_repeat_coalescing`).
+    fn next_event(&mut self, wait: bool) -> Option<(InputEvent, Instant)> {
+        loop {
+            let (event, time) = match self.pending.pop_front() {
+                Some(event) => event,
+                None if wait => self.input_recv.recv().ok()?,
+                None => self.input_recv.try_recv().ok()?
+            };
+            if self.repeats.accept(&event, time) {
+                return Some((event, time));
+            }
+        }
+    }
+
+
+    /// Iterates over the events, waiting for each of them. The iteration ends if the input thread stopped.
+    /// 
+    /// ```no_run
+    /// # use termkan::input::Input;
+    /// for event in Input::get().events() {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn events(&mut self) -> impl Iterator<Item = InputEvent> + '_ {
+        std::iter::from_fn(move || self.next_event(true).map(|(event, _)| event))
+    }
+
+
+    /// Iterates over the events that already occured, without blocking.
+    pub fn try_events(&mut self) -> impl Iterator<Item = InputEvent> + '_ {
+        std::iter::from_fn(move || self.get_event())
+    }
+
+
+    /// Returns all the events that already occured, in order. Never blocks the current thread.
+    /// 
+    /// The events arriving while it runs are not returned, so that a frame processes a fixed set of events:
+    /// ```no_run
+    /// # use termkan::input::Input;
+    /// for event in Input::get().drain_events() {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn drain_events(&mut self) -> Vec<InputEvent> {
+        let now = Instant::now();
+        let mut events: Vec<(InputEvent, Instant)> = self.pending.drain(..).collect();
+        while let Ok((event, time)) = self.input_recv.try_recv() {
+            if time > now {
+                // kept for the next call
+                self.pending.push_back((event, time));
+                break;
+            }
+            events.push((event, time));
+        }
+        events.into_iter().filter(|(event, time)| self.repeats.accept(event, *time)).map(|(event, _)| event).collect()
+    }
+
+
+    /// Sets the number of events kept while the application does not read them, and which events are dropped
+    /// when there are more, so that the memory used by the events is bounded.
+    /// 
+    /// Defaults to `DEFAULT_BUFFER_CAPACITY` events with `BufferPolicy::DropOldest`. The events received while
+    /// waiting for a reply of the terminal (see `query_mode`) are kept apart and not limited.
+    pub fn set_buffer_policy(&mut self, capacity: usize, policy: BufferPolicy) {
+        self.input_recv.set_policy(capacity, policy);
+    }
+
+
+    /// Coalesces the repeated key events: a key event identical to the last one sent less than `window`
+    /// before is dropped, so that a held key gives at most one event per `window` instead of one per
+    /// autorepeat of the terminal. Distinct keys are not affected.
+    /// 
+    /// A zero `window`, the default, disables the coalescing. The keys read by `read_line` are never coalesced.
+    pub fn set_repeat_coalescing(&mut self, window: Duration) {
+        self.repeats.set_window(window);
+    }
+
+
+    /// Asks the terminal whether the private mode `mode` (eg. 1000 for the mouse) is enabled, using a DECRQM query.
+    /// 
+    /// Waits at most `timeout` for the reply, and returns None if the terminal did not answer.
+    /// The other events received in the meantime are kept and returned by the next calls to `get_event`.
+    pub fn query_mode(&mut self, mode: u16, timeout: Duration) -> Option<ModeStatus> {
+        print!("\x1b[?{}$p", mode);
+        stdout().flush().expect("Could not write to stdout");
+        self.wait_mode_report(mode, timeout)
+    }
+
+
+    /// Waits for the reply to a DECRQM query about `mode`.
+    pub(crate) fn wait_mode_report(&mut self, mode: u16, timeout: Duration) -> Option<ModeStatus> {
+        self.wait_reply(timeout, |event| match event {
+            InputEvent::ModeReport(m, status) if *m == mode => Some(*status),
+            _ => None
+        })
+    }
+
+
+    /// Asks the terminal the position of the cursor, as a column and a row starting from 0.
+    /// 
+    /// Waits at most `timeout` for the reply, and returns None if the terminal did not answer.
+    /// The other events received in the meantime are kept and returned by the next calls to `get_event`.
+    pub fn query_cursor_position(&mut self, timeout: Duration) -> Option<Vec2> {
+        self.wait_cursor_position(timeout, || {
+            print!("\x1b[6n");
+            stdout().flush().expect("Could not write to stdout");
+        })
+    }
+
+
+    /// Calls `request` to send a cursor position query, and waits for the reply.
+    pub(crate) fn wait_cursor_position<F>(&mut self, timeout: Duration, request: F) -> Option<Vec2>
+        where F: FnOnce()
+    {
+        CURSOR_QUERY_PENDING.store(true, Ordering::SeqCst);
+        request();
+        let position = self.wait_reply(timeout, |event| match event {
+            InputEvent::CursorPosition(p) => Some(*p),
+            _ => None
+        });
+        CURSOR_QUERY_PENDING.store(false, Ordering::SeqCst);
+        position
+    }
+
+
+    /// Reads a line with the line editing of the terminal, and returns it without the line feed.
+    /// 
+    /// If stdin is a terminal, canonical mode and echo are enabled until the line is read, then the
+    /// previous settings (eg. the raw mode of the `Renderer`) are restored. Ctrl+D is disabled meanwhile,
+    /// as an end of file would stop the input thread. The text is echoed at the cursor position, so the
+    /// renderer does not know about it: use `Renderer::mark_dirty` to redraw the cells it covered.
+    /// 
+    /// The other events received in the meantime are kept and returned by the next calls to `get_event`.
+    /// Fails with `ErrorKind::UnexpectedEof` if the input ends before a line feed.
+    pub fn read_line(&mut self) -> io::Result<String> {
+        let saved = PlatformTerminal::set_line_mode().ok();
+
+        let mut line = String::new();
+        let result = loop {
+            match self.input_recv.recv() {
+                Ok((InputEvent::Key(KeyEvent::Char('\n')), _)) => break Ok(line),
+                Ok((InputEvent::Key(KeyEvent::Char(c)), _)) => line.push(c),
+                Ok(event) => self.pending.push_back(event),
+                Err(_) => break Err(Error::new(ErrorKind::UnexpectedEof, "input ended before a line feed"))
+            }
+        };
+
+        if let Some(terminal) = saved {
+            terminal.restore();
+        }
+        result
+    }
+
+
+    /// Waits at most `timeout` for an event for which `reply` returns a value, the other events
+    /// are kept in `pending`.
+    fn wait_reply<T, F>(&mut self, timeout: Duration, mut reply: F) -> Option<T>
+        where F: FnMut(&InputEvent) -> Option<T>
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.input_recv.recv_timeout(remaining) {
+                Ok((event, time)) => match reply(&event) {
+                    Some(value) => return Some(value),
+                    None => self.pending.push_back((event, time))
+                }
+                Err(_) => return None
+            }
+        }
+    }
+
+
+    /// Enable MouseEvent.
+    pub fn enable_mouse() {
+        print!("\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Enable `MouseEvent::Move`, reported when the mouse moves without any button pressed.
+    /// The mouse should also be enabled with `enable_mouse`.
+    pub fn enable_mouse_motion() {
+        print!("\x1b[?1003h");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Disable `MouseEvent::Move`.
+    pub fn disable_mouse_motion() {
+        print!("\x1b[?1003l");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Enable bracketed paste: pasted text is reported as a single `InputEvent::Paste` instead
+    /// of one key event per character.
+    pub fn enable_bracketed_paste() {
+        print!("\x1b[?2004h");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Disable bracketed paste.
+    pub fn disable_bracketed_paste() {
+        print!("\x1b[?2004l");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Enable `InputEvent::FocusGained` and `InputEvent::FocusLost`, reported when the terminal
+    /// window gains or loses the focus.
+    pub fn enable_focus_events() {
+        print!("\x1b[?1004h");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Disable focus events.
+    pub fn disable_focus_events() {
+        print!("\x1b[?1004l");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Enable the kitty keyboard protocol, on terminals supporting it: keys are reported without
+    /// ambiguity and their release is reported as `InputEvent::KeyReleased`. Other terminals ignore it
+    /// and keep reporting the keys as usual.
+    ///
+    /// The protocol is only enabled for the current screen buffer, it stops when the renderer exits.
+    pub fn enable_kitty_keyboard() {
+        // flags: 1 disambiguates the escape codes, 2 reports the event types
+        print!("\x1b[>3u");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Disable the kitty keyboard protocol.
+    pub fn disable_kitty_keyboard() {
+        print!("\x1b[<u");
+        stdout().flush().expect("Could not write to stdout");
+    }
+
+
+    /// Disable MouseEvent, enabled by `enable_mouse` or `enable_mouse_motion`.
+    pub fn disable_mouse() {
+        print!("\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l");
+        stdout().flush().expect("Could not write to stdout");
+    }
+}
+
+
+impl Default for Input {
+
+    /// Same as `Input::new`.
+    fn default() -> Self {
+        Input::new()
+    }
+}
+
+
+/// Single-line text editing driven by key events: typed characters are inserted at the cursor, which
+/// is moved with Left, Right, Home and End. Backspace and Delete remove the character before and after
+/// the cursor.
+/// 
+/// The text is edited by `char`: a character made of several code points (eg. an emoji with a skin tone
+/// modifier) needs several Backspace to be removed.
+/// 
+/// ```no_run
+/// use termkan::input::{Input, InputEvent, KeyEvent, LineEditor};
+/// 
+/// let mut editor = LineEditor::new();
+/// for event in Input::get().events() {
+///     match event {
+///         InputEvent::Key(KeyEvent::Char('\n')) => break,
+///         InputEvent::Key(key) => { editor.feed(key); }
+///         _ => ()
+///     }
+/// }
+/// println!("{}", editor.text());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineEditor {
+    text: String,
+    /// position of the cursor, in chars
+    cursor: usize
+}
+
+
+impl LineEditor {
+
+    /// Creates an empty editor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+
+    /// Creates an editor containing `text`, the cursor at the end.
+    pub fn with_text(text: &str) -> Self {
+        Self { text: text.to_string(), cursor: text.chars().count() }
+    }
+
+
+    /// Returns the edited text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+
+    /// Returns the position of the cursor, as the number of chars before it.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+
+    /// Removes all the text.
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+
+    /// Edits the text according to `key`. Returns false if the key is not an editing key (eg. Up,
+    /// Enter or Ctrl+c), which is then left to the caller.
+    pub fn feed(&mut self, key: KeyEvent) -> bool {
+        let len = self.text.chars().count();
+        match key {
+            KeyEvent::Char(c) if !c.is_control() => {
+                let i = self.byte_index(self.cursor);
+                self.text.insert(i, c);
+                self.cursor += 1;
+            }
+            KeyEvent::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    let i = self.byte_index(self.cursor);
+                    self.text.remove(i);
+                }
+            }
+            KeyEvent::Delete => {
+                if self.cursor < len {
+                    let i = self.byte_index(self.cursor);
+                    self.text.remove(i);
+                }
+            }
+            KeyEvent::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyEvent::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyEvent::Home => self.cursor = 0,
+            KeyEvent::End => self.cursor = len,
+            _ => return false
+        }
+        true
+    }
+
+
+    /// Returns the index in bytes of the char at `index`, the length of the text after the last char.
+    fn byte_index(&self, index: usize) -> usize {
+        self.text.char_indices().nth(index).map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+
+/// Click recognized by a `ClickDetector`, with the button and the position of the last press.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ClickEvent {
+    Click(MouseButton, Vec2),
+    DoubleClick(MouseButton, Vec2),
+    TripleClick(MouseButton, Vec2)
+}
+
+
+/// Time within which the presses of a double click must follow each other by default.
+pub const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+
+
+/// Recognizes double and triple clicks from the mouse events: presses of the same button, each less than
+/// `threshold` after the previous one, at most `tolerance` cells away from it on both axes.
+/// 
+/// ```no_run
+/// use termkan::input::{ClickDetector, ClickEvent, Input, InputEvent, MouseButton};
+/// 
+/// let mut clicks = ClickDetector::default();
+/// loop {
+///     if let (InputEvent::Mouse(event), time) = Input::get().get_event_blocking_timed() {
+///         if let Some(ClickEvent::DoubleClick(MouseButton::Left, pos)) = clicks.feed(event, time) {
+///             // open the file at pos
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClickDetector {
+    threshold: Duration,
+    tolerance: i32,
+    /// button, position and time of the last press, and number of presses in the current click
+    last: Option<(MouseButton, Vec2, Instant, u8)>
+}
+
+
+impl ClickDetector {
+
+    /// Creates a detector, see `ClickDetector` for `threshold` and `tolerance`.
+    pub fn new(threshold: Duration, tolerance: i32) -> Self {
+        Self { threshold, tolerance, last: None }
+    }
+
+
+    /// Processes a mouse event received at `time` (see `Input::get_event_timed`). Returns the click it
+    /// completes, if it is a press: a press after a triple click starts a new click.
+    pub fn feed(&mut self, event: MouseEvent, time: Instant) -> Option<ClickEvent> {
+        let (button, pos) = match event {
+            MouseEvent::ButtonPressed(button, pos, _) => (button, pos),
+            _ => return None
+        };
+
+        let count = match self.last {
+            Some((b, p, t, count)) if b == button
+                && count < 3
+                && time.saturating_duration_since(t) <= self.threshold
+                && (pos.x - p.x).abs() <= self.tolerance
+                && (pos.y - p.y).abs() <= self.tolerance => count + 1,
+            _ => 1
+        };
+        self.last = Some((button, pos, time, count));
+
+        Some(match count {
+            1 => ClickEvent::Click(button, pos),
+            2 => ClickEvent::DoubleClick(button, pos),
+            _ => ClickEvent::TripleClick(button, pos)
+        })
+    }
+}
+
+
+impl Default for ClickDetector {
+
+    /// Detector with `DOUBLE_CLICK_TIME` and a tolerance of 1 cell.
+    fn default() -> Self {
+        ClickDetector::new(DOUBLE_CLICK_TIME, 1)
+    }
+}
+
+
+/// Step of a drag reported by a `DragTracker`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DragEvent {
+    /// A button was pressed at the position, starting a drag.
+    Begin(MouseButton, Vec2),
+    /// The mouse moved to the position while holding the button, by the delta since the last event.
+    Move(MouseButton, Vec2, Vec2),
+    /// The button was released at the position, carries the vector from the start of the drag.
+    End(MouseButton, Vec2, Vec2)
+}
+
+
+/// Follows the drags of the mouse: a button pressed, held while moving (`MouseEvent::Hold`, reported when
+/// the mouse is enabled with `Input::enable_mouse`) and released.
+/// 
+/// ```no_run
+/// use termkan::input::{DragEvent, DragTracker, Input, InputEvent};
+/// 
+/// let mut drag = DragTracker::new();
+/// let mut selection = None;
+/// for event in Input::get().events() {
+///     if let InputEvent::Mouse(event) = event {
+///         if let Some(DragEvent::Move(_, pos, _)) = drag.feed(event) {
+///             selection = Some((drag.start().unwrap(), pos));
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct DragTracker {
+    /// button, start and current position of the drag
+    drag: Option<(MouseButton, Vec2, Vec2)>
+}
+
+
+impl DragTracker {
+
+    /// Creates a tracker, no drag is in progress.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+
+    /// Processes a mouse event. Returns the step of the drag it makes, if any.
+    pub fn feed(&mut self, event: MouseEvent) -> Option<DragEvent> {
+        match (event, self.drag) {
+            (MouseEvent::ButtonPressed(button, pos, _), _) => {
+                self.drag = Some((button, pos, pos));
+                Some(DragEvent::Begin(button, pos))
+            }
+            (MouseEvent::Hold(_, pos, _), Some((button, start, current))) => {
+                self.drag = Some((button, start, pos));
+                Some(DragEvent::Move(button, pos, pos - current))
+            }
+            (MouseEvent::ButtonReleased(_, pos, _), Some((button, start, _))) => {
+                self.drag = None;
+                Some(DragEvent::End(button, pos, pos - start))
+            }
+            _ => None
+        }
+    }
+
+
+    /// Returns true if a drag is in progress.
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+
+    /// Returns the button of the drag in progress.
+    pub fn button(&self) -> Option<MouseButton> {
+        self.drag.map(|(button, _, _)| button)
+    }
+
+
+    /// Returns the position where the drag in progress started.
+    pub fn start(&self) -> Option<Vec2> {
+        self.drag.map(|(_, start, _)| start)
+    }
+
+
+    /// Returns the current position of the drag in progress.
+    pub fn current(&self) -> Option<Vec2> {
+        self.drag.map(|(_, _, current)| current)
+    }
+
+
+    /// Returns the vector from the start to the current position of the drag in progress.
+    pub fn vector(&self) -> Option<Vec2> {
+        self.drag.map(|(_, start, current)| current - start)
     }
 }
@@ -31,6 +31,7 @@
 */
 
 
+#[cfg(unix)]
 extern crate termios;
 extern crate image;
 
@@ -41,6 +42,8 @@ pub mod img;
 
 pub mod rds;
 pub mod input;
+pub mod widgets;
+pub mod boxchars;
 
 
 
@@ -50,20 +53,90 @@ mod tests {
 
     use crate::rds::Renderer;
 
-    use crate::math::Vec2;
+    use crate::math::{Rect, Vec2};
     use crate::img::*;
-    use crate::input::{Input, InputEvent, KeyEvent, MouseEvent};
+    use crate::input::{Input, InputEvent, KeyEvent, KeyParseError, ModeStatus, Modifiers, MouseButton, MouseEvent, ScrollDirection};
 
+    use crate::rds::{RendererConfig, RenderingServer, RenderingDirective, Priority, RenderMode, CursorStyle, CapturedDirective};
+    use crate::widgets::List;
+
+    use std::io::{self, Write};
     use std::sync::{Arc, Mutex};
 
 
+    /// Output sink shared between a test and a rendering server.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+
+    /// Reader returning one byte at a time, waiting `delay` before each read.
+    struct SlowReader {
+        bytes: std::collections::VecDeque<u8>,
+        delay: std::time::Duration
+    }
+
+    impl SlowReader {
+        fn new(bytes: &[u8], delay: std::time::Duration) -> Self {
+            Self { bytes: bytes.iter().copied().collect(), delay }
+        }
+    }
+
+    impl io::Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            std::thread::sleep(self.delay);
+            match self.bytes.pop_front() {
+                Some(b) if !buf.is_empty() => {
+                    buf[0] = b;
+                    Ok(1)
+                }
+                _ => Ok(0)
+            }
+        }
+    }
+
+
+    /// Creates a rendering server of size `size` writing to a SharedBuffer, which is empty when it is returned.
+    fn headless_server(size: Vec2) -> (RenderingServer, SharedBuffer) {
+        let buffer = SharedBuffer::default();
+        let mut server = RenderingServer::new(buffer.clone(), &RendererConfig::default());
+        server.execute(RenderingDirective::SetSynchronized(false));
+        server.execute(RenderingDirective::SetColorMode(ColorMode::TrueColor));
+        server.execute(RenderingDirective::UpdateScreenSize(size));
+        buffer.take();
+        (server, buffer)
+    }
+
+
+    fn capture(server: &mut RenderingServer) -> Image {
+        let (response, frame) = std::sync::mpsc::channel();
+        server.execute(RenderingDirective::CaptureFrame(response));
+        frame.recv().unwrap()
+    }
+
+
     #[test]
     fn renderer() {
         // load an image and draw it on screen
         let img = Arc::new(Mutex::new(Image::load("icon.png").unwrap()));
 
         // get the renderer
-        let rdr = Renderer::get();
+        let mut rdr = Renderer::get();
 
 
         // draw a frame on screen
@@ -93,9 +166,10 @@ mod tests {
 
     #[test]
     fn input() {
-        let rdr = Renderer::get();
-        let inp = Input::get();
+        let mut rdr = Renderer::get();
+        let mut inp = Input::get();
         Input::enable_mouse();
+        rdr.set_target_fps(60);
 
         let mut pos = Renderer::get_size() / 2;
 
@@ -115,8 +189,8 @@ mod tests {
                         _ => ()
                     }
                     InputEvent::Mouse(event) => match event {
-                        MouseEvent::ButtonPressed(_, mpos) | MouseEvent::Hold(_, mpos)
-                            => pos = mpos,
+                        MouseEvent::ButtonPressed(_, cell, _) | MouseEvent::Hold(_, cell, _)
+                            => pos = rdr.cell_to_pixel(cell),
                         _ => ()
                     }
                     _ => ()
@@ -133,4 +207,2695 @@ mod tests {
             rdr.end_draw();
         }
     }
+
+
+    #[test]
+    fn synchronized_output() {
+        let (mut server, buffer) = headless_server(vec2!(8, 8));
+
+        server.execute(RenderingDirective::SetSynchronized(true));
+        server.execute(RenderingDirective::DrawRect(vec2!(2, 2), vec2!(4, 4), Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        let out = buffer.take();
+        assert!(out.starts_with("\x1b[?2026h"));
+        assert!(out.ends_with("\x1b[?2026l"));
+        assert!(out.contains(&format!("{:-}", Color::RED)));
+
+        server.execute(RenderingDirective::SetSynchronized(false));
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 0), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        assert!(!buffer.take().contains("2026"));
+    }
+
+
+    #[test]
+    fn color_from_hex_string() {
+        assert_eq!(Color::from_str_hex("#abc"), Ok(Color::hex(0xaabbcc)));
+        assert_eq!(Color::from_str_hex("ff8800"), Ok(Color::hex(0xff8800)));
+        assert_eq!(Color::from_str_hex("#FF8800"), Ok(Color::hex(0xff8800)));
+        assert_eq!(Color::from_str_hex("ffaa00cc"), Ok(Color::hex(0xffaa00)));
+        assert_eq!("#abc".parse::<Color>(), Ok(Color::hex(0xaabbcc)));
+
+        assert_eq!(Color::from_str_hex(""), Err(ColorParseError::InvalidLength(0)));
+        assert_eq!(Color::from_str_hex("#abcd"), Err(ColorParseError::InvalidLength(4)));
+        assert_eq!(Color::from_str_hex("##abc"), Err(ColorParseError::InvalidCharacter('#')));
+        assert_eq!(Color::from_str_hex("#ff88zz"), Err(ColorParseError::InvalidCharacter('z')));
+    }
+
+
+    #[test]
+    fn key_spec() {
+        assert_eq!(KeyEvent::parse("Ctrl+c"), Ok(KeyEvent::Ctrl('c')));
+        assert_eq!(KeyEvent::parse("alt+x"), Ok(KeyEvent::Alt('x')));
+        assert_eq!(KeyEvent::parse("Up"), Ok(KeyEvent::Up));
+        assert_eq!(KeyEvent::parse("F5"), Ok(KeyEvent::F(5)));
+        assert_eq!(KeyEvent::parse("+"), Ok(KeyEvent::Char('+')));
+        assert_eq!(KeyEvent::parse("Ctrl++"), Ok(KeyEvent::Ctrl('+')));
+
+        let keys = [
+            KeyEvent::Ctrl('c'), KeyEvent::Alt('x'), KeyEvent::Up, KeyEvent::PageDown, KeyEvent::F(12),
+            KeyEvent::Char('a'), KeyEvent::Char(' '), KeyEvent::Char('\n'), KeyEvent::Alt(' '), KeyEvent::Esc
+        ];
+        for key in keys {
+            assert_eq!(KeyEvent::parse(&key.to_string()), Ok(key));
+        }
+
+        assert_eq!(KeyEvent::parse("Hyper"), Err(KeyParseError::UnknownKey("Hyper".to_string())));
+        assert_eq!(KeyEvent::parse("Shift+a"), Err(KeyParseError::UnknownModifier("Shift".to_string())));
+        assert!(KeyEvent::parse("F0").is_err());
+        assert!(KeyEvent::parse("").is_err());
+    }
+
+
+    #[test]
+    fn frame_bytes() {
+        let (mut server, buffer) = headless_server(vec2!(40, 20));
+        let frame_bytes = |server: &mut RenderingServer| {
+            let (response, bytes) = std::sync::mpsc::channel();
+            server.execute(RenderingDirective::GetFrameBytes(response));
+            bytes.recv().unwrap()
+        };
+
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        let full = frame_bytes(&mut server);
+        assert_eq!(full, buffer.take().len());
+        assert!(full > 400);
+
+        server.execute(RenderingDirective::PushFrame);
+        let empty = frame_bytes(&mut server);
+        assert_eq!(empty, buffer.take().len());
+        assert!(empty < 10);
+    }
+
+
+    #[test]
+    fn ansi_palettes() {
+        assert_eq!(Color::BLACK.to_ansi256(), 16);
+        assert_eq!(Color::WHITE.to_ansi256(), 231);
+        assert_eq!(Color::rgb(255, 0, 0).to_ansi256(), 196);
+        assert_eq!(Color::rgb(95, 135, 175).to_ansi256(), 67);
+        assert_eq!(Color::rgb(128, 128, 128).to_ansi256(), 244);
+        assert_eq!(Color::rgb(8, 8, 8).to_ansi256(), 232);
+        assert_eq!(Color::rgb(238, 238, 238).to_ansi256(), 255);
+
+        assert_eq!(Color::BLACK.to_ansi16(), 0);
+        assert_eq!(Color::rgb(255, 0, 0).to_ansi16(), 9);
+        assert_eq!(Color::rgb(200, 10, 0).to_ansi16(), 1);
+        assert_eq!(Color::WHITE.to_ansi16(), 15);
+
+        let (mut server, buffer) = headless_server(vec2!(1, 2));
+        server.execute(RenderingDirective::SetColorMode(ColorMode::Ansi256));
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 0), Color::rgb(255, 0, 0)));
+        server.execute(RenderingDirective::PushFrame);
+        let out = buffer.take();
+        assert!(out.contains("\x1b[48;5;196m"));
+        assert!(!out.contains("38;2;"));
+
+        server.execute(RenderingDirective::SetColorMode(ColorMode::Ansi16));
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 0), Color::rgb(90, 90, 250)));
+        server.execute(RenderingDirective::PushFrame);
+        assert!(buffer.take().contains("\x1b[104m"));
+    }
+
+
+    #[test]
+    fn list_widget() {
+        let mut list = List::new((0..10).collect::<Vec<i32>>(), vec2!(0, 0), vec2!(10, 6), 2);
+        assert_eq!(list.visible_rows(), 3);
+        assert_eq!(list.selected(), 0);
+
+        let down = InputEvent::Key(KeyEvent::Down);
+        assert!(list.handle_event(&down));
+        assert_eq!((list.selected(), list.scroll()), (1, 0));
+        list.handle_event(&down);
+        assert_eq!((list.selected(), list.scroll()), (2, 0));
+        list.handle_event(&down);
+        assert_eq!((list.selected(), list.scroll()), (3, 1));
+
+        list.handle_event(&InputEvent::Key(KeyEvent::End));
+        assert_eq!((list.selected(), list.scroll()), (9, 7));
+        list.handle_event(&down);
+        assert_eq!(list.selected_item(), Some(&9));
+        list.handle_event(&InputEvent::Key(KeyEvent::Up));
+        assert_eq!((list.selected(), list.scroll()), (8, 7));
+        assert!(!list.handle_event(&InputEvent::Key(KeyEvent::Char('x'))));
+
+        let mut img = Image::new(10, 6);
+        let mut drawn = Vec::new();
+        list.draw(&mut img, |_, item, pos, _, selected| drawn.push((*item, pos.y, selected)));
+        assert_eq!(drawn, vec![(7, 0, false), (8, 2, true), (9, 4, false)]);
+        assert_eq!(img[vec2!(0, 2)], Color::DIM_GRAY);
+        assert_eq!(img[vec2!(0, 0)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn list_widget_mouse() {
+        let none = Modifiers::default();
+        let click = |x, y| InputEvent::Mouse(MouseEvent::ButtonPressed(MouseButton::Left, vec2!(x, y), none));
+
+        // rows of 2 pixels starting at the pixel row 2: one row per cell in half block mode
+        let mut list = List::new((0..10).collect::<Vec<i32>>(), vec2!(0, 2), vec2!(10, 12), 2);
+        assert!(list.handle_event(&click(3, 3)));
+        assert_eq!(list.selected(), 2);
+        assert!(!list.handle_event(&click(3, 0)));
+        assert!(!list.handle_event(&click(3, 7)));
+        assert_eq!(list.selected(), 2);
+
+        // the cells of the Braille mode are 4 pixels tall
+        list.set_render_mode(RenderMode::Braille);
+        assert!(list.handle_event(&click(1, 2)));
+        assert_eq!(list.selected(), 3);
+
+        // the wheel scrolls the list under the mouse only
+        let scroll = |y| InputEvent::Mouse(MouseEvent::Scroll(ScrollDirection::Down, vec2!(0, y), none));
+        assert!(list.handle_event(&scroll(1)));
+        assert_eq!(list.scroll(), 1);
+        assert!(!list.handle_event(&scroll(4)));
+    }
+
+
+    #[test]
+    fn color_mode_detection() {
+        use crate::rds::color_mode_from;
+
+        assert_eq!(color_mode_from("truecolor", "xterm"), ColorMode::TrueColor);
+        assert_eq!(color_mode_from("24bit", "xterm-256color"), ColorMode::TrueColor);
+        assert_eq!(color_mode_from("", "xterm-256color"), ColorMode::Ansi256);
+        assert_eq!(color_mode_from("yes", "screen-256color"), ColorMode::Ansi256);
+        assert_eq!(color_mode_from("", "xterm"), ColorMode::Ansi16);
+        assert_eq!(color_mode_from("", "linux"), ColorMode::Ansi16);
+    }
+
+
+    #[test]
+    fn image_concat() {
+        let mut a = Image::new(2, 2);
+        a.clear(Color::RED);
+        let mut b = Image::new(2, 2);
+        b.clear(Color::BLUE);
+        let c = Image::new(1, 3);
+
+        let h = Image::concat_h(&[&a, &b], Color::WHITE);
+        assert_eq!(h.size(), vec2!(4, 2));
+        for j in 0..2 {
+            assert_eq!([h[(0, j)], h[(1, j)], h[(2, j)], h[(3, j)]], [Color::RED, Color::RED, Color::BLUE, Color::BLUE]);
+        }
+
+        let v = Image::concat_v(&[&a, &c], Color::WHITE);
+        assert_eq!(v.size(), vec2!(2, 5));
+        assert_eq!(v[(1, 1)], Color::RED);
+        assert_eq!(v[(0, 2)], Color::BLACK);
+        assert_eq!(v[(1, 2)], Color::WHITE);
+
+        assert_eq!(Image::concat_h(&[], Color::WHITE).size(), Vec2::ZERO);
+    }
+
+
+    #[test]
+    fn out_of_range_writes() {
+        let mut img = Image::new(4, 4);
+        assert!(img.set_pixel(vec2!(1, 2), Color::RED));
+        assert!(!img.set_pixel(vec2!(4, 0), Color::RED));
+        assert!(!img.set_pixel(vec2!(-1, 2), Color::RED));
+
+        img.point((100, 100), Color::BLUE);
+        img.line((-5, -5), (20, 20), Color::GREEN);
+        img.rect((2, 2), (10, 10), Color::WHITE);
+        img.ellipse_boundary((0, 0), (10, 10), Color::YELLOW);
+
+        assert_eq!(img[(0, 0)], Color::GREEN);
+        assert_eq!(img[(1, 1)], Color::GREEN);
+        assert_eq!(img[(1, 2)], Color::RED);
+        assert_eq!(img[(3, 3)], Color::WHITE);
+        assert_eq!(img[(3, 0)], Color::BLACK);
+        assert_eq!(img[(100, 100)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn get_pixel() {
+        let mut img = Image::new(3, 2);
+        img.point((2, 1), Color::RED);
+
+        assert_eq!(img.get_pixel(vec2!(2, 1)), Some(Color::RED));
+        assert_eq!(img.get_pixel(vec2!(0, 0)), Some(Color::BLACK));
+        assert_eq!(img.get_pixel(vec2!(3, 1)), None);
+        assert_eq!(img.get_pixel(vec2!(0, -1)), None);
+        assert_eq!(img[(3, 1)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn input_timestamps() {
+        let start = std::time::Instant::now();
+        let mut inp = Input::with_reader(SlowReader::new(b"ab", std::time::Duration::from_millis(5)));
+
+        let (a, ta) = inp.get_event_blocking_timed();
+        let (b, tb) = inp.get_event_blocking_timed();
+        assert_eq!(a, InputEvent::Key(KeyEvent::Char('a')));
+        assert_eq!(b, InputEvent::Key(KeyEvent::Char('b')));
+        assert!(start < ta && ta < tb);
+        assert!(tb <= std::time::Instant::now());
+    }
+
+
+    #[test]
+    fn single_color_frame() {
+        let (mut server, buffer) = headless_server(vec2!(20, 10));
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        let out = buffer.take();
+        // cursor position at the start of each of the 5 rows and the color
+        assert_eq!(out.matches("\x1b[").count(), 6);
+        assert_eq!(out.matches(' ').count(), 100);
+
+        server.execute(RenderingDirective::ClearScreen(Color::BLUE));
+        server.execute(RenderingDirective::DrawRect(vec2!(0, 0), vec2!(20, 5), Color::GREEN));
+        server.execute(RenderingDirective::PushFrame);
+        let out = buffer.take();
+        assert_eq!(out.matches("\x1b[").count(), 7);
+        assert!(out.contains(&format!("{:+}▄", Color::BLUE)));
+        assert_eq!(out.matches('█').count(), 40);
+    }
+
+
+    #[test]
+    fn filled_ellipse() {
+        let count = |img: &Image, c: Color| {
+            let mut n = 0;
+            for j in 0..img.size().y {
+                for i in 0..img.size().x {
+                    if img[(i, j)] == c { n += 1; }
+                }
+            }
+            n
+        };
+
+        let mut img = Image::new(10, 10);
+        img.ellipse((5, 5), (4, 4), Color::RED);
+        assert_eq!(count(&img, Color::RED), 13);
+        assert_eq!(img[(5, 3)], Color::RED);
+        assert_eq!(img[(4, 4)], Color::RED);
+        assert_eq!(img[(3, 3)], Color::BLACK);
+
+        // odd sizes are rounded down, zero sizes give lines
+        let mut img = Image::new(10, 10);
+        img.ellipse((5, 5), (5, 5), Color::RED);
+        assert_eq!(count(&img, Color::RED), 13);
+        let mut img = Image::new(10, 10);
+        img.ellipse((5, 5), (6, 0), Color::RED);
+        assert_eq!(count(&img, Color::RED), 7);
+        let mut img = Image::new(10, 10);
+        img.ellipse((5, 5), (0, 0), Color::RED);
+        assert_eq!(count(&img, Color::RED), 1);
+
+        // clipped at the edges
+        let mut img = Image::new(10, 10);
+        img.ellipse((0, 0), (4, 4), Color::RED);
+        assert_eq!(count(&img, Color::RED), 6);
+        img.ellipse((50, 50), (20, 20), Color::RED);
+        assert_eq!(count(&img, Color::RED), 6);
+    }
+
+
+    #[test]
+    fn image_transpose() {
+        let mut img = Image::new(3, 2);
+        img.point((2, 0), Color::RED);
+        img.point((0, 1), Color::BLUE);
+
+        let t = img.transpose();
+        assert_eq!(t.size(), vec2!(2, 3));
+        assert_eq!(t[(0, 2)], Color::RED);
+        assert_eq!(t[(1, 0)], Color::BLUE);
+
+        let tt = t.transpose();
+        assert_eq!(tt.size(), img.size());
+        for j in 0..2 {
+            for i in 0..3 {
+                assert_eq!(tt[(i, j)], img[(i, j)]);
+            }
+        }
+    }
+
+
+    #[test]
+    fn initial_clear_color() {
+        let buffer = SharedBuffer::default();
+        let mut server = RenderingServer::new(buffer.clone(), &RendererConfig::new().clear_color(Color::NAVY_BLUE));
+        server.execute(RenderingDirective::SetColorMode(ColorMode::TrueColor));
+        server.start().unwrap();
+        let out = buffer.take();
+        assert!(out.starts_with(&format!("{:-}", Color::NAVY_BLUE)));
+        assert!(out.ends_with("\x1b[2J"));
+
+        // the first frame starts from the clear color
+        server.execute(RenderingDirective::UpdateScreenSize(vec2!(4, 4)));
+        server.execute(RenderingDirective::PushFrame);
+        let out = buffer.take();
+        assert!(!out.contains("\x1b[48"));
+        assert_eq!(out.matches(' ').count(), 8);
+
+        let mut server = RenderingServer::new(buffer.clone(), &RendererConfig::default());
+        server.start().unwrap();
+        assert!(!buffer.take().contains("2J"));
+    }
+
+
+    #[test]
+    fn polygon_fill() {
+        let mut img = Image::new(4, 4);
+        img.polygon(&[vec2!(0, 0), vec2!(4, 0), vec2!(4, 4), vec2!(0, 4)], Color::RED);
+        assert!((0..4).all(|j| (0..4).all(|i| img[(i, j)] == Color::RED)));
+
+        // concave arrow pointing right
+        let arrow = [
+            vec2!(0, 4), vec2!(10, 4), vec2!(10, 0), vec2!(20, 10),
+            vec2!(10, 20), vec2!(10, 16), vec2!(0, 16)
+        ];
+        let mut img = Image::new(24, 24);
+        img.polygon(&arrow, Color::RED);
+        assert_eq!(img[(5, 10)], Color::RED);    // shaft
+        assert_eq!(img[(15, 10)], Color::RED);   // head
+        assert_eq!(img[(12, 3)], Color::RED);    // head, above the shaft
+        assert_eq!(img[(5, 2)], Color::BLACK);   // notch above the shaft
+        assert_eq!(img[(5, 18)], Color::BLACK);  // notch below the shaft
+        assert_eq!(img[(19, 3)], Color::BLACK);  // outside of the head
+
+        // self-intersecting shapes
+        let mut img = Image::new(8, 8);
+        img.polygon(&[vec2!(0, 0), vec2!(8, 8), vec2!(8, 0), vec2!(0, 8)], Color::RED);
+        assert_eq!(img[(1, 4)], Color::RED);
+        assert_eq!(img[(6, 4)], Color::RED);
+        assert_eq!(img[(4, 1)], Color::BLACK);
+        assert_eq!(img[(4, 6)], Color::BLACK);
+
+        let star = [vec2!(10, 0), vec2!(16, 19), vec2!(0, 7), vec2!(20, 7), vec2!(4, 19)];
+        let mut img = Image::new(20, 20);
+        img.polygon(&star, Color::RED);
+        assert_eq!(img[(10, 11)], Color::BLACK); // center of the pentagram, covered twice
+        assert_eq!(img[(10, 3)], Color::RED);    // top branch
+        assert_eq!(img[(3, 8)], Color::RED);     // left branch
+
+        let mut img = Image::new(8, 8);
+        img.polygon_boundary(&[vec2!(1, 1), vec2!(5, 1), vec2!(5, 5)], Color::RED);
+        assert_eq!(img[(3, 1)], Color::RED);
+        assert_eq!(img[(3, 3)], Color::RED);
+        assert_eq!(img[(4, 2)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn capture_frame() {
+        let (mut server, _) = headless_server(vec2!(8, 6));
+        server.execute(RenderingDirective::ClearScreen(Color::BLUE));
+        server.execute(RenderingDirective::DrawRect(vec2!(1, 1), vec2!(3, 2), Color::RED));
+        server.execute(RenderingDirective::DrawLine(vec2!(0, 5), vec2!(7, 5), Color::WHITE));
+
+        let mut expected = Image::new(8, 6);
+        expected.clear(Color::BLUE);
+        expected.rect((1, 1), (3, 2), Color::RED);
+        expected.line((0, 5), (7, 5), Color::WHITE);
+        assert_eq!(capture(&mut server), expected);
+
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(capture(&mut server), expected);
+    }
+
+
+    #[test]
+    fn bezier_curves() {
+        // the midpoint of a quadratic curve is (p0 + 2 p1 + p2) / 4
+        let mut img = Image::new(40, 40);
+        img.bezier_quad((0, 0), (20, 40), (40, 0), Color::RED);
+        assert_eq!(img[(20, 20)], Color::RED);
+        assert_eq!(img[(0, 0)], Color::RED);
+        assert_eq!(img[(39, 2)], Color::RED);
+        assert_eq!(img[(20, 30)], Color::BLACK);
+
+        // the midpoint of a cubic curve is (p0 + 3 p1 + 3 p2 + p3) / 8
+        let mut img = Image::new(40, 40);
+        img.bezier_cubic((0, 30), (0, 0), (32, 0), (32, 30), Color::RED);
+        assert_eq!(img[(16, 8)], Color::RED);
+        assert_eq!(img[(32, 30)], Color::RED);
+        assert_eq!(img[(16, 0)], Color::BLACK);
+
+        // the curve is continuous: every row between the endpoints has a pixel
+        for y in 8..=30 {
+            assert!((0..16).any(|x| img[(x, y)] == Color::RED));
+        }
+
+        // degenerate curve
+        let mut img = Image::new(4, 4);
+        img.bezier_cubic((1, 1), (1, 1), (1, 1), (1, 1), Color::RED);
+        assert_eq!(img[(1, 1)], Color::RED);
+    }
+
+
+    #[test]
+    fn gradient_line() {
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 0.5), Color::rgb(128, 128, 128));
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 2.0), Color::WHITE);
+
+        let mut img = Image::new(20, 20);
+        img.line_gradient((2, 3), (12, 13), Color::RED, Color::BLUE);
+        assert_eq!(img[(2, 3)], Color::RED);
+        assert_eq!(img[(12, 13)], Color::BLUE);
+
+        let middle = img[(7, 8)];
+        assert!(middle.r > 0 && middle.r < 255);
+        assert!(middle.b > 0 && middle.b < 255);
+        assert_eq!(middle, Color::RED.lerp(Color::BLUE, 0.5));
+
+        let mut img = Image::new(4, 4);
+        img.line_gradient((1, 1), (1, 1), Color::RED, Color::BLUE);
+        assert_eq!(img[(1, 1)], Color::RED);
+    }
+
+
+    #[test]
+    fn ordered_dithering() {
+        for i in 16..=255 {
+            assert_eq!(Color::from_ansi256(i).to_ansi256(), i);
+        }
+
+        let mut img = Image::new(8, 8);
+        img.clear(Color::rgb(64, 64, 64));
+        let dithered = img.dither(ColorMode::Ansi16);
+
+        // only palette colors, alternating with a period of 4 pixels
+        let dark = dithered[(0, 0)];
+        assert!(dark == Color::BLACK || dark == Color::rgb(127, 127, 127));
+        assert!((0..8).any(|i| dithered[(i, 0)] != dark));
+        for j in 0..8 {
+            for i in 0..8 {
+                assert_eq!(dithered[(i, j)], dithered[(i % 4, j % 4)]);
+                assert_eq!(dithered[(i, j)], ColorMode::Ansi16.nearest(dithered[(i, j)]));
+            }
+        }
+        assert_eq!(img.dither(ColorMode::Ansi16), dithered);
+        assert_eq!(img.dither(ColorMode::TrueColor), img);
+
+        // the renderer outputs the same frame twice
+        let (mut server, buffer) = headless_server(vec2!(8, 8));
+        server.execute(RenderingDirective::SetColorMode(ColorMode::Ansi16));
+        server.execute(RenderingDirective::SetDithering(true));
+        server.execute(RenderingDirective::ClearScreen(Color::rgb(64, 64, 64)));
+        server.execute(RenderingDirective::PushFrame);
+        let first = buffer.take();
+        assert!(first.contains("\x1b[90m") || first.contains("\x1b[100m"));
+        assert!(first.contains('▀') || first.contains('▄'));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take(), "\x1b[H");
+    }
+
+
+    #[test]
+    fn rounded_rect() {
+        let mut rounded = Image::new(12, 12);
+        rounded.rounded_rect((1, 2), (8, 6), 0, Color::RED);
+        let mut plain = Image::new(12, 12);
+        plain.rect((1, 2), (8, 6), Color::RED);
+        assert_eq!(rounded, plain);
+
+        // corners are cut
+        let mut img = Image::new(12, 12);
+        img.rounded_rect((0, 0), (10, 10), 3, Color::RED);
+        assert_eq!(img[(0, 0)], Color::BLACK);
+        assert_eq!(img[(9, 9)], Color::BLACK);
+        assert_eq!(img[(5, 0)], Color::RED);
+        assert_eq!(img[(0, 5)], Color::RED);
+        assert_eq!(img[(1, 1)], Color::RED);
+
+        // a large radius gives a pill
+        let mut img = Image::new(30, 12);
+        img.rounded_rect((0, 0), (20, 8), 100, Color::RED);
+        let row = |img: &Image, y: i32| (0..30).filter(|&x| img[(x, y)] == Color::RED).count();
+        assert_eq!(row(&img, 3), 20);
+        assert_eq!(row(&img, 4), 20);
+        assert!(row(&img, 0) <= 20 - 4);
+        assert!(row(&img, 0) < row(&img, 1) && row(&img, 1) < row(&img, 2));
+        assert_eq!(row(&img, 0), row(&img, 7));
+
+        // the outline surrounds the filled rectangle
+        let mut outline = Image::new(12, 12);
+        outline.rounded_rect_boundary((1, 1), (10, 8), 3, Color::RED);
+        let mut filled = Image::new(12, 12);
+        filled.rounded_rect((1, 1), (10, 8), 3, Color::RED);
+        for j in 0..12 {
+            for i in 0..12 {
+                if outline[(i, j)] == Color::RED {
+                    assert_eq!(filled[(i, j)], Color::RED);
+                }
+            }
+        }
+        assert_eq!(outline[(5, 1)], Color::RED);
+        assert_eq!(outline[(1, 5)], Color::RED);
+        assert_eq!(outline[(5, 5)], Color::BLACK);
+        assert_eq!(outline[(1, 1)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn blit_scaled_int() {
+        let mut src = Image::new(2, 2);
+        src[(0, 0)] = Color::RED;
+        src[(1, 0)] = Color::GREEN;
+        src[(0, 1)] = Color::BLUE;
+        src[(1, 1)] = Color::WHITE;
+
+        let mut img = Image::new(8, 8);
+        img.blit_scaled_int(&src, (1, 1), 3);
+        for j in 0..8 {
+            for i in 0..8 {
+                let expected = if (1..7).contains(&i) && (1..7).contains(&j) {
+                    src[((i - 1) / 3, (j - 1) / 3)]
+                } else {
+                    Color::BLACK
+                };
+                assert_eq!(img[(i, j)], expected);
+            }
+        }
+
+        // clipped at the borders
+        let mut img = Image::new(4, 4);
+        img.blit_scaled_int(&src, (-2, 2), 3);
+        assert_eq!(img[(0, 2)], Color::RED);
+        assert_eq!(img[(1, 3)], Color::GREEN);
+    }
+
+
+    #[test]
+    #[should_panic]
+    fn blit_scaled_int_zero_factor() {
+        let src = Image::new(2, 2);
+        Image::new(4, 4).blit_scaled_int(&src, (0, 0), 0);
+    }
+
+
+    #[test]
+    fn low_priority_over_budget() {
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, Instant};
+
+        let (mut server, _) = headless_server(vec2!(4, 4));
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = Arc::clone(&now);
+        server.set_clock(move || *clock.lock().unwrap());
+        server.execute(RenderingDirective::SetFrameBudget(Some(Duration::from_millis(10))));
+
+        let draw = |server: &mut RenderingServer, priority, p: Vec2| {
+            server.execute(RenderingDirective::SetPriority(priority));
+            server.execute(RenderingDirective::DrawPoint(p, Color::RED));
+        };
+        // within the budget, everything is drawn
+        server.execute(RenderingDirective::BeginFrame);
+        *now.lock().unwrap() += Duration::from_millis(5);
+        draw(&mut server, Priority::Low, vec2!(0, 0));
+        draw(&mut server, Priority::High, vec2!(1, 0));
+        let frame = capture(&mut server);
+        assert_eq!(frame[(0, 0)], Color::RED);
+        assert_eq!(frame[(1, 0)], Color::RED);
+        server.execute(RenderingDirective::PushFrame);
+
+        // over the budget, low priority draws are skipped
+        server.execute(RenderingDirective::BeginFrame);
+        *now.lock().unwrap() += Duration::from_millis(20);
+        draw(&mut server, Priority::Low, vec2!(2, 0));
+        draw(&mut server, Priority::High, vec2!(3, 0));
+        let frame = capture(&mut server);
+        assert_eq!(frame[(2, 0)], Color::BLACK);
+        assert_eq!(frame[(3, 0)], Color::RED);
+        server.execute(RenderingDirective::PushFrame);
+
+        // the budget is measured from the beginning of each frame
+        server.execute(RenderingDirective::BeginFrame);
+        draw(&mut server, Priority::Low, vec2!(2, 1));
+        assert_eq!(capture(&mut server)[(2, 1)], Color::RED);
+    }
+
+
+    #[test]
+    fn gradients() {
+        let mut img = Image::new(11, 3);
+        img.gradient_linear((0, 0), (10, 0), Color::BLACK, Color::WHITE);
+        assert_eq!(img[(5, 0)], Color::rgb(128, 128, 128));
+        assert_eq!(img[(5, 2)], Color::rgb(128, 128, 128));
+        assert_eq!(img[(0, 1)], Color::BLACK);
+        assert_eq!(img[(10, 1)], Color::WHITE);
+
+        // the projection is clamped outside of the axis
+        let mut img = Image::new(11, 3);
+        img.gradient_linear((3, 0), (7, 0), Color::RED, Color::BLUE);
+        assert_eq!(img[(0, 0)], Color::RED);
+        assert_eq!(img[(10, 2)], Color::BLUE);
+
+        let mut img = Image::new(11, 11);
+        img.gradient_radial((5, 5), 4, Color::WHITE, Color::BLACK);
+        assert_eq!(img[(5, 5)], Color::WHITE);
+        assert_eq!(img[(7, 5)], Color::rgb(128, 128, 128));
+        assert_eq!(img[(5, 9)], Color::BLACK);
+        assert_eq!(img[(0, 0)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn vec2_lerp_int() {
+        let a = vec2!(-7, 3);
+        let b = vec2!(25, -14);
+        for (num, den) in [(0, 1), (1, 1), (1, 2), (1, 3), (2, 3), (1, 4), (3, 4), (5, 8), (7, 10), (-1, 2), (3, 2), (1, -4),
+                           (5, 2), (-3, 2), (7, 3)] {
+            assert_eq!(a.lerp_int(b, num, den), a.lerp(b, num as f32 / den as f32), "{}/{}", num, den);
+        }
+
+        // the value is rounded, not only the offset
+        let (a, b) = (vec2!(-1, 3), vec2!(0, 4));
+        assert_eq!(a.lerp_int(b, 1, 2), vec2!(-1, 4));
+        assert_eq!(a.lerp_int(b, 1, 2), a.lerp(b, 0.5));
+
+        // no overflow on large vectors, where lerp loses precision
+        let a = vec2!(i32::MIN, 0);
+        let b = vec2!(i32::MAX, 0);
+        assert_eq!(a.lerp_int(b, 1, 2), vec2!(-1, 0));
+        assert_eq!(a.lerp_int(b, 1, 1), b);
+        assert_eq!(a.lerp_int(b, i32::MAX, 1), vec2!(i32::MAX, 0));
+        assert_eq!(a.lerp_int(b, i32::MIN, -1), vec2!(i32::MAX, 0));
+        assert_eq!(a.lerp(b, 0.5), vec2!(0, 0));
+        assert_eq!(a.lerp(b, 0.0), a);
+    }
+
+
+    #[test]
+    fn vec2_sub_assign() {
+        let mut v = vec2!(3, -4);
+        v -= vec2!(1, 2);
+        assert_eq!(v, vec2!(2, -6));
+        v -= vec2!(2, -6);
+        assert_eq!(v, Vec2::ZERO);
+
+        // the compound operators agree with the binary ones
+        let (a, b) = (vec2!(7, -3), vec2!(-2, 5));
+        let mut v = a;
+        v -= b;
+        assert_eq!(v, a - b);
+        let mut v = a;
+        v += b;
+        assert_eq!(v, a + b);
+        assert_ne!(a - b, a + b);
+    }
+
+
+    #[test]
+    fn whole_image_alpha() {
+        use std::sync::{Arc, Mutex};
+
+        let mut sprite = Image::new(3, 2);
+        sprite.clear(Color::RED);
+        sprite[(1, 0)] = Color::BLACK;
+        let sprite = Arc::new(Mutex::new(sprite));
+
+        let (mut server, _) = headless_server(vec2!(6, 6));
+        server.execute(RenderingDirective::ClearScreen(Color::BLUE));
+        server.execute(RenderingDirective::DrawWholeImageAlpha(Arc::clone(&sprite), vec2!(1, 1), Color::BLACK));
+        server.execute(RenderingDirective::DrawWholeImage(Arc::clone(&sprite), vec2!(-1, 4)));
+        server.execute(RenderingDirective::DrawWholeImageAlpha(Arc::clone(&sprite), vec2!(5, -1), Color::BLACK));
+
+        let frame = capture(&mut server);
+
+        assert_eq!(frame[(1, 1)], Color::RED);
+        assert_eq!(frame[(2, 1)], Color::BLUE); // colorkey
+        assert_eq!(frame[(3, 2)], Color::RED);
+        assert_eq!(frame[(4, 2)], Color::BLUE);
+
+        // clipped at the edges
+        assert_eq!(frame[(0, 4)], Color::BLACK);
+        assert_eq!(frame[(1, 4)], Color::RED);
+        assert_eq!(frame[(2, 4)], Color::BLUE);
+        assert_eq!(frame[(5, 0)], Color::RED);
+        assert_eq!(frame[(5, 1)], Color::BLUE);
+    }
+
+
+    #[test]
+    fn image_blit() {
+        let mut src = Image::new(4, 4);
+        for j in 0..4 {
+            for i in 0..4 {
+                src[(i, j)] = Color::rgb(i as u8, j as u8, 1);
+            }
+        }
+        src[(2, 2)] = Color::BLACK;
+
+        let mut img = Image::new(5, 5);
+        img.clear(Color::WHITE);
+        img.blit(&src, (vec2!(1, 1), vec2!(2, 2)), (2, 0), Some(Color::BLACK));
+        assert_eq!(img[(2, 0)], Color::rgb(1, 1, 1));
+        assert_eq!(img[(3, 0)], Color::rgb(2, 1, 1));
+        assert_eq!(img[(2, 1)], Color::rgb(1, 2, 1));
+        assert_eq!(img[(3, 1)], Color::WHITE); // colorkey
+        assert_eq!(img[(4, 0)], Color::WHITE);
+        assert_eq!(img[(2, 2)], Color::WHITE);
+
+        // clipped to both images
+        let mut img = Image::new(5, 5);
+        img.blit(&src, (vec2!(-1, 2), vec2!(4, 4)), (-1, 3), None);
+        assert_eq!(img[(0, 3)], Color::rgb(0, 2, 1));
+        assert_eq!(img[(1, 4)], Color::rgb(1, 3, 1));
+        assert_eq!(img[(2, 4)], Color::rgb(2, 3, 1));
+        assert_eq!(img[(3, 4)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn mode_report() {
+        use std::time::Duration;
+
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[?1000;1$y\x1b[?2026;0$y".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModeReport(1000, ModeStatus::Set));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModeReport(2026, ModeStatus::NotRecognized));
+        assert!(ModeStatus::PermanentlySet.is_set());
+        assert!(!ModeStatus::Reset.is_set());
+
+        // the events received before the reply are kept
+        let mut input = Input::with_reader(io::Cursor::new(b"a\x1b[?1006;2$yb".to_vec()));
+        assert_eq!(input.wait_mode_report(1006, Duration::from_secs(5)), Some(ModeStatus::Reset));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('a')));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('b')));
+        assert_eq!(input.wait_mode_report(1000, Duration::from_millis(50)), None);
+    }
+
+
+    #[test]
+    fn scroll_view() {
+        use std::sync::{Arc, Mutex};
+
+        // a document with one color per row
+        let mut doc = Image::new(4, 20);
+        for y in 0..20 {
+            doc.rect((0, y), (4, 1), Color::rgb(y as u8, 0, 0));
+        }
+
+        let (mut server, _) = headless_server(vec2!(4, 6));
+        let mut frame_at = |offset: Vec2| {
+            let region = doc.view(offset, vec2!(4, 6));
+            server.execute(RenderingDirective::DrawWholeImage(Arc::new(Mutex::new(region)), Vec2::ZERO));
+            capture(&mut server)
+        };
+
+        let frame = frame_at(vec2!(0, 0));
+        assert_eq!(frame[(0, 0)], Color::rgb(0, 0, 0));
+        assert_eq!(frame[(3, 5)], Color::rgb(5, 0, 0));
+
+        let frame = frame_at(vec2!(0, 7));
+        assert_eq!(frame[(0, 0)], Color::rgb(7, 0, 0));
+        assert_eq!(frame[(3, 5)], Color::rgb(12, 0, 0));
+
+        // clamped to the bounds of the document
+        let frame = frame_at(vec2!(3, 100));
+        assert_eq!(frame[(0, 0)], Color::rgb(14, 0, 0));
+        assert_eq!(frame[(3, 5)], Color::rgb(19, 0, 0));
+        assert_eq!(frame_at(vec2!(-5, -5)), frame_at(vec2!(0, 0)));
+
+        // padded when the document is smaller than the screen
+        let small = doc.view((0, 0), (6, 2));
+        assert_eq!(small[(3, 1)], Color::rgb(1, 0, 0));
+        assert_eq!(small[(5, 1)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn image_crop_flip_rotate() {
+        let mut img = Image::new(3, 2);
+        for j in 0..2 {
+            for i in 0..3 {
+                img[(i, j)] = Color::rgb(i as u8, j as u8, 0);
+            }
+        }
+
+        let crop = img.crop((1, 0), (5, 1));
+        assert_eq!(crop.size(), vec2!(2, 1));
+        assert_eq!(crop[(0, 0)], Color::rgb(1, 0, 0));
+        assert_eq!(img.crop((-2, -2), (3, 3)).size(), vec2!(1, 1));
+        assert_eq!(img.crop((5, 5), (3, 3)).size(), Vec2::ZERO);
+
+        assert_eq!(img.flip_horizontal()[(0, 1)], Color::rgb(2, 1, 0));
+        assert_eq!(img.flip_vertical()[(0, 0)], Color::rgb(0, 1, 0));
+        assert_eq!(img.flip_horizontal().flip_horizontal(), img);
+        assert_eq!(img.flip_vertical().flip_vertical(), img);
+
+        // clockwise: the bottom left corner goes to the top left
+        let rotated = img.rotate90(1);
+        assert_eq!(rotated.size(), vec2!(2, 3));
+        assert_eq!(rotated[(0, 0)], Color::rgb(0, 1, 0));
+        assert_eq!(rotated[(1, 0)], Color::rgb(0, 0, 0));
+        assert_eq!(rotated[(0, 2)], Color::rgb(2, 1, 0));
+        assert_eq!(img.rotate90(3), img.rotate90(1).rotate90(2));
+        assert_eq!(img.rotate90(1).rotate90(1).rotate90(1).rotate90(1), img);
+        assert_eq!(img.rotate90(4), img);
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn non_tty_output() {
+        use std::os::unix::io::AsRawFd;
+        use crate::rds::RendererError;
+
+        let file = std::fs::File::open(file!()).unwrap();
+        let fd = file.as_raw_fd();
+        assert_eq!(crate::rds::check_tty(fd), Err(RendererError::NotATerminal(fd)));
+
+        let (reader, _writer) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert!(crate::rds::check_tty(reader.as_raw_fd()).is_err());
+    }
+
+
+    #[test]
+    fn color_temperature() {
+        let c = Color::rgb(100, 100, 100);
+        let warm = c.adjust_temperature(1000);
+        assert!(warm.r > c.r && warm.b < c.b);
+        assert!(warm.r as i32 - warm.b as i32 > 0);
+
+        let cool = c.adjust_temperature(-1000);
+        assert!(cool.r < c.r && cool.b > c.b);
+        assert!(cool.b as i32 - cool.r as i32 > 0);
+
+        assert_eq!(c.adjust_temperature(0), c);
+        assert_eq!(Color::WHITE.adjust_temperature(100000), Color::rgb(255, 255, 0));
+        assert_eq!(Color::BLACK.adjust_temperature(-100000), Color::rgb(0, 0, 255));
+    }
+
+
+    #[test]
+    fn save_and_load() {
+        let mut img = Image::new(5, 3);
+        img.gradient_linear((0, 0), (4, 2), Color::RED, Color::BLUE);
+        img[(2, 1)] = Color::rgb(12, 34, 56);
+
+        let path = std::env::temp_dir().join(format!("termkan-save-{}.png", std::process::id()));
+        img.save(&path).unwrap();
+        let loaded = Image::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), img);
+
+        assert!(Image::new(0, 0).save(&path).is_err());
+        assert!(!path.exists());
+    }
+
+
+    #[test]
+    fn image_load_errors() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+
+        let missing = dir.join(format!("termkan-missing-{}.png", id));
+        assert!(matches!(Image::load(&missing), Err(ImageLoadError::NotFound)));
+
+        let corrupt = dir.join(format!("termkan-corrupt-{}.png", id));
+        std::fs::write(&corrupt, b"\x89PNG\r\n\x1a\nnot really a png").unwrap();
+        let result = Image::load(&corrupt);
+        std::fs::remove_file(&corrupt).unwrap();
+        assert!(matches!(result, Err(ImageLoadError::Corrupt(_))), "{:?}", result);
+
+        let unsupported = dir.join(format!("termkan-unsupported-{}.xyz", id));
+        std::fs::write(&unsupported, b"data").unwrap();
+        let result = Image::load(&unsupported);
+        std::fs::remove_file(&unsupported).unwrap();
+        assert!(matches!(result, Err(ImageLoadError::UnsupportedFormat(_))), "{:?}", result);
+
+        let valid = dir.join(format!("termkan-valid-{}.png", id));
+        let mut img = Image::new(2, 2);
+        img[(1, 0)] = Color::RED;
+        img.save(&valid).unwrap();
+        let result = Image::load(&valid);
+        std::fs::remove_file(&valid).unwrap();
+        assert_eq!(result.unwrap(), img);
+    }
+
+
+    #[test]
+    fn draw_text() {
+        let mut img = Image::new(20, 20);
+        img.draw_text((1, 1), "HI", Color::WHITE);
+        // left bar of the H
+        for y in 1..8 {
+            assert_eq!(img[(1, y)], Color::WHITE);
+        }
+        assert_eq!(img[(2, 4)], Color::WHITE);
+        assert_eq!(img[(2, 1)], Color::BLACK);
+        // the I starts one pixel after the H
+        assert_eq!(img[(6, 1)], Color::BLACK);
+        assert_eq!(img[(8, 1)], Color::WHITE);
+        assert_eq!(img[(9, 4)], Color::WHITE);
+        assert_eq!(img[(8, 4)], Color::BLACK);
+        assert_eq!(img[(1, 8)], Color::BLACK);
+
+        // new lines and placeholder box
+        let mut img = Image::new(20, 20);
+        img.draw_text((0, 0), " \né", Color::WHITE);
+        assert_eq!(img[(0, 8)], Color::WHITE);
+        assert_eq!(img[(4, 14)], Color::WHITE);
+        assert_eq!(img[(2, 11)], Color::BLACK);
+    }
+
+
+    #[test]
+    fn box_frame() {
+        use crate::boxchars::{BoxStyle, ROUNDED};
+
+        let (mut server, output) = headless_server(vec2!(5, 8));
+        server.execute(RenderingDirective::DrawBoxFrame(vec2!(0, 0), vec2!(4, 3), BoxStyle::Rounded, Color::WHITE));
+        server.execute(RenderingDirective::PushFrame);
+
+        // characters of the 5x4 cells, without the escape sequences
+        let frame = output.take();
+        let mut cells = Vec::new();
+        let mut chars = frame.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                cells.push(c);
+            }
+        }
+        let rows: Vec<String> = cells.chunks(5).map(|row| row.iter().collect()).collect();
+        assert_eq!(rows, vec!["╭──╮ ", "│  │ ", "╰──╯ ", "     "]);
+        assert_eq!(ROUNDED.top_left, rows[0].chars().next().unwrap());
+
+        // the characters stay until the screen is cleared
+        server.execute(RenderingDirective::PushFrame);
+        assert!(!output.take().contains('╭'));
+        server.execute(RenderingDirective::ClearScreen(Color::BLACK));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(output.take().chars().filter(|&c| c == ' ').count(), 10);
+    }
+
+
+    #[test]
+    fn sheet_font() {
+        // 'a' is a diagonal and 'b' a full square, on a blue background
+        let mut sheet = Image::new(4, 2);
+        sheet.clear(Color::BLUE);
+        sheet[(0, 0)] = Color::WHITE;
+        sheet[(1, 1)] = Color::WHITE;
+        sheet.rect((2, 0), (2, 2), Color::WHITE);
+        let font = Font::from_sheet(sheet, (2, 2), 'a').with_colorkey(Some(Color::BLUE));
+
+        let mut img = Image::new(6, 4);
+        img.draw_text_font((0, 0), "ab\nbzb", &font, Color::RED);
+        assert_eq!(img[(0, 0)], Color::RED);
+        assert_eq!(img[(1, 0)], Color::BLACK);
+        assert_eq!(img[(1, 1)], Color::RED);
+        for p in [(2, 0), (3, 0), (2, 1), (3, 1), (0, 2), (1, 3)] {
+            assert_eq!(img[p], Color::RED);
+        }
+        // 'z' is not in the font
+        assert_eq!(img[(2, 2)], Color::BLACK);
+        assert_eq!(img[(4, 2)], Color::RED);
+
+        // without colorkey the background of the glyphs is drawn
+        let font = font.with_colorkey(None);
+        let mut img = Image::new(2, 2);
+        img.draw_text_font((0, 0), "a", &font, Color::RED);
+        assert_eq!(img[(1, 0)], Color::RED);
+    }
+
+
+    #[test]
+    fn antialiased_line() {
+        // shallow diagonal, the edges have fractional intensities
+        let mut img = Image::new(10, 10);
+        img.line_aa((0, 0), (8, 3), Color::WHITE);
+        assert_eq!(img[(0, 0)], Color::WHITE);
+        assert_eq!(img[(8, 3)], Color::WHITE);
+        let partial = (0..10).flat_map(|x| (0..10).map(move |y| (x, y)))
+            .map(|p| img[p])
+            .filter(|&c| c != Color::BLACK && c != Color::WHITE)
+            .count();
+        assert!(partial >= 6);
+        // the coverage of the two pixels of a column adds up to the full color
+        let column = img[(1, 0)].r as i32 + img[(1, 1)].r as i32;
+        assert!((254..=256).contains(&column));
+
+        // blended with the background
+        let mut img = Image::new(10, 10);
+        img.clear(Color::rgb(100, 0, 0));
+        img.line_aa((0, 0), (3, 8), Color::rgb(200, 0, 0));
+        let c = img[(1, 2)];
+        assert!(c.r > 100 && c.r < 200);
+
+        // horizontal, vertical and diagonal lines are crisp
+        for (p1, p2) in [((1, 1), (8, 1)), ((2, 8), (2, 0)), ((0, 0), (7, 7))] {
+            let mut aa = Image::new(10, 10);
+            aa.line_aa(p1, p2, Color::WHITE);
+            let mut crisp = Image::new(10, 10);
+            crisp.line(p1, p2, Color::WHITE);
+            assert_eq!(aa, crisp);
+        }
+    }
+
+
+    #[test]
+    fn ansi_recording() {
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, Instant};
+
+        let path = std::env::temp_dir().join(format!("termkan-recording-{}.ans", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (mut server, output) = headless_server(vec2!(4, 2));
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = Arc::clone(&now);
+        server.set_clock(move || *clock.lock().unwrap());
+
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path).unwrap();
+        server.execute(RenderingDirective::StartRecording(file));
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        let first = output.take();
+        *now.lock().unwrap() += Duration::from_millis(40);
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 1), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        let second = output.take();
+        server.execute(RenderingDirective::StopRecording);
+        server.execute(RenderingDirective::PushFrame);
+
+        // the first frame starts with the colors of the terminal
+        let first = format!("{:-}{:+}{}", Color::BLACK, Color::BLACK, first);
+        let recording = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(recording, format!(
+            "frame 0 {}\n{}\nframe 40 {}\n{}\n", first.len(), first, second.len(), second
+        ));
+
+        // the playback writes the frames with the recorded delay
+        let mut replayed = Vec::new();
+        let start = Instant::now();
+        crate::rds::replay(std::fs::File::open(&path).unwrap(), &mut replayed).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(String::from_utf8(replayed).unwrap(), first + &second);
+    }
+
+
+    #[test]
+    fn ansi_recording_started_late() {
+        let path = std::env::temp_dir().join(format!("termkan-late-recording-{}.ans", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (mut server, output) = headless_server(vec2!(4, 4));
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        output.take();
+
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path).unwrap();
+        server.execute(RenderingDirective::StartRecording(file));
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 1), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        server.execute(RenderingDirective::StopRecording);
+
+        let mut replayed = Vec::new();
+        crate::rds::replay(std::fs::File::open(&path).unwrap(), &mut replayed).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let replayed = String::from_utf8(replayed).unwrap();
+
+        // the colors of the terminal are set first, then every cell of the screen is drawn
+        assert!(replayed.starts_with(&format!("{:-}{:+}", Color::RED, Color::BLACK)), "{:?}", replayed);
+        let mut cells = replayed.clone();
+        while let Some(start) = cells.find('\x1b') {
+            let end = start + cells[start..].find(|c: char| c.is_ascii_alphabetic()).unwrap() + 1;
+            cells.replace_range(start..end, "");
+        }
+        assert_eq!(cells.chars().count(), 8, "{:?}", replayed);
+        assert!(replayed.contains(&format!("{:-}", Color::BLUE)) || replayed.contains(&format!("{:+}", Color::BLUE)));
+    }
+
+
+    #[test]
+    fn ansi_recording_write_error() {
+        let path = std::env::temp_dir().join(format!("termkan-readonly-recording-{}.ans", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+
+        // the file is opened read only, so the frames cannot be recorded
+        let (mut server, output) = headless_server(vec2!(4, 2));
+        server.execute(RenderingDirective::StartRecording(std::fs::File::open(&path).unwrap()));
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        std::fs::remove_file(&path).unwrap();
+        assert!(output.take().contains(&format!("{:-}", Color::RED)));
+
+        // the recording is dropped and the next frames are still pushed
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 1), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        assert!(output.take().contains("\x1b[1;2H"));
+        assert_eq!(server.prev_screen().get_pixel(vec2!(1, 1)), Some(Color::BLUE));
+    }
+
+
+    #[test]
+    fn quantize() {
+        let mut img = Image::new(10, 4);
+        img.clear(Color::BLUE);
+        img[(3, 2)] = Color::rgb(250, 10, 10);
+
+        let (quantized, palette) = img.quantize(2);
+        assert_eq!(quantized, img);
+        assert_eq!(palette.len(), 2);
+        assert!(palette.contains(&Color::BLUE) && palette.contains(&Color::rgb(250, 10, 10)));
+
+        // fewer colors than requested
+        let (quantized, palette) = img.quantize(16);
+        assert_eq!(quantized, img);
+        assert_eq!(palette.len(), 2);
+
+        // gradient reduced to 4 colors
+        let mut img = Image::new(64, 1);
+        img.gradient_linear((0, 0), (63, 0), Color::BLACK, Color::WHITE);
+        let (quantized, palette) = img.quantize(4);
+        assert_eq!(palette.len(), 4);
+        for x in 0..64 {
+            assert!(palette.contains(&quantized[(x, 0)]));
+            assert!((quantized[(x, 0)].r as i32 - img[(x, 0)].r as i32).abs() <= 40);
+        }
+
+        let (quantized, palette) = Image::new(0, 0).quantize(4);
+        assert!(palette.is_empty());
+        assert_eq!(quantized.size(), Vec2::ZERO);
+    }
+
+
+    #[test]
+    fn quadrant_rendering() {
+        let (mut server, output) = headless_server(vec2!(6, 2));
+        server.execute(RenderingDirective::SetRenderMode(RenderMode::Quadrant));
+
+        // diagonal, full cell, and a cell with three colors
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 0), Color::RED));
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 1), Color::RED));
+        server.execute(RenderingDirective::DrawRect(vec2!(2, 0), vec2!(2, 2), Color::BLUE));
+        server.execute(RenderingDirective::DrawPoint(vec2!(4, 0), Color::WHITE));
+        server.execute(RenderingDirective::DrawPoint(vec2!(5, 0), Color::rgb(230, 230, 230)));
+        server.execute(RenderingDirective::DrawPoint(vec2!(4, 1), Color::rgb(20, 0, 0)));
+        server.execute(RenderingDirective::PushFrame);
+
+        assert_eq!(output.take(), format!(
+            "\x1b[H{}▚{:-} {}{:-}▀", Color::RED, Color::BLUE, Color::WHITE, Color::BLACK
+        ));
+
+        // the half blocks are used by default
+        let (mut server, output) = headless_server(vec2!(2, 2));
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 0), Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(output.take(), format!("\x1b[H{:-}▄█", Color::RED));
+        assert_eq!(RenderMode::Quadrant.cell_size(), vec2!(2, 2));
+    }
+
+
+    #[test]
+    fn braille_rendering() {
+        let (mut server, output) = headless_server(vec2!(4, 4));
+        server.execute(RenderingDirective::SetRenderMode(RenderMode::Braille));
+        for p in [vec2!(0, 0), vec2!(1, 1), vec2!(0, 3), vec2!(1, 3)] {
+            server.execute(RenderingDirective::DrawPoint(p, Color::GREEN));
+        }
+        // the dominant color is used for the whole cell
+        server.execute(RenderingDirective::DrawPoint(vec2!(2, 0), Color::RED));
+        server.execute(RenderingDirective::DrawPoint(vec2!(3, 0), Color::RED));
+        server.execute(RenderingDirective::DrawPoint(vec2!(2, 2), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+
+        assert_eq!(output.take(), format!("\x1b[H{}\u{28D1}{}\u{280D}", Color::GREEN, Color::RED));
+        assert_eq!(RenderMode::Braille.cell_size(), vec2!(2, 4));
+    }
+
+
+    #[test]
+    fn spinner() {
+        use crate::boxchars::{spinner_char, SPINNER};
+
+        for frame in 0..SPINNER.len() as u32 {
+            assert_ne!(spinner_char(frame), spinner_char(frame + 1));
+        }
+        assert_eq!(spinner_char(SPINNER.len() as u32), spinner_char(0));
+        assert_eq!(spinner_char(u32::MAX), SPINNER[u32::MAX as usize % SPINNER.len()]);
+
+        let (mut server, output) = headless_server(vec2!(2, 2));
+        server.execute(RenderingDirective::DrawChar(vec2!(1, 0), spinner_char(3), Color::WHITE));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(output.take(), format!("\x1b[H█{}⠸", Color::WHITE));
+    }
+
+
+    #[test]
+    fn unknown_terminal_size() {
+        use crate::rds::{size_or_default, DEFAULT_TERMINAL_SIZE};
+
+        assert_eq!(size_or_default(Vec2::ZERO), DEFAULT_TERMINAL_SIZE);
+        assert_eq!(size_or_default(vec2!(120, 0)), DEFAULT_TERMINAL_SIZE);
+        assert_eq!(size_or_default(vec2!(120, 40)), vec2!(120, 40));
+
+        // never empty, even when stdout is not a terminal
+        let size = Renderer::get_size();
+        assert!(size.x > 0 && size.y > 0);
+    }
+
+
+    #[test]
+    fn target_fps() {
+        use std::time::{Duration, Instant};
+        use crate::rds::FrameLimiter;
+
+        let mut limiter = FrameLimiter::new();
+        limiter.target_fps = 10;
+        let start = Instant::now();
+        for _ in 0..10 {
+            limiter.begin_frame();
+            limiter.end_frame();
+        }
+        // frames can be late on a loaded machine, so the upper bound on the time is loose
+        let limited = start.elapsed();
+        assert!(limited >= Duration::from_millis(990) && limited < Duration::from_secs(2), "{:?}", limited);
+        assert!(limiter.current_fps() > 0.0 && limiter.current_fps() <= 10.0 + 1e-3, "{}", limiter.current_fps());
+
+        // unlimited
+        let mut limiter = FrameLimiter::new();
+        let start = Instant::now();
+        for _ in 0..10 {
+            limiter.begin_frame();
+            limiter.end_frame();
+        }
+        assert!(start.elapsed() < limited);
+    }
+
+
+    #[test]
+    fn static_background() {
+        let mut background = Image::new(4, 4);
+        background.gradient_linear((0, 0), (3, 0), Color::RED, Color::BLUE);
+
+        let (mut server, _) = headless_server(vec2!(4, 4));
+        server.execute(RenderingDirective::ClearScreen(Color::GREEN));
+        server.execute(RenderingDirective::SetBackground(Some(background.clone())));
+        server.execute(RenderingDirective::BeginFrame);
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 1), Color::WHITE));
+        let frame = capture(&mut server);
+        assert_eq!(frame[(1, 1)], Color::WHITE);
+        assert_eq!(frame[(0, 0)], background[(0, 0)]);
+        assert_eq!(frame[(3, 3)], background[(3, 3)]);
+        server.execute(RenderingDirective::PushFrame);
+
+        // each frame starts from the background
+        server.execute(RenderingDirective::BeginFrame);
+        assert_eq!(capture(&mut server), background);
+        server.execute(RenderingDirective::PushFrame);
+
+        server.execute(RenderingDirective::SetBackground(None));
+        server.execute(RenderingDirective::BeginFrame);
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 1), Color::WHITE));
+        server.execute(RenderingDirective::PushFrame);
+        server.execute(RenderingDirective::BeginFrame);
+        assert_eq!(capture(&mut server)[(1, 1)], Color::WHITE);
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn resize_debounce() {
+        use std::time::{Duration, Instant};
+        use crate::input::ResizeDebouncer;
+
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut debouncer = ResizeDebouncer::new(Duration::from_millis(50));
+        assert!(!debouncer.poll(at(0)));
+
+        // a burst of signals gives one event, 50ms after the last one
+        for ms in [0, 10, 20, 30] {
+            debouncer.signal(at(ms));
+            assert!(!debouncer.poll(at(ms + 5)));
+        }
+        assert!(!debouncer.poll(at(79)));
+        assert!(debouncer.poll(at(80)));
+        assert!(!debouncer.poll(at(200)));
+
+        debouncer.signal(at(300));
+        assert_eq!(debouncer.timeout(at(320)), Some(Duration::from_millis(30)));
+        assert_eq!(debouncer.timeout(at(400)), Some(Duration::ZERO));
+        assert!(debouncer.poll(at(400)));
+        assert_eq!(debouncer.timeout(at(400)), None);
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn resize_sender_does_not_keep_input_open() {
+        use std::time::Instant;
+
+        let (mut inp, sender) = Input::start(io::empty());
+        let resize = sender.downgrade();
+        drop(sender);
+        // the input ends with the reader even if the resize watcher is still running
+        assert_eq!(inp.events().count(), 0);
+        assert_eq!(inp.read_line().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert!(resize.send((InputEvent::Resize(vec2!(1, 1)), Instant::now())).is_err());
+    }
+
+
+    #[test]
+    fn mouse_motion() {
+        // SGR, X10 and rxvt encodings of a move to the cell (5, 3), reported 0-based
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[<35;5;3M\x1b[M\x43\x25\x23\x1b[67;5;3M".to_vec()));
+        for _ in 0..3 {
+            assert_eq!(input.get_event_blocking(), InputEvent::Mouse(MouseEvent::Move(vec2!(4, 2), Modifiers::default())));
+        }
+
+        // motion with a button pressed is still a Hold
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[<0;5;3M\x1b[<32;6;3M".to_vec()));
+        input.get_event_blocking();
+        assert!(matches!(input.get_event_blocking(), InputEvent::Mouse(MouseEvent::Hold(_, _, _))));
+    }
+
+
+    #[test]
+    fn vec2_components() {
+        let v = vec2!(3, -4);
+        assert_eq!(v.xy(), (3, -4));
+        let (x, y) = v.xy();
+        assert_eq!(vec2!(x, y), v);
+
+        assert_eq!(v.with_x(7), vec2!(7, -4));
+        assert_eq!(v.with_y(0), vec2!(3, 0));
+        assert_eq!(v.with_x(1).with_y(2), vec2!(1, 2));
+        assert_eq!(v, vec2!(3, -4));
+    }
+
+
+    #[test]
+    fn malformed_escape_sequences() {
+        // truncated by the end of the input
+        for seq in [&b"\x1b[<"[..], b"\x1b[<0;5", b"\x1b[<0;5;", b"\x1b[M\x20", b"\x1b[12", b"\x1b"] {
+            let mut input = Input::with_reader(io::Cursor::new(seq.to_vec()));
+            assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(seq.to_vec()));
+        }
+
+        // garbage inside a sequence does not prevent parsing what follows
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[<0;x;3Ma\x1b[1;2;M\x1b[99999~b".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\x1b[<0;x;3M".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('a')));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\x1b[1;2;M".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\x1b[99999~".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('b')));
+
+        // X10 button bytes out of range
+        for seq in [&b"\x1b[M\x85!!"[..], b"\x1b[M\x1f!!", b"\x1b[M\xe0!!"] {
+            let mut input = Input::with_reader(io::Cursor::new(seq.to_vec()));
+            assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(seq.to_vec()));
+        }
+    }
+
+
+    #[test]
+    fn auto_clear() {
+        let buffer = SharedBuffer::default();
+        let mut server = RenderingServer::new(buffer, &RendererConfig::new().auto_clear(Color::NAVY_BLUE));
+        server.execute(RenderingDirective::SetSynchronized(false));
+        server.execute(RenderingDirective::UpdateScreenSize(vec2!(4, 4)));
+
+        server.execute(RenderingDirective::BeginFrame);
+        server.execute(RenderingDirective::DrawRect(vec2!(1, 1), vec2!(2, 2), Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+
+        server.execute(RenderingDirective::BeginFrame);
+        let frame = capture(&mut server);
+        for x in 0..4 {
+            for y in 0..4 {
+                assert_eq!(frame.get_pixel(vec2!(x, y)), Some(Color::NAVY_BLUE));
+            }
+        }
+    }
+
+
+    #[test]
+    fn unsupported_sequences() {
+        // an unknown CSI sequence and an invalid UTF-8 character
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[5;3zq\x1bO9\xff".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\x1b[5;3z".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('q')));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\x1bO9".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\xff".to_vec()));
+    }
+
+
+    #[test]
+    fn key_modifiers() {
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::default() };
+
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[3;2~\x1b[1;5C\x1b[3~\x1b[1;7H\x1b[15;3~".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModifiedKey(KeyEvent::Delete, shift));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModifiedKey(KeyEvent::Right, ctrl));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Delete));
+        assert_eq!(input.get_event_blocking(),
+                   InputEvent::ModifiedKey(KeyEvent::Home, Modifiers { ctrl: true, alt: true, shift: false }));
+        assert_eq!(input.get_event_blocking(),
+                   InputEvent::ModifiedKey(KeyEvent::F(5), Modifiers { alt: true, ..Modifiers::default() }));
+    }
+
+
+    #[test]
+    fn mouse_modifiers() {
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::default() };
+        let none = Modifiers::default();
+
+        // Ctrl+left press then plain left press, in SGR, X10 and rxvt encodings
+        let mut input = Input::with_reader(io::Cursor::new(
+            b"\x1b[<16;5;3M\x1b[<0;5;3M\x1b[M\x30\x25\x23\x1b[M\x20\x25\x23\x1b[48;5;3M\x1b[32;5;3M".to_vec()
+        ));
+        for _ in 0..3 {
+            assert_eq!(input.get_event_blocking(),
+                       InputEvent::Mouse(MouseEvent::ButtonPressed(MouseButton::Left, vec2!(4, 2), ctrl)));
+            assert_eq!(input.get_event_blocking(),
+                       InputEvent::Mouse(MouseEvent::ButtonPressed(MouseButton::Left, vec2!(4, 2), none)));
+        }
+
+        // Shift+drag keeps the button and reports the modifier
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[<2;5;3M\x1b[<36;6;3M".to_vec()));
+        input.get_event_blocking();
+        assert_eq!(input.get_event_blocking(),
+                   InputEvent::Mouse(MouseEvent::Hold(MouseButton::Right, vec2!(5, 2), shift)));
+    }
+
+
+    #[test]
+    fn mouse_cell_to_pixel() {
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[<0;5;3M".to_vec()));
+        let cell = match input.get_event_blocking() {
+            InputEvent::Mouse(MouseEvent::ButtonPressed(_, cell, _)) => cell,
+            event => panic!("unexpected event {:?}", event)
+        };
+        assert_eq!(RenderMode::HalfBlock.cell_to_pixel(cell), vec2!(4, 4));
+        assert_eq!(RenderMode::Quadrant.cell_to_pixel(cell), vec2!(8, 4));
+        assert_eq!(RenderMode::Braille.cell_to_pixel(cell), vec2!(8, 8));
+
+        let mut rdr = Renderer::new_headless((20, 20));
+        rdr.set_render_mode(RenderMode::Braille);
+        assert_eq!(rdr.cell_to_pixel(cell), vec2!(8, 8));
+    }
+
+
+    #[test]
+    fn mouse_scroll() {
+        let none = Modifiers::default();
+
+        // X10 wheel up, then a left press and release: the release is not attributed to the wheel
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[M\x60\x25\x23\x1b[M\x20\x25\x23\x1b[M\x23\x25\x23".to_vec()));
+        assert_eq!(input.get_event_blocking(),
+                   InputEvent::Mouse(MouseEvent::Scroll(ScrollDirection::Up, vec2!(4, 2), none)));
+        input.get_event_blocking();
+        assert_eq!(input.get_event_blocking(),
+                   InputEvent::Mouse(MouseEvent::ButtonReleased(MouseButton::Left, vec2!(4, 2), none)));
+
+        // wheel down in X10, SGR and rxvt encodings
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[M\x61\x25\x23\x1b[<65;5;3M\x1b[97;5;3M".to_vec()));
+        for _ in 0..3 {
+            assert_eq!(input.get_event_blocking(),
+                       InputEvent::Mouse(MouseEvent::Scroll(ScrollDirection::Down, vec2!(4, 2), none)));
+        }
+    }
+
+
+    #[test]
+    fn bracketed_paste() {
+        let mut input = Input::with_reader(io::Cursor::new(b"a\x1b[200~fn main() {\r    \x1b[A\xc3\xa9\r}\x1b[201~b".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('a')));
+        assert_eq!(input.get_event_blocking(), InputEvent::Paste("fn main() {\n    \x1b[A\u{e9}\n}".to_string()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('b')));
+
+        // unterminated paste
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[200~abc".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Unsupported(b"\x1b[200~abc".to_vec()));
+    }
+
+
+    #[test]
+    fn focus_events() {
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[O\x1b[Ia".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::FocusLost);
+        assert_eq!(input.get_event_blocking(), InputEvent::FocusGained);
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('a')));
+    }
+
+
+    #[test]
+    fn event_iterators() {
+        let mut input = Input::with_reader(io::Cursor::new(b"ab\x1b[I".to_vec()));
+        let events: Vec<_> = input.events().collect();
+        assert_eq!(events, vec![
+            InputEvent::Key(KeyEvent::Char('a')),
+            InputEvent::Key(KeyEvent::Char('b')),
+            InputEvent::FocusGained
+        ]);
+        assert_eq!(input.events().next(), None);
+
+        let mut input = Input::with_reader(SlowReader::new(b"cd", std::time::Duration::from_millis(5)));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(input.try_events().count(), 2);
+        assert_eq!(input.try_events().next(), None);
+    }
+
+
+    #[test]
+    fn independent_renderers() {
+        let (a_out, b_out) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut a = Renderer::with_output(a_out.clone(), RendererConfig::new());
+        let mut b = Renderer::with_output(b_out.clone(), RendererConfig::new());
+        for rdr in [&mut a, &mut b] {
+            rdr.set_synchronized_output(false);
+            rdr.set_color_mode(ColorMode::TrueColor);
+        }
+
+        a.begin_draw();
+        b.begin_draw();
+        a.draw_rect(vec2!(0, 0), vec2!(2, 2), Color::RED);
+        b.draw_rect(vec2!(0, 0), vec2!(2, 2), Color::GREEN);
+        assert_eq!(a.capture_frame().get_pixel(vec2!(1, 1)), Some(Color::RED));
+        assert_eq!(b.capture_frame().get_pixel(vec2!(1, 1)), Some(Color::GREEN));
+        a.present();
+        b.present();
+
+        let (a_frame, b_frame) = (a_out.take(), b_out.take());
+        assert!(a_frame.contains(&format!("{:-}", Color::RED)));
+        assert!(!a_frame.contains(&format!("{:-}", Color::GREEN)));
+        assert!(b_frame.contains(&format!("{:-}", Color::GREEN)));
+    }
+
+
+    #[test]
+    fn reentrant_singleton() {
+        let guard = Input::get();
+        let result = std::panic::catch_unwind(|| Input::get().try_events().count());
+        let message = result.expect_err("Input::get did not panic");
+        assert!(message.downcast_ref::<String>().is_some_and(|m| m.contains("already borrowed on this thread")));
+
+        // the other threads wait for the guard to be dropped
+        let other = std::thread::spawn(|| Input::get().try_events().count());
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!other.is_finished());
+        drop(guard);
+        other.join().unwrap();
+
+        // and the current thread can borrow it again
+        drop(Input::get());
+    }
+
+
+    #[test]
+    fn instance_screen_size() {
+        let cells = Renderer::get_terminal_size();
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.set_render_mode(RenderMode::Braille);
+        assert_eq!(rdr.screen_size(), vec2!(2 * cells.x, 4 * cells.y));
+        assert_eq!(Canvas::size(&rdr), rdr.screen_size());
+
+        // the whole terminal is drawn in the render mode of this renderer
+        rdr.begin_draw();
+        assert_eq!(rdr.capture_frame().size(), vec2!(2 * cells.x, 4 * cells.y));
+        rdr.end_draw();
+
+        // the singleton is not affected
+        assert_eq!(Renderer::get_size(), vec2!(cells.x, 2 * cells.y));
+    }
+
+
+    #[test]
+    fn render_to_string() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.set_synchronized_output(false);
+        rdr.set_color_mode(ColorMode::TrueColor);
+        rdr.begin_draw();
+        rdr.end_draw();
+
+        rdr.begin_draw();
+        rdr.draw_point(vec2!(2, 1), Color::RED);
+        // only the changed cell is written: its bottom half is red
+        let frame = rdr.render_to_string();
+        assert_eq!(frame, format!("\x1b[H\x1b[1;3H{}\u{2584}", Color::RED));
+        // the frame is not pushed
+        assert_eq!(rdr.render_to_string(), frame);
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn render_before_first_frame() {
+        // the screen has no size until the first begin_draw
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.set_synchronized_output(false);
+        rdr.mark_dirty((0, 0), (2, 2));
+        assert_eq!(rdr.render_to_string(), "\x1b[H");
+
+        // the rendering thread is still running
+        rdr.begin_draw();
+        rdr.draw_point(vec2!(0, 0), Color::RED);
+        assert_eq!(rdr.pixel_at((0, 0)), Some(Color::RED));
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn sparse_frame_cursor() {
+        let (mut server, buffer) = headless_server(vec2!(4, 6));
+        server.execute(RenderingDirective::PushFrame);
+        buffer.take();
+
+        // changes at the end of the first row, at the start of the next one and after a gap in the last one
+        server.execute(RenderingDirective::DrawPoint(vec2!(3, 1), Color::RED));
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 3), Color::RED));
+        server.execute(RenderingDirective::DrawPoint(vec2!(1, 5), Color::RED));
+        server.execute(RenderingDirective::DrawPoint(vec2!(3, 5), Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+
+        let cell = format!("{}\u{2584}", Color::RED);
+        assert_eq!(buffer.take(), format!("\x1b[H\x1b[1;4H{}\x1b[2;1H▄\x1b[3;2H▄\x1b[3;4H▄", cell));
+    }
+
+
+    #[test]
+    fn cell_colors() {
+        let [a, b, c, d] = [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE];
+        let (mut server, buffer) = headless_server(vec2!(4, 2));
+        for (x, (top, bottom)) in [(a, b), (c, d), (a, d), (b, b)].into_iter().enumerate() {
+            server.execute(RenderingDirective::DrawPoint(vec2!(x as i32, 0), top));
+            server.execute(RenderingDirective::DrawPoint(vec2!(x as i32, 1), bottom));
+        }
+        server.execute(RenderingDirective::PushFrame);
+        // each cell sets the colors it needs that are not already set
+        assert_eq!(buffer.take(), format!("\x1b[H{:+}{:-}▀{:+}{:-}▀{:+}▀{:-} ", a, b, c, d, a, b));
+
+        // after a change of color mode, the colors are set again in the new mode
+        server.execute(RenderingDirective::SetColorMode(ColorMode::Ansi256));
+        let out = buffer.take();
+        assert_eq!(out, format!("\x1b[48;5;{}m\x1b[38;5;{}m", b.to_ansi256(), a.to_ansi256()));
+
+        // and every cell is drawn again with them, as well as after a change of dithering
+        server.execute(RenderingDirective::PushFrame);
+        let out = buffer.take();
+        assert_eq!(out.matches(['▀', '▄', '█', ' ']).count(), 4);
+        assert!(!out.contains("8;2;"), "{:?}", out);
+        server.execute(RenderingDirective::SetDithering(true));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take().matches(['▀', '▄', '█', ' ']).count(), 4);
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take(), "\x1b[H");
+    }
+
+
+    #[test]
+    fn clip_stack() {
+        let (mut server, _) = headless_server(vec2!(8, 6));
+        server.execute(RenderingDirective::ClearScreen(Color::BLUE));
+        server.execute(RenderingDirective::PushClip(vec2!(2, 1), vec2!(3, 3)));
+        server.execute(RenderingDirective::DrawRect(vec2!(0, 0), vec2!(8, 6), Color::RED));
+        // nested clips intersect
+        server.execute(RenderingDirective::PushClip(vec2!(3, 0), vec2!(10, 10)));
+        server.execute(RenderingDirective::ClearScreen(Color::GREEN));
+        server.execute(RenderingDirective::PopClip);
+        server.execute(RenderingDirective::PopClip);
+        server.execute(RenderingDirective::DrawPoint(vec2!(7, 5), Color::WHITE));
+
+        let mut expected = Image::new(8, 6);
+        expected.clear(Color::BLUE);
+        expected.rect(vec2!(2, 1), vec2!(3, 3), Color::RED);
+        expected.rect(vec2!(3, 1), vec2!(2, 3), Color::GREEN);
+        expected.point(vec2!(7, 5), Color::WHITE);
+        assert!(capture(&mut server) == expected);
+    }
+
+
+    #[test]
+    fn clip_batch() {
+        let (mut server, _) = headless_server(vec2!(8, 6));
+        server.execute(RenderingDirective::PushClip(vec2!(1, 1), vec2!(4, 3)));
+        server.execute(RenderingDirective::Batch(vec![
+            RenderingDirective::GradientLinear(vec2!(0, 0), vec2!(7, 0), Color::RED, Color::BLUE),
+            RenderingDirective::DrawEllipse(vec2!(4, 3), vec2!(6, 6), Color::GREEN),
+        ]));
+        server.execute(RenderingDirective::PopClip);
+        let frame = capture(&mut server);
+
+        let mut expected = Image::new(8, 6);
+        expected.set_clip(Some(Rect::new(vec2!(1, 1), vec2!(4, 3))));
+        expected.gradient_linear(vec2!(0, 0), vec2!(7, 0), Color::RED, Color::BLUE);
+        expected.ellipse(vec2!(4, 3), vec2!(6, 6), Color::GREEN);
+        assert!(frame == expected);
+        assert_eq!(frame.get_pixel(vec2!(0, 0)), Some(Color::BLACK));
+        assert_eq!(frame.get_pixel(vec2!(5, 3)), Some(Color::BLACK));
+        assert_eq!(frame.clip(), None);
+    }
+
+
+    #[test]
+    fn present_region_ignores_camera() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.set_camera(vec2!(90, 90));
+        rdr.begin_draw();
+        let size = rdr.screen_size();
+        let mut world = Image::new(size.x as usize + 10, size.y as usize + 10);
+        world.point(vec2!(5, 5), Color::RED);
+        rdr.present_region(&world, vec2!(5, 5));
+        let frame = rdr.capture_frame();
+        assert_eq!(frame.get_pixel(vec2!(0, 0)), Some(Color::RED));
+        assert_eq!(frame.get_pixel(vec2!(1, 1)), Some(Color::BLACK));
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn camera() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.set_camera(vec2!(90, 90));
+        assert_eq!(rdr.camera(), vec2!(90, 90));
+        assert_eq!(rdr.world_to_screen(vec2!(100, 100)), vec2!(10, 10));
+        assert_eq!(rdr.screen_to_world(vec2!(10, 10)), vec2!(100, 100));
+
+        rdr.begin_draw();
+        rdr.draw_point(vec2!(100, 100), Color::RED);
+        rdr.draw_rect(vec2!(85, 92), vec2!(10, 2), Color::GREEN);
+        let frame = rdr.capture_frame();
+        assert_eq!(frame.get_pixel(vec2!(10, 10)), Some(Color::RED));
+        // clipped at the edge of the screen
+        assert_eq!(frame.get_pixel(vec2!(0, 2)), Some(Color::GREEN));
+        assert_eq!(frame.get_pixel(vec2!(4, 3)), Some(Color::GREEN));
+        assert_eq!(frame.get_pixel(vec2!(5, 3)), Some(Color::BLACK));
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn layers() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        let overlay = rdr.create_layer(Color::BLACK);
+
+        rdr.begin_draw();
+        rdr.clear_screen(Color::BLUE);
+        rdr.draw_on_layer(overlay, |rdr| rdr.draw_rect(vec2!(0, 0), vec2!(2, 2), Color::RED));
+        let frame = rdr.capture_frame();
+        assert_eq!(frame.get_pixel(vec2!(1, 1)), Some(Color::RED));
+        // the transparent pixels of the layer show the screen under it
+        assert_eq!(frame.get_pixel(vec2!(5, 5)), Some(Color::BLUE));
+        rdr.end_draw();
+
+        // the layer is kept while the screen is redrawn
+        rdr.begin_draw();
+        rdr.clear_screen(Color::GREEN);
+        let frame = rdr.capture_frame();
+        assert_eq!(frame.get_pixel(vec2!(1, 1)), Some(Color::RED));
+        assert_eq!(frame.get_pixel(vec2!(5, 5)), Some(Color::GREEN));
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn dirty_regions() {
+        let (mut server, buffer) = headless_server(vec2!(40, 20));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 400);
+
+        // without marked regions, the cells of the rows that changed are compared
+        server.execute(RenderingDirective::DrawPoint(vec2!(3, 3), Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 40);
+        buffer.take();
+
+        server.execute(RenderingDirective::DrawRect(vec2!(10, 4), vec2!(2, 2), Color::RED));
+        server.execute(RenderingDirective::MarkDirty(vec2!(10, 4), vec2!(2, 2)));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 2);
+        assert_eq!(buffer.take().matches('█').count(), 2);
+
+        // the regions only apply to one frame
+        server.execute(RenderingDirective::DrawPoint(vec2!(3, 3), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 40);
+    }
+
+
+    #[test]
+    fn set_title() {
+        let out = SharedBuffer::default();
+        let rdr = Renderer::with_output(out.clone(), RendererConfig::new());
+        rdr.capture_frame();
+        out.take();
+
+        rdr.set_title("build: ok\x07\x1b");
+        rdr.capture_frame(); // waits for the title to be written
+        assert_eq!(out.take(), "\x1b]0;build: ok\x07");
+    }
+
+
+    #[test]
+    fn bells() {
+        let out = SharedBuffer::default();
+        let mut rdr = Renderer::with_output(out.clone(), RendererConfig::new());
+        rdr.capture_frame();
+        out.take();
+
+        // outside of a frame
+        rdr.ring_bell();
+        rdr.capture_frame();
+        assert_eq!(out.take(), "\x07");
+
+        // during a frame, the bell does not wait for the frame to be pushed
+        rdr.begin_draw();
+        rdr.ring_bell();
+        rdr.capture_frame();
+        assert_eq!(out.take(), "\x07");
+        rdr.end_draw();
+
+        rdr.visual_bell();
+        rdr.capture_frame();
+        assert!(out.take().ends_with("\x1b[?5h"));
+    }
+
+
+    #[test]
+    fn visual_bell_deadline() {
+        use std::time::{Duration, Instant};
+        use crate::rds::VISUAL_BELL_DURATION;
+
+        let (mut server, output) = headless_server(vec2!(4, 2));
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = Arc::clone(&now);
+        server.set_clock(move || *clock.lock().unwrap());
+
+        server.execute(RenderingDirective::VisualBell);
+        assert_eq!(output.take(), "\x1b[?5h");
+
+        // the reverse video stays until a frame after the end of the flash
+        server.execute(RenderingDirective::BeginFrame);
+        server.execute(RenderingDirective::PushFrame);
+        assert!(!output.take().contains("\x1b[?5l"));
+        *now.lock().unwrap() += VISUAL_BELL_DURATION + Duration::from_millis(1);
+        server.execute(RenderingDirective::BeginFrame);
+        assert_eq!(output.take(), "\x1b[?5l");
+        server.execute(RenderingDirective::PushFrame);
+        assert!(!output.take().contains("\x1b[?5l"));
+
+        // the shutdown does not wait
+        server.execute(RenderingDirective::VisualBell);
+        output.take();
+        let (response, _done) = std::sync::mpsc::channel();
+        server.execute(RenderingDirective::Shutdown(response));
+        assert!(output.take().starts_with("\x1b[?5l"));
+    }
+
+
+    #[test]
+    fn shutdown() {
+        let buffer = SharedBuffer::default();
+        let mut rdr = Renderer::with_output(buffer.clone(), RendererConfig::new());
+        rdr.capture_frame();
+        buffer.take();
+
+        rdr.shutdown();
+        assert_eq!(buffer.take(), "\x1b[?25h\x1b[?1049l\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l\x1b[?1004l\x1b[?2004l");
+
+        // the terminal is only reset once, dropping the renderer does not exit
+        rdr.shutdown();
+        drop(rdr);
+        assert_eq!(buffer.take(), "");
+    }
+
+
+    #[test]
+    fn restore_on_panic() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // hook recording that it was called, chained with the restoring hook whether it was installed before
+        // or by this test; the hook of the test harness is put back at the end
+        let previous = Arc::new(std::panic::take_hook());
+        let chained = Arc::new(AtomicBool::new(false));
+        let called = Arc::clone(&chained);
+        let default = Arc::clone(&previous);
+        std::panic::set_hook(Box::new(move |info| {
+            called.store(true, Ordering::SeqCst);
+            default(info);
+        }));
+
+        let buffer = SharedBuffer::default();
+        let shut_down = Arc::new(AtomicBool::new(false));
+        let id = crate::rds::restore_on_panic(buffer.clone(), None, Arc::clone(&shut_down));
+
+        // a terminal that was already reset is forgotten
+        let forgotten = SharedBuffer::default();
+        let forgotten_id = crate::rds::restore_on_panic(forgotten.clone(), None, Arc::new(AtomicBool::new(false)));
+        crate::rds::forget_on_panic(forgotten_id);
+
+        assert!(std::panic::catch_unwind(|| panic!("panic in a frame")).is_err());
+        assert!(buffer.take().contains("\x1b[?25h\x1b[?1049l\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l\x1b[?1004l\x1b[?2004l"));
+        assert!(shut_down.load(Ordering::SeqCst));
+        assert!(chained.load(Ordering::SeqCst));
+        assert_eq!(forgotten.take(), "");
+
+        // the terminal is only reset once
+        assert!(std::panic::catch_unwind(|| panic!("second panic")).is_err());
+        assert_eq!(buffer.take(), "");
+        crate::rds::forget_on_panic(id);
+
+        let _ = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| previous(info)));
+    }
+
+
+    #[test]
+    fn kitty_keyboard() {
+        let none = Modifiers::default();
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+
+        let mut input = Input::with_reader(io::Cursor::new(
+            b"\x1b[97u\x1b[97;1:3u\x1b[97;5u\x1b[97;6:3u\x1b[27u\x1b[1;1:3A\x1b[3;2:3~".to_vec()
+        ));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('a')));
+        assert_eq!(input.get_event_blocking(), InputEvent::KeyReleased(KeyEvent::Char('a'), none));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Ctrl('a')));
+        assert_eq!(input.get_event_blocking(),
+                   InputEvent::KeyReleased(KeyEvent::Char('a'), Modifiers { ctrl: true, shift: true, alt: false }));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Esc));
+        assert_eq!(input.get_event_blocking(), InputEvent::KeyReleased(KeyEvent::Up, none));
+        assert_eq!(input.get_event_blocking(), InputEvent::KeyReleased(KeyEvent::Delete, shift));
+    }
+
+
+    #[test]
+    fn cursor_position() {
+        use std::time::Duration;
+
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[5;10R".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::CursorPosition(vec2!(9, 4)));
+
+        // on the first row, the reply is only recognized during a query, the events received before are kept
+        let mut input = Input::with_reader(SlowReader::new(b"a\x1b[1;3R", Duration::from_millis(20)));
+        assert_eq!(input.wait_cursor_position(Duration::from_secs(5), || ()), Some(vec2!(2, 0)));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('a')));
+        assert_eq!(input.wait_cursor_position(Duration::from_millis(50), || ()), None);
+
+        // the renderer sends the query, the reply is read by the given input
+        let out = SharedBuffer::default();
+        let rdr = Renderer::with_output(out.clone(), RendererConfig::new());
+        let mut input = Input::with_reader(SlowReader::new(b"\x1b[1;1R", Duration::from_millis(5)));
+        assert_eq!(rdr.query_cursor_position(&mut input), Some(vec2!(0, 0)));
+        assert!(out.take().ends_with("\x1b[6n"));
+    }
+
+
+    #[test]
+    fn resize_between_frames() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+
+        rdr.begin_draw_with_size(vec2!(8, 6));
+        rdr.clear_screen(Color::RED);
+        rdr.end_draw();
+        assert_eq!(rdr.capture_frame().size(), vec2!(8, 6));
+
+        // the terminal grows: the first draw of the next frame covers the new size
+        rdr.begin_draw_with_size(vec2!(12, 10));
+        rdr.clear_screen(Color::BLUE);
+        let frame = rdr.capture_frame();
+        assert_eq!(frame.size(), vec2!(12, 10));
+        assert_eq!(frame.get_pixel(vec2!(11, 9)), Some(Color::BLUE));
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn arcs() {
+        let center = vec2!(10, 10);
+        let size = vec2!(16, 12);
+
+        // quarter arc in the top right quadrant
+        let mut img = Image::new(21, 21);
+        img.arc(center, size, 0.0, 90.0, Color::RED);
+        assert_eq!(img[(18, 10)], Color::RED);
+        assert_eq!(img[(10, 4)], Color::RED);
+        for y in 0..21 {
+            for x in 0..21 {
+                if img[(x, y)] == Color::RED {
+                    assert!(x >= 10 && y <= 10, "pixel ({}, {}) outside of the quarter", x, y);
+                }
+            }
+        }
+
+        // a whole turn draws the ellipse
+        let mut full = Image::new(21, 21);
+        full.ellipse_boundary(center, size, Color::RED);
+        let mut img = Image::new(21, 21);
+        img.arc(center, size, 45.0, 405.0, Color::RED);
+        assert_eq!(img, full);
+
+        // from 90 to 0 goes the long way: the quarter and the three other quarters make the ellipse
+        img = Image::new(21, 21);
+        img.arc(center, size, 90.0, 0.0, Color::RED);
+        assert_eq!(img[(2, 10)], Color::RED);
+        assert_eq!(img[(10, 16)], Color::RED);
+        assert_eq!(img[(16, 6)], Color::BLACK);
+        img.arc(center, size, 0.0, 90.0, Color::RED);
+        assert_eq!(img, full);
+    }
+
+
+    #[test]
+    fn circle_size() {
+        // half blocks are square with the default aspect, quadrants are twice as high as wide
+        assert_eq!(RenderMode::HalfBlock.circle_size(5, crate::rds::DEFAULT_CELL_ASPECT), vec2!(10, 10));
+        assert_eq!(RenderMode::Quadrant.circle_size(5, crate::rds::DEFAULT_CELL_ASPECT), vec2!(10, 5));
+        assert_eq!(RenderMode::Braille.circle_size(5, crate::rds::DEFAULT_CELL_ASPECT), vec2!(10, 10));
+        assert_eq!(RenderMode::HalfBlock.circle_size(6, 2.5), vec2!(12, 10));
+
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.begin_draw_with_size(vec2!(30, 30));
+        rdr.set_cell_aspect(3.0);
+        rdr.draw_circle(vec2!(15, 15), 6, Color::RED);
+        let mut expected = Image::new(30, 30);
+        expected.ellipse_boundary(vec2!(15, 15), vec2!(12, 8), Color::RED);
+        assert_eq!(rdr.capture_frame(), expected);
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn draw_batch() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.begin_draw_with_size(vec2!(40, 25));
+
+        let points: Vec<(Vec2, Color)> = (0..1000)
+            .map(|i| (vec2!(i % 40, i / 40), Color::rgb(i as u8, (i / 256) as u8, 7)))
+            .collect();
+        rdr.draw_points(&points);
+        let frame = rdr.capture_frame();
+        for &(p, c) in &points {
+            assert_eq!(frame.get_pixel(p), Some(c));
+        }
+
+        let mut batch = crate::rds::DrawBatch::new();
+        batch.draw_rect(vec2!(0, 0), vec2!(4, 4), Color::RED);
+        batch.draw_point(vec2!(1, 1), Color::BLUE);
+        assert_eq!(batch.len(), 2);
+        rdr.draw_batch(batch);
+        let frame = rdr.capture_frame();
+        assert_eq!(frame.get_pixel(vec2!(0, 0)), Some(Color::RED));
+        assert_eq!(frame.get_pixel(vec2!(1, 1)), Some(Color::BLUE));
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn with_frame() {
+        let mut direct = Renderer::with_output(io::sink(), RendererConfig::new());
+        direct.with_frame(|screen| {
+            screen.clear(Color::BLUE);
+            screen.rect((2, 2), (5, 3), Color::RED);
+            screen.line((0, 10), (20, 15), Color::WHITE);
+        });
+
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.begin_draw();
+        rdr.clear_screen(Color::BLUE);
+        rdr.draw_rect(vec2!(2, 2), vec2!(5, 3), Color::RED);
+        rdr.draw_line(vec2!(0, 10), vec2!(20, 15), Color::WHITE);
+        rdr.end_draw();
+
+        assert_eq!(direct.capture_frame(), rdr.capture_frame());
+        assert_eq!(direct.render_to_string(), rdr.render_to_string());
+    }
+
+
+    #[test]
+    fn vec2_conversions() {
+        assert_eq!(Vec2::from([3, -4]), vec2!(3, -4));
+        assert_eq!(<[i32; 2]>::from(vec2!(3, -4)), [3, -4]);
+        let a: [i32; 2] = vec2!(7, 8).into();
+        assert_eq!(a, [7, 8]);
+
+        assert_eq!(Vec2::from((1.4f32, -2.6f32)), vec2!(1, -3));
+        assert_eq!(Vec2::from((2.5f32, -0.5f32)), vec2!(3, -1));
+        let f: (f32, f32) = vec2!(-5, 6).into();
+        assert_eq!(f, (-5.0, 6.0));
+    }
+
+
+    #[test]
+    fn integer_tuples() {
+        let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        rdr.begin_draw_with_size(vec2!(10, 10));
+        rdr.draw_point((1i32, 2i32), Color::RED);
+        rdr.draw_point((2u32, 3u32), Color::GREEN);
+        rdr.draw_point((3isize, 4isize), Color::BLUE);
+        rdr.draw_point((4usize, 5usize), Color::WHITE);
+        rdr.draw_point(vec2!(5, 6), Color::RED);
+        let frame = rdr.capture_frame();
+        rdr.end_draw();
+
+        assert_eq!(frame.get_pixel((1, 2)), Some(Color::RED));
+        assert_eq!(frame.get_pixel((2, 3)), Some(Color::GREEN));
+        assert_eq!(frame.get_pixel((3, 4)), Some(Color::BLUE));
+        assert_eq!(frame.get_pixel((4, 5)), Some(Color::WHITE));
+        assert_eq!(frame.get_pixel((5, 6)), Some(Color::RED));
+
+        assert!(std::panic::catch_unwind(|| Vec2::from((usize::MAX, 0usize))).is_err());
+        assert!(std::panic::catch_unwind(|| Vec2::from((0isize, isize::MIN))).is_err());
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use serde_test::{assert_tokens, assert_de_tokens, assert_de_tokens_error, Token};
+
+        assert_tokens(&vec2!(3, -4), &[
+            Token::Struct { name: "Vec2", len: 2 },
+            Token::Str("x"), Token::I32(3),
+            Token::Str("y"), Token::I32(-4),
+            Token::StructEnd
+        ]);
+
+        assert_tokens(&Color::rgb(255, 136, 0), &[Token::Str("#ff8800")]);
+        assert_de_tokens(&Color::rgb(255, 136, 0), &[Token::Str("f80")]);
+        assert_de_tokens_error::<Color>(&[Token::Str("#ff88")], "color has 4 digits, expected 3, 6 or 8");
+    }
+
+
+    #[test]
+    fn vec2_display_parse() {
+        use crate::math::Vec2ParseError;
+
+        assert_eq!(vec2!(3, 4).to_string(), "(3, 4)");
+        for v in [vec2!(3, 4), vec2!(-12, 0), vec2!(i32::MIN, i32::MAX)] {
+            assert_eq!(v.to_string().parse::<Vec2>(), Ok(v));
+        }
+        assert_eq!(" ( -3 ,4 ) ".parse::<Vec2>(), Ok(vec2!(-3, 4)));
+        assert_eq!("7,-8".parse::<Vec2>(), Ok(vec2!(7, -8)));
+
+        assert_eq!("(1, 2".parse::<Vec2>(), Err(Vec2ParseError::UnbalancedParentheses));
+        assert_eq!("(1, 2, 3)".parse::<Vec2>(), Err(Vec2ParseError::InvalidCoordinateCount(3)));
+        assert_eq!("()".parse::<Vec2>(), Err(Vec2ParseError::InvalidCoordinateCount(1)));
+        assert_eq!("(1, x)".parse::<Vec2>(), Err(Vec2ParseError::InvalidCoordinate("x".to_string())));
+        assert_eq!(Vec2ParseError::InvalidCoordinate("x".to_string()).to_string(), "invalid coordinate \"x\" in vector");
+    }
+
+
+    #[test]
+    fn rects() {
+        use crate::math::Rect;
+
+        let a = Rect::new(vec2!(0, 0), vec2!(10, 10));
+        assert!(a.contains(vec2!(0, 0)));
+        assert!(a.contains(vec2!(9, 9)));
+        assert!(!a.contains(vec2!(10, 5)));
+
+        // negative sizes are normalized
+        assert_eq!(Rect::new(vec2!(10, 10), vec2!(-10, -5)), Rect::new(vec2!(0, 5), vec2!(10, 5)));
+        assert_eq!(Rect::from_corners(vec2!(4, 8), vec2!(2, 1)), Rect::new(vec2!(2, 1), vec2!(2, 7)));
+
+        // overlapping
+        let b = Rect::new(vec2!(5, -2), vec2!(10, 4));
+        assert!(a.intersects(b) && b.intersects(a));
+        assert_eq!(a.intersection(b), Some(Rect::new(vec2!(5, 0), vec2!(5, 2))));
+        assert_eq!(a.union(b), Rect::new(vec2!(0, -2), vec2!(15, 12)));
+
+        // touching by a side
+        let c = Rect::new(vec2!(10, 0), vec2!(3, 3));
+        assert!(!a.intersects(c));
+        assert_eq!(a.intersection(c), None);
+        assert_eq!(a.union(c), Rect::new(vec2!(0, 0), vec2!(13, 10)));
+
+        // disjoint
+        let d = Rect::new(vec2!(20, 20), vec2!(2, 2));
+        assert!(!d.intersects(a));
+        assert_eq!(d.intersection(a), None);
+        assert_eq!(a.union(d), Rect::new(vec2!(0, 0), vec2!(22, 22)));
+
+        let (pos, size): (Vec2, Vec2) = d.into();
+        assert_eq!(Rect::from((pos, size)), d);
+    }
+
+
+    #[test]
+    fn line_editor() {
+        use crate::input::LineEditor;
+
+        let mut editor = LineEditor::new();
+        // backspace and left at the start do nothing
+        assert!(editor.feed(KeyEvent::Backspace));
+        assert!(editor.feed(KeyEvent::Left));
+        assert_eq!((editor.text(), editor.cursor()), ("", 0));
+
+        for c in "héllo".chars() {
+            editor.feed(KeyEvent::Char(c));
+        }
+        assert_eq!((editor.text(), editor.cursor()), ("héllo", 5));
+
+        // insertion in the middle, after a multi-byte char
+        editor.feed(KeyEvent::Home);
+        editor.feed(KeyEvent::Right);
+        editor.feed(KeyEvent::Right);
+        editor.feed(KeyEvent::Char('→'));
+        assert_eq!((editor.text(), editor.cursor()), ("hé→llo", 3));
+
+        editor.feed(KeyEvent::Backspace);
+        editor.feed(KeyEvent::Backspace);
+        assert_eq!((editor.text(), editor.cursor()), ("hllo", 1));
+        editor.feed(KeyEvent::Delete);
+        assert_eq!((editor.text(), editor.cursor()), ("hlo", 1));
+
+        // the cursor stays in the text
+        editor.feed(KeyEvent::End);
+        editor.feed(KeyEvent::Right);
+        editor.feed(KeyEvent::Delete);
+        assert_eq!((editor.text(), editor.cursor()), ("hlo", 3));
+
+        // keys that do not edit the text are left to the caller
+        assert!(!editor.feed(KeyEvent::Up));
+        assert!(!editor.feed(KeyEvent::Char('\n')));
+        assert!(!editor.feed(KeyEvent::Ctrl('c')));
+
+        let mut editor = LineEditor::with_text("abc");
+        editor.feed(KeyEvent::Char('d'));
+        assert_eq!(editor.text(), "abcd");
+        editor.clear();
+        assert_eq!((editor.text(), editor.cursor()), ("", 0));
+    }
+
+
+    #[test]
+    fn repeat_coalescing() {
+        use std::time::{Duration, Instant};
+        use crate::input::RepeatFilter;
+
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut filter = RepeatFilter::new(Duration::from_millis(100));
+        let up = InputEvent::Key(KeyEvent::Up);
+        let down = InputEvent::Key(KeyEvent::Down);
+
+        // autorepeats every 30ms give one event per 100ms
+        let accepted: Vec<u64> = (0..10).map(|i| i * 30).filter(|&ms| filter.accept(&up, at(ms))).collect();
+        assert_eq!(accepted, [0, 120, 240]);
+
+        // distinct keys pass through
+        assert!(filter.accept(&down, at(280)));
+        assert!(filter.accept(&up, at(290)));
+        assert!(!filter.accept(&up, at(300)));
+
+        filter.set_window(Duration::ZERO);
+        assert!(filter.accept(&up, at(310)));
+
+        // through the Input
+        let mut input = Input::with_reader(SlowReader::new(b"aaaabaa", Duration::from_millis(20)));
+        input.set_repeat_coalescing(Duration::from_secs(60));
+        let events: Vec<InputEvent> = input.events().collect();
+        assert_eq!(events, [
+            InputEvent::Key(KeyEvent::Char('a')),
+            InputEvent::Key(KeyEvent::Char('b')),
+            InputEvent::Key(KeyEvent::Char('a'))
+        ]);
+    }
+
+
+    #[test]
+    fn drain_events() {
+        let mut input = Input::with_reader(io::Cursor::new(b"a\x1b[Ab".to_vec()));
+        // wait until the three events are parsed
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(input.drain_events(), [
+            InputEvent::Key(KeyEvent::Char('a')),
+            InputEvent::Key(KeyEvent::Up),
+            InputEvent::Key(KeyEvent::Char('b'))
+        ]);
+        assert_eq!(input.drain_events(), []);
+    }
+
+
+    #[test]
+    fn buffer_policy() {
+        use std::time::Duration;
+        use crate::input::BufferPolicy;
+
+        let keys = |s: &str| s.chars().map(|c| InputEvent::Key(KeyEvent::Char(c))).collect::<Vec<_>>();
+
+        // the application only reads the events once they are all received
+        let mut input = Input::with_reader(SlowReader::new(b"abcdef", Duration::from_millis(10)));
+        input.set_buffer_policy(3, BufferPolicy::DropOldest);
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(input.events().collect::<Vec<_>>(), keys("def"));
+
+        let mut input = Input::with_reader(SlowReader::new(b"abcdef", Duration::from_millis(10)));
+        input.set_buffer_policy(3, BufferPolicy::DropNewest);
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(input.events().collect::<Vec<_>>(), keys("abc"));
+    }
+
+
+    #[test]
+    fn click_detector() {
+        use std::time::{Duration, Instant};
+        use crate::input::{ClickDetector, ClickEvent};
+
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let none = Modifiers::default();
+        let press = |b, x, y| MouseEvent::ButtonPressed(b, vec2!(x, y), none);
+        let left = MouseButton::Left;
+
+        let mut clicks = ClickDetector::default();
+        assert_eq!(clicks.feed(press(left, 5, 4), at(0)), Some(ClickEvent::Click(left, vec2!(5, 4))));
+        assert_eq!(clicks.feed(MouseEvent::ButtonReleased(left, vec2!(5, 4), none), at(50)), None);
+        assert_eq!(clicks.feed(press(left, 6, 4), at(200)), Some(ClickEvent::DoubleClick(left, vec2!(6, 4))));
+        assert_eq!(clicks.feed(press(left, 6, 4), at(400)), Some(ClickEvent::TripleClick(left, vec2!(6, 4))));
+        // a fourth press starts a new click
+        assert_eq!(clicks.feed(press(left, 6, 4), at(600)), Some(ClickEvent::Click(left, vec2!(6, 4))));
+        // the default tolerance is one cell
+        assert_eq!(clicks.feed(press(left, 6, 6), at(700)), Some(ClickEvent::Click(left, vec2!(6, 6))));
+
+        // too slow: two single clicks
+        let mut clicks = ClickDetector::new(Duration::from_millis(300), 1);
+        assert_eq!(clicks.feed(press(left, 0, 0), at(0)), Some(ClickEvent::Click(left, vec2!(0, 0))));
+        assert_eq!(clicks.feed(press(left, 0, 0), at(301)), Some(ClickEvent::Click(left, vec2!(0, 0))));
+
+        // other button or too far away
+        assert_eq!(clicks.feed(press(MouseButton::Right, 0, 0), at(350)),
+                   Some(ClickEvent::Click(MouseButton::Right, vec2!(0, 0))));
+        assert_eq!(clicks.feed(press(MouseButton::Right, 0, 2), at(400)),
+                   Some(ClickEvent::Click(MouseButton::Right, vec2!(0, 2))));
+    }
+
+
+    #[test]
+    fn drag_tracker() {
+        use crate::input::{DragTracker, DragEvent};
+
+        let none = Modifiers::default();
+        let left = MouseButton::Left;
+        let mut drag = DragTracker::new();
+
+        assert_eq!(drag.feed(MouseEvent::Hold(left, vec2!(1, 1), none)), None);
+        assert!(!drag.is_dragging());
+
+        assert_eq!(drag.feed(MouseEvent::ButtonPressed(left, vec2!(2, 4), none)), Some(DragEvent::Begin(left, vec2!(2, 4))));
+        assert_eq!(drag.feed(MouseEvent::Hold(left, vec2!(5, 4), none)), Some(DragEvent::Move(left, vec2!(5, 4), vec2!(3, 0))));
+        assert_eq!(drag.feed(MouseEvent::Hold(left, vec2!(4, 8), none)), Some(DragEvent::Move(left, vec2!(4, 8), vec2!(-1, 4))));
+        assert_eq!((drag.start(), drag.current(), drag.vector()), (Some(vec2!(2, 4)), Some(vec2!(4, 8)), Some(vec2!(2, 4))));
+        assert_eq!(drag.button(), Some(left));
+
+        assert_eq!(drag.feed(MouseEvent::ButtonReleased(left, vec2!(4, 10), none)), Some(DragEvent::End(left, vec2!(4, 10), vec2!(2, 6))));
+        assert!(!drag.is_dragging());
+        assert_eq!(drag.vector(), None);
+
+        // with the events of the input thread
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[<0;3;3M\x1b[<32;5;3M\x1b[<0;5;3m".to_vec()));
+        let mut drag = DragTracker::new();
+        let events: Vec<_> = input.events().filter_map(|event| match event {
+            InputEvent::Mouse(event) => drag.feed(event),
+            _ => None
+        }).collect();
+        assert_eq!(events, [
+            DragEvent::Begin(left, vec2!(2, 2)),
+            DragEvent::Move(left, vec2!(4, 2), vec2!(2, 0)),
+            DragEvent::End(left, vec2!(4, 2), vec2!(2, 0))
+        ]);
+    }
+
+
+    #[test]
+    fn function_keys() {
+        // F5-F12 in the xterm encoding
+        let mut input = Input::with_reader(io::Cursor::new(
+            b"\x1b[15~\x1b[17~\x1b[18~\x1b[19~\x1b[20~\x1b[21~\x1b[23~\x1b[24~".to_vec()));
+        for n in 5..=12 {
+            assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(n)));
+        }
+
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::default() };
+        let mut input = Input::with_reader(io::Cursor::new(
+            b"\x1bOQ\x1b[[E\x1b[11~\x1b[34~\x1bO2S\x1b[24;5~\x1b[23$\x1b[17^\x1b[P\x1b[57376u".to_vec()));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(2)));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(5)));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(1)));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(20)));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModifiedKey(KeyEvent::F(4), shift));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModifiedKey(KeyEvent::F(12), ctrl));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModifiedKey(KeyEvent::F(11), shift));
+        assert_eq!(input.get_event_blocking(), InputEvent::ModifiedKey(KeyEvent::F(6), ctrl));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(1)));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::F(13)));
+    }
+
+
+    #[test]
+    fn read_line() {
+        let mut input = Input::with_reader(io::Cursor::new(b"\x1b[Ahello world\nnext".to_vec()));
+        assert_eq!(input.read_line().unwrap(), "hello world");
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Up));
+        assert_eq!(input.get_event_blocking(), InputEvent::Key(KeyEvent::Char('n')));
+
+        // the input ends before the line feed
+        let mut input = Input::with_reader(io::Cursor::new(b"abc".to_vec()));
+        assert_eq!(input.read_line().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // doubled letters are not coalesced as key repeats, the events read afterwards are
+        let mut input = Input::with_reader(io::Cursor::new(b"hello\nzz".to_vec()));
+        input.set_repeat_coalescing(std::time::Duration::from_secs(60));
+        assert_eq!(input.read_line().unwrap(), "hello");
+        assert_eq!(input.events().collect::<Vec<_>>(), [InputEvent::Key(KeyEvent::Char('z'))]);
+    }
+
+
+    #[test]
+    fn headless_renderer() {
+        let mut rdr = Renderer::new_headless((8, 6));
+        rdr.begin_draw();
+        rdr.clear_screen(Color::BLUE);
+        rdr.draw_rect((1, 1), (3, 2), Color::RED);
+        rdr.draw_point((7, 5), Color::WHITE);
+        rdr.end_draw();
+
+        let directives = rdr.captured_directives();
+        assert_eq!(directives.iter().filter(|d| d.name.starts_with("Draw")).count(), 2);
+        assert!(directives.contains(&CapturedDirective {
+            name: "DrawPoint",
+            args: vec![format!("{:?}", vec2!(7, 5)), format!("{:?}", Color::WHITE)]
+        }));
+        assert_eq!(directives.last().map(|d| d.name), Some("PushFrame"));
+        assert_eq!(Canvas::size(&rdr), vec2!(8, 6));
+
+        assert_eq!(rdr.capture_frame().size(), vec2!(8, 6));
+        assert_eq!(rdr.pixel_at((0, 0)), Some(Color::BLUE));
+        assert_eq!(rdr.pixel_at((2, 2)), Some(Color::RED));
+        assert_eq!(rdr.pixel_at((4, 2)), Some(Color::BLUE));
+        assert_eq!(rdr.pixel_at((7, 5)), Some(Color::WHITE));
+        assert_eq!(rdr.pixel_at((8, 5)), None);
+
+        // the other renderers do not capture anything
+        let rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+        assert!(rdr.captured_directives().is_empty());
+    }
+
+
+    #[test]
+    fn headless_renderer_before_first_frame() {
+        let mut rdr = Renderer::new_headless((8, 8));
+        assert_eq!(rdr.capture_frame().size(), vec2!(8, 8));
+        assert_eq!(rdr.pixel_at((7, 7)), Some(Color::BLACK));
+
+        // the whole screen is written, with exact colors whatever the environment
+        rdr.set_synchronized_output(false);
+        rdr.mark_dirty((0, 0), (2, 2));
+        let frame = rdr.render_to_string();
+        assert!(frame.starts_with("\x1b[H"), "{:?}", frame);
+        rdr.begin_draw();
+        rdr.draw_point((0, 0), Color::rgb(1, 2, 3));
+        let frame = rdr.render_to_string();
+        assert!(frame.contains(&format!("{:-}", Color::rgb(1, 2, 3))), "{:?}", frame);
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn take_captured_directives() {
+        let mut rdr = Renderer::new_headless((8, 6));
+        assert_eq!(rdr.take_captured_directives().len(), rdr.captured_directives().len());
+        assert!(rdr.captured_directives().is_empty());
+
+        rdr.begin_draw();
+        rdr.draw_whole_image(Arc::new(Mutex::new(Image::new(40, 30))), (0, 0));
+        let mut batch = crate::rds::DrawBatch::new();
+        batch.draw_point((1, 1), Color::RED);
+        batch.draw_point((2, 2), Color::RED);
+        rdr.draw_batch(batch);
+        rdr.end_draw();
+
+        let names: Vec<_> = rdr.take_captured_directives().iter().map(|d| d.name).collect();
+        // the screen has its size from the start, it is not resized by the first frame
+        assert_eq!(names, ["BeginFrame", "DrawWholeImage", "DrawPoint", "DrawPoint", "PushFrame"]);
+        rdr.begin_draw();
+        rdr.draw_whole_image(Arc::new(Mutex::new(Image::new(40, 30))), (0, 0));
+        let directives = rdr.take_captured_directives();
+        // the image is summarized by its size
+        assert_eq!(directives[1].args, ["Image(40x30)", &format!("{:?}", vec2!(0, 0))]);
+        rdr.end_draw();
+    }
+
+
+    #[test]
+    fn software_cursor() {
+        use std::time::{Duration, Instant};
+
+        let (mut server, _) = headless_server(vec2!(4, 4));
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = Arc::clone(&now);
+        server.set_clock(move || *clock.lock().unwrap());
+        let inverted = |c: Color| Color::rgb(255 - c.r, 255 - c.g, 255 - c.b);
+
+        server.execute(RenderingDirective::SetCursor(Some(vec2!(1, 3)), CursorStyle::Block));
+        server.execute(RenderingDirective::ClearScreen(Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        // the cell of the pixel (1, 3) is made of the pixels (1, 2) and (1, 3)
+        assert_eq!(server.prev_screen().get_pixel((1, 2)), Some(inverted(Color::BLUE)));
+        assert_eq!(server.prev_screen().get_pixel((1, 3)), Some(inverted(Color::BLUE)));
+        assert_eq!(server.prev_screen().get_pixel((1, 1)), Some(Color::BLUE));
+        assert_eq!(server.prev_screen().get_pixel((0, 3)), Some(Color::BLUE));
+
+        // the screen buffer is untouched, and the cursor is drawn again over the next frame
+        assert_eq!(capture(&mut server).get_pixel((1, 3)), Some(Color::BLUE));
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.prev_screen().get_pixel((1, 3)), Some(inverted(Color::RED)));
+
+        server.execute(RenderingDirective::SetCursor(Some(vec2!(2, 0)), CursorStyle::Underline));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.prev_screen().get_pixel((1, 3)), Some(Color::RED));
+        assert_eq!(server.prev_screen().get_pixel((2, 0)), Some(Color::RED));
+        assert_eq!(server.prev_screen().get_pixel((2, 1)), Some(inverted(Color::RED)));
+
+        // blinking: shown during the first half of the period
+        server.execute(RenderingDirective::SetCursorBlink(Some(Duration::from_millis(1000))));
+        *now.lock().unwrap() += Duration::from_millis(600);
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.prev_screen().get_pixel((2, 1)), Some(Color::RED));
+        *now.lock().unwrap() += Duration::from_millis(500);
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.prev_screen().get_pixel((2, 1)), Some(inverted(Color::RED)));
+
+        server.execute(RenderingDirective::SetCursor(None, CursorStyle::Bar));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.prev_screen().get_pixel((2, 1)), Some(Color::RED));
+    }
+
+
+    #[test]
+    fn unchanged_rows() {
+        let (mut server, buffer) = headless_server(vec2!(10, 8));
+        server.execute(RenderingDirective::ClearScreen(Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        buffer.take();
+
+        // nothing changed: no cell is compared nor written
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 0);
+        let out = buffer.take();
+        assert!(!out.contains(['▀', '▄', '█', ' ']), "{:?}", out);
+
+        // only the row of the change is compared, the cursor is moved to it
+        server.execute(RenderingDirective::DrawPoint(vec2!(4, 5), Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 10);
+        let out = buffer.take();
+        assert!(out.contains("\x1b[3;5H"), "{:?}", out);
+        assert_eq!(out.matches(['▀', '▄', '█', ' ']).count(), 1);
+    }
+
+
+    #[test]
+    fn color_runs() {
+        let (mut server, buffer) = headless_server(vec2!(10, 2));
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take(), format!("\x1b[H\x1b[48;2;255;0;0m{}", " ".repeat(10)));
+
+        // the colors shown the same way in the color mode are sent once
+        let (mut server, buffer) = headless_server(vec2!(10, 2));
+        server.execute(RenderingDirective::SetColorMode(ColorMode::Ansi256));
+        for x in 0..10 {
+            server.execute(RenderingDirective::DrawLine(vec2!(x, 0), vec2!(x, 1), Color::rgb(240 + x as u8, 0, 0)));
+        }
+        buffer.take();
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take(), format!("\x1b[H\x1b[48;5;196m{}", " ".repeat(10)));
+    }
+
+
+    #[test]
+    fn damage_only() {
+        let (mut server, buffer) = headless_server(vec2!(10, 10));
+        server.execute(RenderingDirective::PushFrame);
+        server.execute(RenderingDirective::SetDamageOnly(true));
+        buffer.take();
+
+        // only the marked cells are written, even if other cells changed
+        server.execute(RenderingDirective::ClearScreen(Color::RED));
+        server.execute(RenderingDirective::MarkDirty(vec2!(2, 2), vec2!(2, 2)));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 2);
+        assert_eq!(buffer.take().matches(' ').count(), 2);
+
+        // the marked cells are written even if they did not change, nothing is written without regions
+        server.execute(RenderingDirective::MarkDirty(vec2!(2, 2), vec2!(1, 1)));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take().matches(' ').count(), 1);
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 0);
+        assert_eq!(buffer.take(), "\x1b[H");
+
+        // overlapping regions write their cells once, the regions out of the screen are ignored
+        server.execute(RenderingDirective::MarkDirty(vec2!(2, 2), vec2!(2, 2)));
+        server.execute(RenderingDirective::MarkDirty(vec2!(3, 3), vec2!(2, 3)));
+        server.execute(RenderingDirective::MarkDirty(vec2!(-5, 30), vec2!(20, 4)));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(server.examined_cells(), 5);
+        assert_eq!(buffer.take().matches(' ').count(), 5);
+
+        // the frames are compared again once the mode is disabled
+        server.execute(RenderingDirective::SetDamageOnly(false));
+        server.execute(RenderingDirective::DrawPoint(vec2!(0, 9), Color::BLUE));
+        server.execute(RenderingDirective::PushFrame);
+        assert_eq!(buffer.take().matches(['▀', '▄']).count(), 1);
+    }
+
+
+    #[test]
+    fn synchronized_output_default() {
+        let buffer = SharedBuffer::default();
+        let mut rdr = Renderer::with_output(buffer.clone(), RendererConfig::new());
+        rdr.begin_draw_with_size(vec2!(4, 4));
+        rdr.draw_rect((0, 0), (2, 2), Color::RED);
+        rdr.end_draw();
+        rdr.capture_frame(); // waits for the frame to be written
+
+        let out = buffer.take();
+        let frame = &out[out.find("\x1b[?2026h").expect("no begin marker")..];
+        assert!(frame.ends_with("\x1b[?2026l"), "{:?}", frame);
+        assert!(frame.contains("\x1b[H"));
+
+        // the alias disables it as well
+        rdr.set_synchronized(false);
+        rdr.begin_draw_with_size(vec2!(4, 4));
+        rdr.draw_point((0, 0), Color::BLUE);
+        rdr.end_draw();
+        rdr.capture_frame();
+        let out = buffer.take();
+        assert!(!out.is_empty() && !out.contains("2026"), "{:?}", out);
+    }
 }
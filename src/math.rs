@@ -26,6 +26,9 @@
 
 
 use std::ops::{Add, Sub, AddAssign, SubAssign, Mul, MulAssign, Div, DivAssign};
+use std::fmt;
+use std::str::FromStr;
+use std::error::Error;
 
 #[macro_export]
 macro_rules! vec2 {
@@ -37,6 +40,7 @@ macro_rules! vec2 {
 /// Two dimentional vector. Has math operations implemented.
 /// Can be instanced with the macro `vec2!`.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec2 {
     pub x: i32,
     pub y: i32
@@ -65,9 +69,110 @@ impl Vec2 {
         }
     }
 
+
+    /// Returns the components as a tuple.
+    pub const fn xy(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+
+    /// Returns a copy of the vector with `x` as first component.
+    pub const fn with_x(self, x: i32) -> Vec2 {
+        vec2!(x, self.y)
+    }
+
+
+    /// Returns a copy of the vector with `y` as second component.
+    pub const fn with_y(self, y: i32) -> Vec2 {
+        vec2!(self.x, y)
+    }
+
+
+    /// Linear interpolation between `self` (`t = 0`) and `other` (`t = 1`), rounded to the nearest integers.
+    pub fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        let mix = |a: i32, b: i32| (a as f32 + (b as f32 - a as f32) * t).round() as i32;
+        vec2!(mix(self.x, other.x), mix(self.y, other.y))
+    }
+
+
+    /// Same as `lerp` with `t = num / den`, but computed only with integers so that the result does not
+    /// depend on the platform. Halfway values are rounded away from zero, like `lerp`, and the values out
+    /// of the range of `i32` are saturated.
+    /// 
+    /// Panics if `den` is zero.
+    pub fn lerp_int(self, other: Vec2, num: i32, den: i32) -> Vec2 {
+        let (num, den) = if den < 0 {(-(num as i128), -(den as i128))} else {(num as i128, den as i128)};
+        let mix = |a: i32, b: i32| {
+            // the result multiplied by den, rounded once divided
+            let n = a as i128 * den + (b as i128 - a as i128) * num;
+            let rounded = if n >= 0 {(2 * n + den) / (2 * den)} else {(2 * n - den) / (2 * den)};
+            rounded.clamp(i32::MIN as i128, i32::MAX as i128) as i32
+        };
+        vec2!(mix(self.x, other.x), mix(self.y, other.y))
+    }
+
+}
+
+
+
+impl fmt::Display for Vec2 {
+
+    /// Writes the vector as `(x, y)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+
+impl FromStr for Vec2 {
+    type Err = Vec2ParseError;
+
+    /// Parses a vector written as `(x, y)`. The parentheses are optional and whitespace is ignored
+    /// around the coordinates, so `"3,-4"` is also accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let inner = match (s.strip_prefix('('), s.ends_with(')')) {
+            (Some(rest), true) => &rest[..rest.len() - 1],
+            (None, false) => s,
+            _ => return Err(Vec2ParseError::UnbalancedParentheses)
+        };
+
+        let coords: Vec<&str> = inner.split(',').map(str::trim).collect();
+        if coords.len() != 2 {
+            return Err(Vec2ParseError::InvalidCoordinateCount(coords.len()));
+        }
+        let parse = |c: &str| c.parse::<i32>().map_err(|_| Vec2ParseError::InvalidCoordinate(c.to_string()));
+        Ok(Vec2::new(parse(coords[0])?, parse(coords[1])?))
+    }
+}
+
+
+/// Error returned when parsing a Vec2 from a string fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Vec2ParseError {
+    /// The string has an opening parenthesis without a closing one, or the reverse.
+    UnbalancedParentheses,
+    /// The string does not have 2 comma-separated coordinates.
+    InvalidCoordinateCount(usize),
+    /// A coordinate is not an integer.
+    InvalidCoordinate(String)
+}
+
+
+impl fmt::Display for Vec2ParseError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Vec2ParseError::UnbalancedParentheses => write!(f, "unbalanced parentheses in vector"),
+            Vec2ParseError::InvalidCoordinateCount(n) => write!(f, "vector has {} coordinates, expected 2", n),
+            Vec2ParseError::InvalidCoordinate(c) => write!(f, "invalid coordinate {:?} in vector", c)
+        }
+    }
 }
 
 
+impl Error for Vec2ParseError {}
+
 
 impl Add for Vec2 {
     type Output = Self;
@@ -98,7 +203,7 @@ impl Sub for Vec2 {
 impl SubAssign for Vec2 {
 
     fn sub_assign(&mut self, rhs: Self) {
-        *self = *self + rhs;
+        *self = *self - rhs;
     }
 }
 
@@ -137,161 +242,196 @@ impl DivAssign<i32> for Vec2 {
 }
 
 
-impl AsRef<Vec2> for (u32, u32) {
+impl From<(i32, i32)> for Vec2 {
 
-
-    fn as_ref(&self) -> &Vec2 {
-        if self.0 > i32::MAX as u32 || self.1 > i32::MAX as u32 {
-            panic!("Cannot convert {:?} to Vec2, integeroverflow", self);
-        }
-        unsafe {
-            let ptr: *const (u32, u32) = self;
-            &*(ptr as *const Vec2)
-        }
+    fn from(v: (i32, i32)) -> Self {
+        Vec2::new(v.0, v.1)
     }
 }
 
 
-impl AsRef<Vec2> for (i32, i32) {
-
+impl From<(u32, u32)> for Vec2 {
 
-    fn as_ref(&self) -> &Vec2 {
-        unsafe {
-            let ptr: *const (i32, i32) = self;
-            &*(ptr as *const Vec2)
+    fn from(v: (u32, u32)) -> Self {
+        match (i32::try_from(v.0), i32::try_from(v.1)) {
+            (Ok(x), Ok(y)) => Vec2::new(x, y),
+            _ => panic!("Cannot convert {:?} to Vec2, integeroverflow", v)
         }
     }
 }
 
 
-impl AsMut<Vec2> for (u32, u32) {
+impl From<(isize, isize)> for Vec2 {
 
-
-    fn as_mut(&mut self) -> &mut Vec2 {
-        if self.0 > i32::MAX as u32 || self.1 > i32::MAX as u32 {
-            panic!("Cannot convert {:?} to Vec2, integeroverflow", self);
-        }
-        unsafe {
-            let ptr: *mut (u32, u32) = self;
-            &mut *(ptr as *mut Vec2)
+    fn from(v: (isize, isize)) -> Self {
+        match (i32::try_from(v.0), i32::try_from(v.1)) {
+            (Ok(x), Ok(y)) => Vec2::new(x, y),
+            _ => panic!("Cannot convert {:?} to Vec2, integeroverflow", v)
         }
     }
 }
 
 
-impl AsMut<Vec2> for (i32, i32) {
+impl From<(usize, usize)> for Vec2 {
 
-
-    fn as_mut(&mut self) -> &mut Vec2 {
-        unsafe {
-            let ptr: *mut (i32, i32) = self;
-            &mut *(ptr as *mut Vec2)
+    fn from(v: (usize, usize)) -> Self {
+        match (i32::try_from(v.0), i32::try_from(v.1)) {
+            (Ok(x), Ok(y)) => Vec2::new(x, y),
+            _ => panic!("Cannot convert {:?} to Vec2, integeroverflow", v)
         }
     }
 }
 
 
-impl Into<Vec2> for (i32, i32) {
-
+impl From<&Vec2> for Vec2 {
 
-    fn into(self) -> Vec2 {
-        Vec2::new(self.0, self.1)
+    fn from(v: &Vec2) -> Self {
+        *v
     }
 }
 
 
-impl Into<Vec2> for (u32, u32) {
+impl AsRef<Vec2> for Vec2 {
+
+    fn as_ref(&self) -> &Vec2 {
+        self
+    }
+}
+
 
+impl AsMut<Vec2> for Vec2 {
 
-    fn into(self) -> Vec2 {
-        Vec2::new(self.0 as i32, self.1 as i32)
+    fn as_mut(&mut self) -> &mut Vec2 {
+        self
     }
 }
 
+impl From<[i32; 2]> for Vec2 {
 
-impl AsRef<Vec2> for (usize, usize) {
+    fn from(v: [i32; 2]) -> Self {
+        Vec2::new(v[0], v[1])
+    }
+}
 
 
-    fn as_ref(&self) -> &Vec2 {
-        if self.0 > i32::MAX as usize || self.1 > i32::MAX as usize {
-            panic!("Cannot convert {:?} to Vec2, integeroverflow", self);
-        }
-        unsafe {
-            let ptr: *const (usize, usize) = self;
-            &*(ptr as *const Vec2)
-        }
+impl From<Vec2> for [i32; 2] {
+
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
     }
 }
 
 
-impl AsRef<Vec2> for (isize, isize) {
+impl From<(f32, f32)> for Vec2 {
+
+    /// The coordinates are rounded to the nearest integers.
+    fn from(v: (f32, f32)) -> Self {
+        Vec2::new(v.0.round() as i32, v.1.round() as i32)
+    }
+}
 
 
-    fn as_ref(&self) -> &Vec2 {
-        unsafe {
-            let ptr: *const (isize, isize) = self;
-            &*(ptr as *const Vec2)
-        }
+impl From<Vec2> for (f32, f32) {
+
+    fn from(v: Vec2) -> Self {
+        (v.x as f32, v.y as f32)
     }
 }
 
 
-impl AsMut<Vec2> for (usize, usize) {
+/// Rectangle given by the position of its top left corner and its size.
+/// 
+/// The pixels of the rectangle go from `pos` included to `pos + size` excluded, so two rectangles
+/// that touch by a side do not intersect.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub pos: Vec2,
+    pub size: Vec2
+}
+
 
+impl Rect {
 
-    fn as_mut(&mut self) -> &mut Vec2 {
-        if self.0 > i32::MAX as usize || self.1 > i32::MAX as usize {
-            panic!("Cannot convert {:?} to Vec2, integeroverflow", self);
+    /// Creates a rectangle. A negative size is normalized: the rectangle then extends to the left
+    /// (or to the top) of `pos`.
+    pub fn new(pos: Vec2, size: Vec2) -> Self {
+        let mut rect = Rect { pos, size };
+        if size.x < 0 {
+            rect.pos.x += size.x;
+            rect.size.x = -size.x;
         }
-        unsafe {
-            let ptr: *mut (usize, usize) = self;
-            &mut *(ptr as *mut Vec2)
+        if size.y < 0 {
+            rect.pos.y += size.y;
+            rect.size.y = -size.y;
         }
+        rect
     }
-}
 
 
-impl AsMut<Vec2> for (isize, isize) {
+    /// Creates the rectangle going from `a` included to `b` excluded, in any order.
+    pub fn from_corners(a: Vec2, b: Vec2) -> Self {
+        Rect::new(a, b - a)
+    }
 
 
-    fn as_mut(&mut self) -> &mut Vec2 {
-        unsafe {
-            let ptr: *mut (isize, isize) = self;
-            &mut *(ptr as *mut Vec2)
-        }
+    /// Returns the corner opposite to `pos`, excluded from the rectangle.
+    pub fn end(&self) -> Vec2 {
+        self.pos + self.size
     }
-}
 
 
-impl Into<Vec2> for (isize, isize) {
+    /// Returns true if the rectangle has no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.size.x <= 0 || self.size.y <= 0
+    }
 
 
-    fn into(self) -> Vec2 {
-        Vec2::new(self.0 as i32, self.1 as i32)
+    /// Returns true if the pixel `p` is inside the rectangle.
+    pub fn contains(&self, p: Vec2) -> bool {
+        let end = self.end();
+        p.x >= self.pos.x && p.y >= self.pos.y && p.x < end.x && p.y < end.y
     }
-}
 
 
-impl Into<Vec2> for (usize, usize) {
+    /// Returns true if the rectangles have pixels in common.
+    pub fn intersects(&self, other: Rect) -> bool {
+        self.intersection(other).is_some()
+    }
+
+
+    /// Returns the pixels common to both rectangles, None if there are none.
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        let (end, other_end) = (self.end(), other.end());
+        let pos = vec2!(self.pos.x.max(other.pos.x), self.pos.y.max(other.pos.y));
+        let end = vec2!(end.x.min(other_end.x), end.y.min(other_end.y));
+        let rect = Rect { pos, size: end - pos };
+        if rect.is_empty() { None } else { Some(rect) }
+    }
 
 
-    fn into(self) -> Vec2 {
-        Vec2::new(self.0 as i32, self.1 as i32)
+    /// Returns the smallest rectangle containing both rectangles.
+    pub fn union(&self, other: Rect) -> Rect {
+        let (end, other_end) = (self.end(), other.end());
+        let pos = vec2!(self.pos.x.min(other.pos.x), self.pos.y.min(other.pos.y));
+        let end = vec2!(end.x.max(other_end.x), end.y.max(other_end.y));
+        Rect { pos, size: end - pos }
     }
 }
 
 
-impl AsRef<Vec2> for Vec2 {
+impl From<(Vec2, Vec2)> for Rect {
 
-    fn as_ref(&self) -> &Vec2 {
-        self
+    /// Converts a position and a size.
+    fn from((pos, size): (Vec2, Vec2)) -> Self {
+        Rect::new(pos, size)
     }
 }
 
 
-impl AsMut<Vec2> for Vec2 {
+impl From<Rect> for (Vec2, Vec2) {
 
-    fn as_mut(&mut self) -> &mut Vec2 {
-        self
+    fn from(r: Rect) -> Self {
+        (r.pos, r.size)
     }
-}
\ No newline at end of file
+}
@@ -25,25 +25,45 @@
 */
 
 
+#[cfg(unix)]
 extern crate libc;
 
-use crate::math::Vec2;
-use crate::img::{Image, Color};
+use crate::math::{Vec2, Rect};
+use crate::img::{Image, Color, ColorMode, Canvas};
 use crate::input::Input;
+use crate::boxchars::{self, BoxStyle};
 
+#[cfg(unix)]
 use termios::*;
 
 use std::mem;
+use std::cell::Cell;
+use std::thread::LocalKey;
+use std::ops::{Deref, DerefMut, Range};
+use std::fmt;
 
-use std::io::{stdout, Write};
+use std::io::{self, stdout, Write, Read, BufRead, BufReader};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
 
 use std::thread;
-use std::sync::{mpsc, Barrier, Arc, Mutex};
+use std::sync::{mpsc, Barrier, Arc, Mutex, MutexGuard, Once, PoisonError, TryLockError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::panic;
+use std::time::{Duration, Instant};
 
 use std::io::stdin;
-use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::io::IsTerminal;
+#[cfg(windows)]
+use std::ffi::c_void;
 
-const NCCS: usize = 32;
+
+/// Number of a standard stream on Windows, where there are no file descriptors: 0 for stdin and 1 for stdout.
+#[cfg(windows)]
+pub(crate) type RawFd = i32;
 
 
 /// csi macro rule
@@ -53,240 +73,2490 @@ macro_rules! csi {
 
 
 /// Commands that are sent to the rendering server by the Renderer singleton.
-enum RenderingDirective {
+#[derive(Debug)]
+pub(crate) enum RenderingDirective {
     DrawLine(Vec2, Vec2, Color),
+    DrawLineGradient(Vec2, Vec2, Color, Color),
+    DrawLineAa(Vec2, Vec2, Color),
+    DrawBezierQuad(Vec2, Vec2, Vec2, Color),
+    DrawBezierCubic(Vec2, Vec2, Vec2, Vec2, Color),
     DrawRect(Vec2, Vec2, Color),
     DrawRectBoudary(Vec2, Vec2, Color),
+    DrawRoundedRect(Vec2, Vec2, i32, Color),
+    DrawRoundedRectBoundary(Vec2, Vec2, i32, Color),
     DrawEllipseBoudary(Vec2, Vec2, Color),
+    DrawEllipse(Vec2, Vec2, Color),
+    DrawArc(Vec2, Vec2, f32, f32, Color),
+    DrawPolygon(Vec<Vec2>, Color),
+    DrawPolygonBoundary(Vec<Vec2>, Color),
     DrawPoint(Vec2, Color),
+    DrawText(Vec2, String, Color),
+    DrawBoxFrame(Vec2, Vec2, BoxStyle, Color),
+    DrawChar(Vec2, char, Color),
 
     DrawImage(Arc<Mutex<Image>>, Vec2, Vec2, Vec2, Option<Color>),
     DrawWholeImageAlpha(Arc<Mutex<Image>>, Vec2, Color),
     DrawWholeImage(Arc<Mutex<Image>>, Vec2),
+    DrawImageRegion(Arc<Mutex<Image>>, (Vec2, Vec2), Vec2, Option<Color>),
 
     ClearScreen(Color),
+    GradientLinear(Vec2, Vec2, Color, Color),
+    GradientRadial(Vec2, i32, Color, Color),
 
     UpdateScreenSize(Vec2),
+    SetSynchronized(bool),
+    SetColorMode(ColorMode),
+    SetDithering(bool),
+    SetRenderMode(RenderMode),
+    SetBackground(Option<Image>),
+    PushClip(Vec2, Vec2),
+    PopClip,
+    SetCamera(Vec2),
+    CreateLayer(Color),
+    MarkDirty(Vec2, Vec2),
+    SetDamageOnly(bool),
+    SetCursor(Option<Vec2>, CursorStyle),
+    SetCursorBlink(Option<Duration>),
+    SetTitle(String),
+    RingBell,
+    VisualBell,
+    SetLayer(Option<LayerId>),
+    SetPriority(Priority),
+    SetFrameBudget(Option<Duration>),
+    SetBufferPoolSize(usize),
+    StartRecording(File),
+    StopRecording,
     BeginFrame,
-    PushFrame
+    PushFrame,
+
+    GetFrameBytes(mpsc::Sender<usize>),
+    CaptureFrame(mpsc::Sender<Image>),
+    RenderToString(mpsc::Sender<String>),
+    Batch(Vec<RenderingDirective>),
+    LendScreen(mpsc::Sender<Image>, mpsc::Receiver<Image>),
+    Shutdown(mpsc::Sender<()>),
+    QueryCursorPosition,
+    /// the directives are removed from the server if the flag is set
+    GetCapturedDirectives(mpsc::Sender<Vec<CapturedDirective>>, bool)
 }
 
 
-/// This is the core of the library. It will send commands to the rendering server to print on screen.
-/// 
-/// # Usage
+impl RenderingDirective {
+
+    /// Returns true for the directives that draw on the screen buffer.
+    fn is_draw(&self) -> bool {
+        matches!(self, 
+            RenderingDirective::DrawLine(..) | RenderingDirective::DrawLineGradient(..)
+            | RenderingDirective::DrawLineAa(..)
+            | RenderingDirective::DrawBezierQuad(..) | RenderingDirective::DrawBezierCubic(..)
+            | RenderingDirective::DrawRect(..) | RenderingDirective::DrawRectBoudary(..)
+            | RenderingDirective::DrawRoundedRect(..) | RenderingDirective::DrawRoundedRectBoundary(..)
+            | RenderingDirective::DrawEllipseBoudary(..) | RenderingDirective::DrawEllipse(..)
+            | RenderingDirective::DrawArc(..)
+            | RenderingDirective::DrawPolygon(..) | RenderingDirective::DrawPolygonBoundary(..)
+            | RenderingDirective::DrawPoint(..) | RenderingDirective::DrawText(..)
+            | RenderingDirective::DrawBoxFrame(..) | RenderingDirective::DrawChar(..)
+            | RenderingDirective::DrawImage(..)
+            | RenderingDirective::DrawWholeImageAlpha(..) | RenderingDirective::DrawWholeImage(..)
+            | RenderingDirective::DrawImageRegion(..)
+            | RenderingDirective::ClearScreen(..) | RenderingDirective::GradientLinear(..)
+            | RenderingDirective::GradientRadial(..)
+        )
+    }
+
+
+    /// Moves the pixel coordinates of a drawing directive by `-offset`. Directives using cell
+    /// coordinates are left unchanged.
+    fn translate(&mut self, offset: Vec2) {
+        match self {
+            RenderingDirective::DrawLine(p1, p2, _) | RenderingDirective::DrawLineGradient(p1, p2, _, _)
+            | RenderingDirective::DrawLineAa(p1, p2, _) | RenderingDirective::GradientLinear(p1, p2, _, _) => {
+                *p1 -= offset;
+                *p2 -= offset;
+            }
+            RenderingDirective::DrawBezierQuad(p0, p1, p2, _) => {
+                for p in [p0, p1, p2] {
+                    *p -= offset;
+                }
+            }
+            RenderingDirective::DrawBezierCubic(p0, p1, p2, p3, _) => {
+                for p in [p0, p1, p2, p3] {
+                    *p -= offset;
+                }
+            }
+            RenderingDirective::DrawRect(p, ..) | RenderingDirective::DrawRectBoudary(p, ..)
+            | RenderingDirective::DrawRoundedRect(p, ..) | RenderingDirective::DrawRoundedRectBoundary(p, ..)
+            | RenderingDirective::DrawEllipseBoudary(p, ..) | RenderingDirective::DrawEllipse(p, ..)
+            | RenderingDirective::DrawArc(p, ..)
+            | RenderingDirective::DrawPoint(p, _) | RenderingDirective::DrawText(p, ..)
+            | RenderingDirective::DrawImage(_, p, ..) | RenderingDirective::DrawWholeImageAlpha(_, p, _)
+            | RenderingDirective::DrawWholeImage(_, p) | RenderingDirective::DrawImageRegion(_, _, p, _)
+            | RenderingDirective::GradientRadial(p, ..) => *p -= offset,
+            RenderingDirective::DrawPolygon(points, _) | RenderingDirective::DrawPolygonBoundary(points, _) => {
+                for p in points {
+                    *p -= offset;
+                }
+            }
+            _ => ()
+        }
+    }
+
+
+    /// Returns the name and the arguments of the directive, formatted with Debug. Images are summarized by
+    /// their size and the channels of the requests are left out.
+    fn record(&self) -> CapturedDirective {
+        use RenderingDirective::*;
+
+        fn arg<T: fmt::Debug>(value: &T) -> String {
+            format!("{:?}", value)
+        }
+        fn image(img: &Image) -> String {
+            format!("Image({}x{})", img.size().x, img.size().y)
+        }
+        fn shared(img: &Arc<Mutex<Image>>) -> String {
+            image(&img.lock().unwrap_or_else(PoisonError::into_inner))
+        }
+
+        let (name, args) = match self {
+            DrawLine(a, b, c) => ("DrawLine", vec![arg(a), arg(b), arg(c)]),
+            DrawLineGradient(a, b, c1, c2) => ("DrawLineGradient", vec![arg(a), arg(b), arg(c1), arg(c2)]),
+            DrawLineAa(a, b, c) => ("DrawLineAa", vec![arg(a), arg(b), arg(c)]),
+            DrawBezierQuad(p0, p1, p2, c) => ("DrawBezierQuad", vec![arg(p0), arg(p1), arg(p2), arg(c)]),
+            DrawBezierCubic(p0, p1, p2, p3, c) => ("DrawBezierCubic", vec![arg(p0), arg(p1), arg(p2), arg(p3), arg(c)]),
+            DrawRect(p, s, c) => ("DrawRect", vec![arg(p), arg(s), arg(c)]),
+            DrawRectBoudary(p, s, c) => ("DrawRectBoudary", vec![arg(p), arg(s), arg(c)]),
+            DrawRoundedRect(p, s, r, c) => ("DrawRoundedRect", vec![arg(p), arg(s), arg(r), arg(c)]),
+            DrawRoundedRectBoundary(p, s, r, c) => ("DrawRoundedRectBoundary", vec![arg(p), arg(s), arg(r), arg(c)]),
+            DrawEllipseBoudary(p, s, c) => ("DrawEllipseBoudary", vec![arg(p), arg(s), arg(c)]),
+            DrawEllipse(p, s, c) => ("DrawEllipse", vec![arg(p), arg(s), arg(c)]),
+            DrawArc(p, s, start, end, c) => ("DrawArc", vec![arg(p), arg(s), arg(start), arg(end), arg(c)]),
+            DrawPolygon(points, c) => ("DrawPolygon", vec![arg(points), arg(c)]),
+            DrawPolygonBoundary(points, c) => ("DrawPolygonBoundary", vec![arg(points), arg(c)]),
+            DrawPoint(p, c) => ("DrawPoint", vec![arg(p), arg(c)]),
+            DrawText(p, text, c) => ("DrawText", vec![arg(p), arg(text), arg(c)]),
+            DrawBoxFrame(p, s, style, c) => ("DrawBoxFrame", vec![arg(p), arg(s), arg(style), arg(c)]),
+            DrawChar(cell, ch, c) => ("DrawChar", vec![arg(cell), arg(ch), arg(c)]),
+
+            DrawImage(img, pos, size, off, alpha) =>
+                ("DrawImage", vec![shared(img), arg(pos), arg(size), arg(off), arg(alpha)]),
+            DrawWholeImageAlpha(img, pos, alpha) => ("DrawWholeImageAlpha", vec![shared(img), arg(pos), arg(alpha)]),
+            DrawWholeImage(img, pos) => ("DrawWholeImage", vec![shared(img), arg(pos)]),
+            DrawImageRegion(img, rect, dst, colorkey) =>
+                ("DrawImageRegion", vec![shared(img), arg(rect), arg(dst), arg(colorkey)]),
+
+            ClearScreen(c) => ("ClearScreen", vec![arg(c)]),
+            GradientLinear(start, end, a, b) => ("GradientLinear", vec![arg(start), arg(end), arg(a), arg(b)]),
+            GradientRadial(center, radius, inner, outer) =>
+                ("GradientRadial", vec![arg(center), arg(radius), arg(inner), arg(outer)]),
+
+            UpdateScreenSize(size) => ("UpdateScreenSize", vec![arg(size)]),
+            SetSynchronized(enabled) => ("SetSynchronized", vec![arg(enabled)]),
+            SetColorMode(mode) => ("SetColorMode", vec![arg(mode)]),
+            SetDithering(enabled) => ("SetDithering", vec![arg(enabled)]),
+            SetRenderMode(mode) => ("SetRenderMode", vec![arg(mode)]),
+            SetBackground(background) => ("SetBackground", vec![background.as_ref().map_or("None".to_string(), image)]),
+            PushClip(p, s) => ("PushClip", vec![arg(p), arg(s)]),
+            PopClip => ("PopClip", vec![]),
+            SetCamera(camera) => ("SetCamera", vec![arg(camera)]),
+            CreateLayer(colorkey) => ("CreateLayer", vec![arg(colorkey)]),
+            MarkDirty(p, s) => ("MarkDirty", vec![arg(p), arg(s)]),
+            SetDamageOnly(enabled) => ("SetDamageOnly", vec![arg(enabled)]),
+            SetCursor(pos, style) => ("SetCursor", vec![arg(pos), arg(style)]),
+            SetCursorBlink(period) => ("SetCursorBlink", vec![arg(period)]),
+            SetTitle(title) => ("SetTitle", vec![arg(title)]),
+            RingBell => ("RingBell", vec![]),
+            VisualBell => ("VisualBell", vec![]),
+            SetLayer(layer) => ("SetLayer", vec![arg(layer)]),
+            SetPriority(priority) => ("SetPriority", vec![arg(priority)]),
+            SetFrameBudget(budget) => ("SetFrameBudget", vec![arg(budget)]),
+            SetBufferPoolSize(size) => ("SetBufferPoolSize", vec![arg(size)]),
+            StartRecording(file) => ("StartRecording", vec![arg(file)]),
+            StopRecording => ("StopRecording", vec![]),
+            BeginFrame => ("BeginFrame", vec![]),
+            PushFrame => ("PushFrame", vec![]),
+
+            GetFrameBytes(_) => ("GetFrameBytes", vec![]),
+            CaptureFrame(_) => ("CaptureFrame", vec![]),
+            RenderToString(_) => ("RenderToString", vec![]),
+            Batch(directives) => ("Batch", vec![arg(&directives.len())]),
+            LendScreen(..) => ("LendScreen", vec![]),
+            Shutdown(_) => ("Shutdown", vec![]),
+            QueryCursorPosition => ("QueryCursorPosition", vec![]),
+            GetCapturedDirectives(..) => ("GetCapturedDirectives", vec![])
+        };
+        CapturedDirective { name, args }
+    }
+}
+
+
+/// Directive received by the rendering thread of a headless renderer (see `Renderer::captured_directives`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedDirective {
+    /// name of the directive, eg. `"DrawPoint"`
+    pub name: &'static str,
+    /// arguments formatted with Debug, eg. `"Vec2 { x: 1, y: 2 }"`; images are given as `"Image(8x6)"`
+    pub args: Vec<String>
+}
+
+
+/// How the pixels are packed in the cells of the terminal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// 1x2 pixels per cell, drawn with the half block characters (▀ ▄ █). Each pixel has its own color.
+    #[default]
+    HalfBlock,
+    /// 2x2 pixels per cell, drawn with the quadrant characters (▘ ▚ ▟...). Only two colors can be
+    /// shown in a cell, so the cells with more colors are approximated.
+    Quadrant,
+    /// 2x4 pixels per cell, drawn with the Braille patterns (⠁ ⣿...), for monochrome plots and line art.
+    /// The pixels that are not of the clear color (black by default, see `RendererConfig::clear_color`)
+    /// are lit, with the most frequent of their colors.
+    Braille
+}
+
+
+impl RenderMode {
+
+    /// Returns the number of pixels in a cell of the terminal.
+    pub fn cell_size(&self) -> Vec2 {
+        match self {
+            RenderMode::HalfBlock => vec2!(1, 2),
+            RenderMode::Quadrant => vec2!(2, 2),
+            RenderMode::Braille => vec2!(2, 4)
+        }
+    }
+
+
+    /// Returns the pixel at the top left corner of the cell in column `cell.x` and row `cell.y`, eg. the
+    /// position of a `MouseEvent`.
+    pub fn cell_to_pixel(&self, cell: Vec2) -> Vec2 {
+        let size = self.cell_size();
+        vec2!(cell.x * size.x, cell.y * size.y)
+    }
+
+
+    /// Returns the size in pixels of a screen of `cells` columns and rows.
+    pub fn screen_size(&self, cells: Vec2) -> Vec2 {
+        let cell = self.cell_size();
+        vec2!(cells.x * cell.x, cells.y * cell.y)
+    }
+
+
+    /// Returns the size of the ellipse that looks like a circle of radius `radius` (in horizontal pixels),
+    /// for cells `cell_aspect` times higher than wide. The size is the one given to `Image::ellipse_boundary`.
+    pub fn circle_size(&self, radius: i32, cell_aspect: f32) -> Vec2 {
+        let cell = self.cell_size();
+        // height of a pixel divided by its width
+        let pixel_aspect = cell_aspect * cell.x as f32 / cell.y as f32;
+        vec2!(2 * radius, (2.0 * radius as f32 / pixel_aspect).round() as i32)
+    }
+}
+
+
+/// Height of the cells of the terminal divided by their width, used by default to draw circles (see
+/// `Renderer::draw_circle`). Most terminal fonts have cells about twice as high as wide.
+pub const DEFAULT_CELL_ASPECT: f32 = 2.0;
+
+
+/// Identifier of a layer created with `Renderer::create_layer`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LayerId(usize);
+
+
+/// Drawing commands sent at once to the rendering thread with `Renderer::draw_batch`, to avoid the cost of
+/// sending thousands of commands one by one.
 /// 
 /// ```
-/// // get the renderer
-/// let rdr = Renderer::get();
-/// 
-/// ...
-/// 
-/// // start drawing on a frame
-/// rdr.begin_draw();
-/// 
-/// ... // use drawing functions (eg. draw_rect, draw_point...)
-/// 
-/// rdr.end_draw(); // this pushes the frame to the screen
-/// 
-/// ...
-/// 
-/// Renderer::exit(); // to quit the program and reset terminal settings
+/// # use termkan::vec2;
+/// # use termkan::math::Vec2;
+/// # use termkan::img::Color;
+/// # use termkan::rds::{DrawBatch, Renderer};
+/// # let mut rdr = Renderer::new_headless((40, 20));
+/// # rdr.begin_draw();
+/// let mut batch = DrawBatch::new();
+/// batch.draw_point(vec2!(1, 2), Color::RED);
+/// batch.draw_line(vec2!(0, 0), vec2!(5, 5), Color::BLUE);
+/// rdr.draw_batch(batch);
+/// # rdr.end_draw();
 /// ```
-/// 
-/// Screen coordinates start in the top left at (0, 0)
-pub struct Renderer {
-    termios: Termios,
-    default_c_lflags: u32,
-    default_c_cc: [u8; NCCS],
+#[derive(Default)]
+pub struct DrawBatch {
+    directives: Vec<RenderingDirective>
+}
 
-    building_frame: bool,
-    prev_screen_size: Vec2,
 
-    _server_handle: Option<thread::JoinHandle<()>>,
-    sender: mpsc::Sender<RenderingDirective>,
+impl DrawBatch {
 
-    frame_barrier: Arc<Barrier>
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+
+    /// Returns the number of commands in the batch.
+    pub fn len(&self) -> usize {
+        self.directives.len()
+    }
+
+
+    /// Returns true if the batch has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty()
+    }
+
+
+    /// Same as `Renderer::draw_point`.
+    pub fn draw_point<A>(&mut self, p: A, c: Color)
+        where A: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawPoint(p.into(), c));
+    }
+
+
+    /// Same as `Renderer::draw_line`.
+    pub fn draw_line<A, B>(&mut self, p1: A, p2: B, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawLine(p1.into(), p2.into(), c));
+    }
+
+
+    /// Same as `Renderer::draw_rect`.
+    pub fn draw_rect<A, B>(&mut self, p: A, s: B, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawRect(p.into(), s.into(), c));
+    }
+
+
+    /// Same as `Renderer::draw_rect_boundary`.
+    pub fn draw_rect_boundary<A, B>(&mut self, p: A, s: B, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawRectBoudary(p.into(), s.into(), c));
+    }
+
+
+    /// Same as `Renderer::draw_ellipse`.
+    pub fn draw_ellipse<A, B>(&mut self, c: A, s: B, col: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawEllipse(c.into(), s.into(), col));
+    }
+
+
+    /// Same as `Renderer::draw_ellipse_boundary`.
+    pub fn draw_ellipse_boundary<A, B>(&mut self, c: A, s: B, col: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawEllipseBoudary(c.into(), s.into(), col));
+    }
+
+
+    /// Same as `Renderer::draw_text`.
+    pub fn draw_text<A>(&mut self, p: A, text: &str, c: Color)
+        where A: Into<Vec2>
+    {
+        self.directives.push(RenderingDirective::DrawText(p.into(), text.to_string(), c));
+    }
 }
 
 
-/// Renderer singleton
-static mut RENDERER: Option<Renderer> = None;
+/// Image drawn over the screen buffer, the pixels of the color `colorkey` are transparent.
+struct Layer {
+    image: Image,
+    colorkey: Color
+}
 
 
-impl Renderer {
+/// Priority of the drawing commands (see `Renderer::draw_with_priority`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Priority {
+    /// Skipped when the frame is over its time budget.
+    Low,
+    /// Always drawn.
+    #[default]
+    High
+}
+
+
+/// Shape of the cursor drawn by the renderer (see `Renderer::set_cursor`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CursorStyle {
+    /// All the pixels of the cell.
+    #[default]
+    Block,
+    /// The bottom row of pixels of the cell.
+    Underline,
+    /// The left column of pixels of the cell.
+    Bar
+}
+
+
+/// Error returned when the Renderer cannot be created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RendererError {
+    /// The file descriptor is not a terminal (eg. the output is redirected to a file or a pipe). On Windows,
+    /// 0 stands for stdin and 1 for stdout.
+    NotATerminal(RawFd),
+    /// The settings of the terminal could not be read or changed.
+    TerminalSettings
+}
+
+
+impl fmt::Display for RendererError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RendererError::NotATerminal(fd) => write!(f, "file descriptor {} is not a terminal", fd),
+            RendererError::TerminalSettings => write!(f, "could not change the terminal settings")
+        }
+    }
+}
 
-    /// Creates the Input singleton, will only be called once
-    fn init() -> Renderer {
-        let stdinfd = stdin().as_raw_fd();
 
-        let mut termios = match Termios::from_fd(stdinfd) {
-            Ok(t)  => t,
-            Err(_) => panic!("Could not read stdin fd")
+impl std::error::Error for RendererError {}
+
+
+/// Number of columns and rows used when the size of the terminal is unknown.
+pub const DEFAULT_TERMINAL_SIZE: Vec2 = vec2!(80, 24);
+
+
+/// Returns `size`, or `DEFAULT_TERMINAL_SIZE` if it is empty.
+pub(crate) fn size_or_default(size: Vec2) -> Vec2 {
+    if size.x <= 0 || size.y <= 0 {
+        DEFAULT_TERMINAL_SIZE
+    } else {
+        size
+    }
+}
+
+
+/// Returns the color mode of a terminal with the environment variables `COLORTERM` and `TERM` set to
+/// `colorterm` and `term` (see `Renderer::detect_color_mode`).
+pub(crate) fn color_mode_from(colorterm: &str, term: &str) -> ColorMode {
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        ColorMode::TrueColor
+    } else if term.ends_with("256color") {
+        ColorMode::Ansi256
+    } else {
+        ColorMode::Ansi16
+    }
+}
+
+
+/// Returns an error if `fd` is not a terminal.
+#[cfg(unix)]
+pub(crate) fn check_tty(fd: RawFd) -> Result<(), RendererError> {
+    if unsafe { libc::isatty(fd) } == 1 {
+        Ok(())
+    } else {
+        Err(RendererError::NotATerminal(fd))
+    }
+}
+
+
+/// Settings used to create the Renderer (see `Renderer::get_with`).
+/// 
+/// ```no_run
+/// # use termkan::img::Color;
+/// # use termkan::rds::{Renderer, RendererConfig};
+/// let rdr = Renderer::get_with(RendererConfig::new().clear_color(Color::NAVY_BLUE));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RendererConfig {
+    clear_color: Option<Color>,
+    auto_clear: Option<Color>,
+    frame_budget: Option<Duration>,
+    /// whether the server keeps the directives it receives (see `Renderer::new_headless`)
+    capture_directives: bool,
+    /// color mode of the server, detected from the environment if None
+    color_mode: Option<ColorMode>,
+    /// size of the screen of the server before the first frame, empty if None
+    screen_size: Option<Vec2>
+}
+
+
+impl RendererConfig {
+
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+
+    /// Paints the whole screen with `c` as soon as the renderer starts, instead of showing the previous
+    /// content of the terminal until the first frame.
+    pub fn clear_color(mut self, c: Color) -> Self {
+        self.clear_color = Some(c);
+        self
+    }
+
+
+    /// Clears the screen with `c` at each `begin_draw`, so that nothing drawn during a frame remains
+    /// in the next one.
+    /// 
+    /// The whole screen is repainted every frame, which costs a bit of time on big terminals. When
+    /// every frame redraws the whole screen anyway, clearing it manually only where needed is cheaper.
+    pub fn auto_clear(mut self, c: Color) -> Self {
+        self.auto_clear = Some(c);
+        self
+    }
+
+
+    /// Sets the time budget of a frame (see `Renderer::set_frame_budget`).
+    pub fn frame_budget(mut self, budget: Duration) -> Self {
+        self.frame_budget = Some(budget);
+        self
+    }
+}
+
+
+/// State of the rendering server. It lives in the rendering thread, executes the directives sent
+/// by the Renderer and writes the frames to `out`.
+pub(crate) struct RenderingServer {
+    out: Box<dyn Write + Send>,
+
+    screen_size: Vec2,
+    screen: Image,
+    prev_screen: Image,
+
+    /// characters drawn over the pixels, one per cell of the terminal
+    chars: Vec<Option<(char, Color)>>,
+    prev_chars: Vec<Option<(char, Color)>>,
+
+    back: Color,
+    fore: Color,
+
+    synchronized: bool,
+    color_mode: ColorMode,
+    dithering: bool,
+    render_mode: RenderMode,
+    background: Option<Image>,
+    clear_color: Option<Color>,
+    auto_clear: Option<Color>,
+    last_frame_bytes: usize,
+
+    priority: Priority,
+    frame_budget: Option<Duration>,
+    frame_start: Instant,
+    clock: Box<dyn Fn() -> Instant + Send>,
+
+    pool: BufferPool,
+    frame_buffer: Vec<u8>,
+
+    recording: Option<Recording>,
+
+    /// position and style of the cursor drawn over the frames
+    cursor: Option<(Vec2, CursorStyle)>,
+    cursor_blink: Option<Duration>,
+    /// time at which the cursor was placed, it is shown during the first half of each blink period
+    cursor_since: Instant,
+    /// rectangle covered by the cursor in the last frame pushed
+    cursor_drawn: Option<(Vec2, Vec2)>,
+    /// time after which the reverse video of the visual bell is turned off, at the next frame
+    visual_bell_until: Option<Instant>,
+    /// directives received, if they are captured
+    captured: Option<Vec<CapturedDirective>>,
+
+    /// clip rectangles (position and size), the last one is the intersection of all of them
+    clips: Vec<(Vec2, Vec2)>,
+    camera: Vec2,
+    layers: Vec<Layer>,
+    layer: Option<LayerId>,
+    /// regions that changed during the frame, the whole screen is compared if there are none
+    dirty: Vec<(Vec2, Vec2)>,
+    /// if set, only the cells of the dirty regions are written, without comparing them with the previous frame
+    damage_only: bool,
+    examined_cells: usize,
+    /// rows of cells visited by `encode_frame` and their ranges of columns, kept to avoid allocations
+    spans: Vec<(i32, Range<i32>)>
+}
+
+
+impl RenderingServer {
+
+    /// Creates a server writing its frames to `out`.
+    pub(crate) fn new<W>(out: W, config: &RendererConfig) -> Self
+        where W: Write + Send + 'static
+    {
+        let mut server = Self {
+            out: Box::new(out),
+
+            screen_size: Vec2::ZERO,
+            screen: Image::new(0, 0),
+            prev_screen: Image::new(0, 0),
+
+            chars: Vec::new(),
+            prev_chars: Vec::new(),
+
+            back: config.clear_color.unwrap_or(Color::BLACK),
+            fore: Color::BLACK,
+
+            synchronized: true,
+            color_mode: config.color_mode.unwrap_or_else(Renderer::detect_color_mode),
+            dithering: false,
+            render_mode: RenderMode::HalfBlock,
+            background: None,
+            clear_color: config.clear_color,
+            auto_clear: config.auto_clear,
+            last_frame_bytes: 0,
+
+            priority: Priority::High,
+            frame_budget: config.frame_budget,
+            frame_start: Instant::now(),
+            clock: Box::new(Instant::now),
+
+            pool: BufferPool::new(4),
+            frame_buffer: Vec::new(),
+
+            recording: None,
+
+            cursor: None,
+            cursor_blink: None,
+            cursor_since: Instant::now(),
+            visual_bell_until: None,
+            cursor_drawn: None,
+            captured: config.capture_directives.then(Vec::new),
+
+            clips: Vec::new(),
+            camera: Vec2::ZERO,
+            layers: Vec::new(),
+            layer: None,
+            dirty: Vec::new(),
+            damage_only: false,
+            examined_cells: 0,
+            spans: Vec::new()
         };
+        if let Some(size) = config.screen_size {
+            server.execute(RenderingDirective::UpdateScreenSize(size));
+        }
+        server
+    }
 
-        // save and update settings
-        let default_c_lflags = termios.c_lflag;
-        let default_c_cc = termios.c_cc;
 
-        termios.c_lflag &= !(ECHO | ICANON | ISIG);
-        termios.c_cc[VMIN] = 1;
-        termios.c_cc[VTIME] = 0;
+    /// Writes the initial color state to the output, and clears the screen if a clear color is set.
+    pub(crate) fn start(&mut self) -> io::Result<()> {
+        self.write_colors()?;
+        if self.clear_color.is_some() {
+            write!(self.out, "{}", csi!("2J"))?;
+        }
+        self.out.flush()
+    }
 
-        tcsetattr(stdinfd, TCSANOW, &mut termios).expect("could not set stdin attributes");
-        
-        print!("{}{}", 
-            csi!("?25l"),                                   // hide cursor
-            csi!("?1049h")                                 // use alternate screen buffer
-        );
-        stdout().flush().expect("Could not write to stdout"); 
 
-        // setup and start server
-        let (rx, tx) = mpsc::channel();
-        let barrier = Arc::new(Barrier::new(2));
-        let frame_barrier = Arc::clone(&barrier);
+    /// Writes `s` to the output at once, between two frames.
+    fn write_now(&mut self, s: &str) {
+        self.out.write_all(s.as_bytes()).and_then(|_| self.out.flush()).expect("Could not write to stdout");
+    }
 
-        let handle = thread::spawn(move || {
-            let mut screen_size = Renderer::get_size();
-            let mut screen: Image = Image::new(0, 0);
-            let mut prev_screen: Image = Image::new(0, 0);
 
-            let mut back: Color = Color::BLACK;
-            let mut fore: Color = Color::BLACK;
-            print!("{:-}{:+}", back, fore);
+    /// Sets the colors of the terminal to `fore` and `back`, so that the cells written next can rely on them.
+    fn write_colors(&mut self) -> io::Result<()> {
+        self.color_mode.write_escape(&mut self.out, self.back, true)?;
+        self.color_mode.write_escape(&mut self.out, self.fore, false)?;
+        self.out.flush()
+    }
+
 
+    /// Returns the last frame pushed.
+    #[cfg(test)]
+    pub(crate) fn prev_screen(&self) -> &Image {
+        &self.prev_screen
+    }
 
-            loop {
-                match tx.recv().expect("RenderingServer channel was destroyed") {
-                    RenderingDirective::DrawLine(p1, p2, c) => screen.line(p1, p2, c),
-                    RenderingDirective::DrawRect(p, s, c) => screen.rect(p, s, c),
-                    RenderingDirective::DrawRectBoudary(p, s, c) => screen.rect_boudary(p, s, c),
-                    RenderingDirective::DrawEllipseBoudary(center, s, c) => screen.ellipse_boundary(center, s, c),
-                    RenderingDirective::DrawPoint(p, c) => screen.point(p, c),
 
-                    RenderingDirective::DrawImage(img, pos, size, off, alpha) => screen.image(&(*img.lock().unwrap()), pos, size, off, alpha),
-                    RenderingDirective::DrawWholeImageAlpha(img, pos, alpha) => screen.whole_image_alpha(&(*img.lock().unwrap()), pos, alpha),
-                    RenderingDirective::DrawWholeImage(img, pos) => screen.whole_image(&(*img.lock().unwrap()), pos),
+    /// Returns the number of cells compared with the previous frame by the last `encode_frame`.
+    #[cfg(test)]
+    pub(crate) fn examined_cells(&self) -> usize {
+        self.examined_cells
+    }
 
-                    RenderingDirective::ClearScreen(c) => screen.clear(c),
 
-                    RenderingDirective::UpdateScreenSize(size) => {
-                        screen_size = size;
-                        screen.resize(size.x as usize, size.y as usize);
-                    }
+    /// Replaces the clock used to measure the time spent on a frame.
+    #[cfg(test)]
+    pub(crate) fn set_clock<F>(&mut self, clock: F)
+        where F: Fn() -> Instant + Send + 'static
+    {
+        self.clock = Box::new(clock);
+    }
+
+
+    /// Returns true if the time spent since the beginning of the frame is over the budget.
+    fn over_budget(&self) -> bool {
+        match self.frame_budget {
+            Some(budget) => (self.clock)().saturating_duration_since(self.frame_start) > budget,
+            None => false
+        }
+    }
+
+
+    /// Keeps `directive` if the directives are captured, before it is executed. The directives of a batch
+    /// are kept one by one.
+    pub(crate) fn capture(&mut self, directive: &RenderingDirective) {
+        match (&mut self.captured, directive) {
+            (_, RenderingDirective::GetCapturedDirectives(..)) | (None, _) => (),
+            (Some(_), RenderingDirective::Batch(directives)) => {
+                for directive in directives {
+                    self.capture(directive);
+                }
+            }
+            (Some(captured), directive) => captured.push(directive.record())
+        }
+    }
+
+
+    /// Executes a directive. The rendering thread also waits on the frame barrier after `BeginFrame`.
+    /// 
+    /// Low priority drawing directives are skipped when the frame is over its time budget.
+    pub(crate) fn execute(&mut self, mut directive: RenderingDirective) {
+        if self.priority == Priority::Low && directive.is_draw() && self.over_budget() {
+            return;
+        }
+        if let (true, Some(LayerId(i))) = (directive.is_draw(), self.layer) {
+            // draw on the layer as if it was the screen
+            mem::swap(&mut self.screen, &mut self.layers[i].image);
+            self.layer = None;
+            self.execute(directive);
+            self.layer = Some(LayerId(i));
+            mem::swap(&mut self.screen, &mut self.layers[i].image);
+            return;
+        }
+        if directive.is_draw() {
+            directive.translate(self.camera);
+            self.screen.set_clip(self.clips.last().map(|&clip| Rect::from(clip)));
+        }
 
-                    RenderingDirective::BeginFrame => {frame_barrier.wait(); ()},
-                    RenderingDirective::PushFrame => {
-                        // position cursor
-                        print!("\x1b[H");
-
-                        let mut skiped = false;
-
-                        for j in (0..screen_size.y).step_by(2) {
-                            for i in 0..screen_size.x {
-                                let pos1 = vec2!(i, j);
-                                let pos2 = vec2!(i, j + 1);
-
-                                if screen.size() == prev_screen.size() && screen[pos1] == prev_screen[pos1] && screen[pos2] == prev_screen[pos2] {
-                                    skiped = true;
-                                    continue;
-                                }
-                                
-                                // update color
-                                if screen[pos1] != back && screen[pos1] != fore && screen[pos2] == back {
-                                    fore = screen[pos1];
-                                    print!("{:+}", fore);
-                                } else if screen[pos1] != back && screen[pos1] != fore && screen[pos2] == fore {
-                                    back = screen[pos1];
-                                    print!("{:-}", back);
-                                } else if screen[pos2] != back && screen[pos2] != fore && screen[pos1] == back {
-                                    fore = screen[pos2];
-                                    print!("{:+}", fore);
-                                } else if screen[pos2] != back && screen[pos2] != fore && screen[pos1] == fore {
-                                    back = screen[pos2];
-                                    print!("{:-}", back);
-                                } else if screen[pos1] != back && screen[pos1] != fore && screen[pos2] != back && screen[pos2] != fore {
-                                    fore = screen[pos1];
-                                    back = screen[pos2];
-                                    print!("{:+}", fore);
-                                    print!("{:-}", back);
-                                }
-
-                                if skiped {
-                                    print!("\x1b[{};{}H", j/2 + 1, i + 1);
-                                    skiped = false;
-                                }
-
-                                // print pixel
-                                if screen[pos1] == back && screen[pos2] == back {
-                                    print!(" ");
-                                } else if screen[pos1] == back && screen[pos2] == fore {
-                                    print!("▄");
-                                } else if screen[pos1] == fore && screen[pos2] == back {
-                                    print!("▀");
-                                } else if screen[pos1] == fore && screen[pos2] == fore {
-                                    print!("█");
-                                }
-                            }
+        match directive {
+            RenderingDirective::DrawLine(p1, p2, c) => self.screen.line(p1, p2, c),
+            RenderingDirective::DrawLineGradient(p1, p2, c1, c2) => self.screen.line_gradient(p1, p2, c1, c2),
+            RenderingDirective::DrawLineAa(p1, p2, c) => self.screen.line_aa(p1, p2, c),
+            RenderingDirective::DrawBezierQuad(p0, p1, p2, c) => self.screen.bezier_quad(p0, p1, p2, c),
+            RenderingDirective::DrawBezierCubic(p0, p1, p2, p3, c) => self.screen.bezier_cubic(p0, p1, p2, p3, c),
+            RenderingDirective::DrawRect(p, s, c) => self.screen.rect(p, s, c),
+            RenderingDirective::DrawRectBoudary(p, s, c) => self.screen.rect_boudary(p, s, c),
+            RenderingDirective::DrawRoundedRect(p, s, r, c) => self.screen.rounded_rect(p, s, r, c),
+            RenderingDirective::DrawRoundedRectBoundary(p, s, r, c) => self.screen.rounded_rect_boundary(p, s, r, c),
+            RenderingDirective::DrawEllipseBoudary(center, s, c) => self.screen.ellipse_boundary(center, s, c),
+            RenderingDirective::DrawEllipse(center, s, c) => self.screen.ellipse(center, s, c),
+            RenderingDirective::DrawArc(center, s, start, end, c) => self.screen.arc(center, s, start, end, c),
+            RenderingDirective::DrawPolygon(points, c) => self.screen.polygon(&points, c),
+            RenderingDirective::DrawPolygonBoundary(points, c) => self.screen.polygon_boundary(&points, c),
+            RenderingDirective::DrawPoint(p, c) => self.screen.point(p, c),
+            RenderingDirective::DrawText(p, text, c) => self.screen.draw_text(p, &text, c),
+            RenderingDirective::DrawBoxFrame(p, s, style, c) => self.box_frame(p, s, style, c),
+            RenderingDirective::DrawChar(cell, ch, c) => self.set_char(cell, ch, c),
+
+            RenderingDirective::DrawImage(img, pos, size, off, alpha) => self.screen.image(&img.lock().unwrap(), pos, size, off, alpha),
+            RenderingDirective::DrawWholeImageAlpha(img, pos, alpha) => self.screen.whole_image_alpha(&img.lock().unwrap(), pos, alpha),
+            RenderingDirective::DrawWholeImage(img, pos) => self.screen.whole_image(&img.lock().unwrap(), pos),
+            RenderingDirective::DrawImageRegion(img, rect, dst, colorkey) => self.screen.blit(&img.lock().unwrap(), rect, dst, colorkey),
+
+            RenderingDirective::ClearScreen(c) => {
+                self.screen.clear(c);
+                let grid = self.grid_size();
+                for y in 0..grid.y {
+                    for x in 0..grid.x {
+                        if !self.is_cell_clipped(vec2!(x, y)) {
+                            self.chars[(y * grid.x + x) as usize] = None;
                         }
-                        stdout().flush().expect("Could not write to stdout");
-                        prev_screen = screen.clone();
                     }
                 }
             }
-        });
+            RenderingDirective::GradientLinear(start, end, a, b) => self.screen.gradient_linear(start, end, a, b),
+            RenderingDirective::GradientRadial(center, radius, inner, outer) => self.screen.gradient_radial(center, radius, inner, outer),
+
+            RenderingDirective::UpdateScreenSize(size) => {
+                let first_size = self.screen.size() == Vec2::ZERO;
+                self.screen_size = size;
+                self.screen.resize(size.x as usize, size.y as usize);
+                self.reset_chars();
+                for layer in &mut self.layers {
+                    layer.image.resize(size.x as usize, size.y as usize);
+                    layer.image.clear(layer.colorkey);
+                }
+                if let (true, Some(c)) = (first_size, self.clear_color) {
+                    self.screen.clear(c);
+                }
+            }
+            RenderingDirective::SetSynchronized(enabled) => self.synchronized = enabled,
+            RenderingDirective::SetColorMode(mode) => {
+                if mode != self.color_mode {
+                    self.color_mode = mode;
+                    // the colors set in the terminal and the cells were approximated in the previous mode
+                    self.write_colors().expect("Could not write to stdout");
+                    self.prev_screen = Image::new(0, 0);
+                }
+            }
+            RenderingDirective::SetDithering(enabled) => {
+                if enabled != self.dithering {
+                    self.dithering = enabled;
+                    // the cells do not show the same colors anymore
+                    self.prev_screen = Image::new(0, 0);
+                }
+            }
+            RenderingDirective::SetRenderMode(mode) => {
+                self.render_mode = mode;
+                self.reset_chars();
+                // the cells of the terminal do not show the same pixels anymore
+                self.prev_screen = Image::new(0, 0);
+            }
+            RenderingDirective::SetPriority(priority) => self.priority = priority,
+            RenderingDirective::SetFrameBudget(budget) => self.frame_budget = budget,
+            RenderingDirective::SetBufferPoolSize(size) => self.pool.set_capacity(size),
+            RenderingDirective::StartRecording(file) => {
+                self.recording = Some(Recording { file, start: (self.clock)(), first_frame: true });
+                // the playback starts from a blank terminal, so the first recorded frame draws every cell
+                self.prev_screen = Image::new(0, 0);
+            }
+            RenderingDirective::StopRecording => self.recording = None,
+
+            RenderingDirective::SetBackground(background) => self.background = background,
+            RenderingDirective::PushClip(p, s) => {
+                let clip = match self.clips.last() {
+                    // an empty clip keeps its position, nothing is drawn
+                    Some(&clip) => Rect::from(clip).intersection(Rect::new(p, s)).map_or((p, Vec2::ZERO), |r| r.into()),
+                    None => (p, s)
+                };
+                self.clips.push(clip);
+            }
+            RenderingDirective::PopClip => {
+                self.clips.pop();
+            }
+            RenderingDirective::SetCamera(camera) => self.camera = camera,
+            RenderingDirective::CreateLayer(colorkey) => {
+                let mut image = Image::new(self.screen.size().x as usize, self.screen.size().y as usize);
+                image.clear(colorkey);
+                self.layers.push(Layer { image, colorkey });
+            }
+            RenderingDirective::SetLayer(layer) => self.layer = layer,
+            RenderingDirective::MarkDirty(p, s) => self.dirty.push((p, s)),
+            RenderingDirective::SetDamageOnly(enabled) => self.damage_only = enabled,
+            RenderingDirective::SetCursor(pos, style) => {
+                self.cursor = pos.map(|p| (p, style));
+                self.cursor_since = (self.clock)();
+            }
+            RenderingDirective::SetCursorBlink(period) => {
+                self.cursor_blink = period.filter(|p| !p.is_zero());
+                self.cursor_since = (self.clock)();
+            }
+            RenderingDirective::SetTitle(title) => self.write_now(&format!("\x1b]0;{}\x07", title)),
+            RenderingDirective::RingBell => self.write_now("\x07"),
+            RenderingDirective::QueryCursorPosition => self.write_now(csi!("6n")),
+            RenderingDirective::VisualBell => {
+                // reverse video for a short time, turned off by a later frame
+                self.write_now(csi!("?5h"));
+                self.visual_bell_until = Some((self.clock)() + VISUAL_BELL_DURATION);
+            }
+
+            RenderingDirective::BeginFrame => {
+                self.frame_start = (self.clock)();
+                self.end_visual_bell(false);
+                if let Some(background) = &self.background {
+                    self.screen.whole_image(background, Vec2::ZERO);
+                } else if let Some(c) = self.auto_clear {
+                    self.screen.clear(c);
+                    self.chars.fill(None);
+                }
+            }
+            RenderingDirective::PushFrame => {
+                self.end_visual_bell(false);
+                let screen = self.compose_layers();
+                let covered = self.draw_cursor();
+                let bytes = self.push_frame();
+                for (p, c) in covered {
+                    self.screen[p] = c;
+                }
+                self.restore_screen(screen);
+                self.last_frame_bytes = bytes.expect("Could not write to stdout");
+            }
+            RenderingDirective::GetFrameBytes(response) => {
+                let _ = response.send(self.last_frame_bytes);
+            }
+            RenderingDirective::CaptureFrame(response) => {
+                let screen = self.compose_layers();
+                let _ = response.send(self.screen.clone());
+                self.restore_screen(screen);
+            }
+            RenderingDirective::RenderToString(response) => {
+                // the frame is not pushed, the colors of the terminal do not change
+                let colors = (self.fore, self.back);
+                let screen = self.compose_layers();
+                self.encode_frame().expect("Could not write to a Vec");
+                self.restore_screen(screen);
+                (self.fore, self.back) = colors;
+                let _ = response.send(String::from_utf8_lossy(&self.frame_buffer).into_owned());
+            }
+            RenderingDirective::Batch(directives) => {
+                for directive in directives {
+                    self.execute(directive);
+                }
+            }
+            RenderingDirective::LendScreen(lend, back) => {
+                // the renderer draws on the screen buffer until it sends it back
+                let size = self.screen.size();
+                let screen = mem::replace(&mut self.screen, Image::new(0, 0));
+                let screen = match lend.send(screen) {
+                    Ok(()) => back.recv().ok(),
+                    Err(_) => None
+                };
+                self.screen = screen.unwrap_or_else(|| Image::new(size.x as usize, size.y as usize));
+            }
+            RenderingDirective::Shutdown(response) => {
+                self.end_visual_bell(true);
+                self.write_now(RESTORE_SEQUENCE);
+                let _ = response.send(());
+            }
+            RenderingDirective::GetCapturedDirectives(response, take) => {
+                let directives = match &mut self.captured {
+                    Some(captured) if take => mem::take(captured),
+                    Some(captured) => captured.clone(),
+                    None => Vec::new()
+                };
+                let _ = response.send(directives);
+            }
+        }
+        self.screen.set_clip(None);
+    }
+
+
+    /// Turns off the reverse video of the visual bell if its duration has passed, or right away if `now` is set.
+    fn end_visual_bell(&mut self, now: bool) {
+        if let Some(until) = self.visual_bell_until {
+            if now || (self.clock)() >= until {
+                self.write_now(csi!("?5l"));
+                self.visual_bell_until = None;
+            }
+        }
+    }
+
+
+    /// Draws a frame of box-drawing characters around the cells from `p` to `p + s - 1`.
+    fn box_frame(&mut self, p: Vec2, s: Vec2, style: BoxStyle, c: Color) {
+        if s.x < 2 || s.y < 2 {
+            return;
+        }
+        let chars = style.chars();
+        let end = p + s - vec2!(1, 1);
+        for x in p.x + 1..end.x {
+            self.set_char(vec2!(x, p.y), chars.horizontal, c);
+            self.set_char(vec2!(x, end.y), chars.horizontal, c);
+        }
+        for y in p.y + 1..end.y {
+            self.set_char(vec2!(p.x, y), chars.vertical, c);
+            self.set_char(vec2!(end.x, y), chars.vertical, c);
+        }
+        self.set_char(p, chars.top_left, c);
+        self.set_char(vec2!(end.x, p.y), chars.top_right, c);
+        self.set_char(vec2!(p.x, end.y), chars.bottom_left, c);
+        self.set_char(end, chars.bottom_right, c);
+    }
+
+
+    /// Replaces the screen buffer by the composition of the screen and the layers, in the order of their
+    /// creation. Returns the screen buffer, to be given back to `restore_screen`.
+    fn compose_layers(&mut self) -> Option<Image> {
+        if self.layers.is_empty() {
+            return None;
+        }
+        let mut composed = self.pool.take(self.screen.size());
+        composed.copy_from(&self.screen);
+        for layer in &self.layers {
+            composed.whole_image_alpha(&layer.image, Vec2::ZERO, layer.colorkey);
+        }
+        mem::swap(&mut self.screen, &mut composed);
+        Some(composed)
+    }
+
+
+    /// Returns the rectangle of pixels covered by the cursor if it is shown at this time.
+    fn cursor_rect(&self) -> Option<(Vec2, Vec2)> {
+        let (pos, style) = self.cursor?;
+        if let Some(period) = self.cursor_blink {
+            let elapsed = (self.clock)().saturating_duration_since(self.cursor_since);
+            if (elapsed.as_nanos() * 2 / period.as_nanos()) % 2 == 1 {
+                return None;
+            }
+        }
+
+        let cell = self.render_mode.cell_size();
+        let origin = vec2!(pos.x - pos.x.rem_euclid(cell.x), pos.y - pos.y.rem_euclid(cell.y));
+        Some(match style {
+            CursorStyle::Block => (origin, cell),
+            CursorStyle::Underline => (vec2!(origin.x, origin.y + cell.y - 1), vec2!(cell.x, 1)),
+            CursorStyle::Bar => (origin, vec2!(1, cell.y))
+        })
+    }
+
+
+    /// Draws the cursor on the screen buffer by inverting the colors of its pixels, and returns the
+    /// pixels it covered with their colors so that they can be put back once the frame is pushed.
+    fn draw_cursor(&mut self) -> Vec<(Vec2, Color)> {
+        let rect = self.cursor_rect();
+        if !self.dirty.is_empty() || self.damage_only {
+            // the cells of the cursor change even if nothing is drawn there
+            self.dirty.extend(self.cursor_drawn.iter().chain(rect.iter()).copied());
+        }
+        self.cursor_drawn = rect;
+
+        let mut covered = Vec::new();
+        if let Some((p, s)) = rect {
+            for y in p.y..p.y + s.y {
+                for x in p.x..p.x + s.x {
+                    if let Some(c) = self.screen.get_pixel(vec2!(x, y)) {
+                        covered.push((vec2!(x, y), c));
+                        self.screen[vec2!(x, y)] = Color::rgb(255 - c.r, 255 - c.g, 255 - c.b);
+                    }
+                }
+            }
+        }
+        covered
+    }
+
+
+    /// Puts back the screen buffer returned by `compose_layers`.
+    fn restore_screen(&mut self, screen: Option<Image>) {
+        if let Some(mut screen) = screen {
+            mem::swap(&mut self.screen, &mut screen);
+            self.pool.recycle(screen);
+        }
+    }
+
+
+    /// Shows the character `ch` of color `c` in the cell `cell` of the terminal.
+    fn set_char(&mut self, cell: Vec2, ch: char, c: Color) {
+        let grid = self.grid_size();
+        if cell.x < 0 || cell.y < 0 || cell.x >= grid.x || cell.y >= grid.y || self.is_cell_clipped(cell) {
+            return;
+        }
+        self.chars[(cell.y * grid.x + cell.x) as usize] = Some((ch, c));
+    }
+
+
+    /// Returns true if the character of `cell` cannot be drawn because the top left pixel of the cell
+    /// is outside of the clip rectangle.
+    fn is_cell_clipped(&self, cell: Vec2) -> bool {
+        let size = self.render_mode.cell_size();
+        self.clips.last().is_some_and(|&clip| !Rect::from(clip).contains(vec2!(cell.x * size.x, cell.y * size.y)))
+    }
+
+
+    /// Returns the number of columns and rows of cells of the terminal.
+    fn grid_size(&self) -> Vec2 {
+        let cell = self.render_mode.cell_size();
+        vec2!((self.screen_size.x + cell.x - 1) / cell.x, (self.screen_size.y + cell.y - 1) / cell.y)
+    }
+
+
+    /// Resizes the grid of characters to the size of the screen, and clears it.
+    fn reset_chars(&mut self) {
+        let grid = self.grid_size();
+        self.chars.clear();
+        self.chars.resize((grid.x * grid.y).max(0) as usize, None);
+    }
+
+
+    /// Writes the difference between the current and the previous frame to the output.
+    /// Returns the number of bytes written.
+    fn push_frame(&mut self) -> io::Result<usize> {
+        // colors of the terminal the frame relies on
+        let colors = (self.fore, self.back);
+        self.encode_frame()?;
+
+        let out = &self.frame_buffer;
+        self.out.write_all(out)?;
+        self.out.flush()?;
+        if let Some(recording) = &mut self.recording {
+            let time = (self.clock)().saturating_duration_since(recording.start);
+            // a recording that cannot be written is dropped, the frames are still shown
+            if recording.write_frame(time, out, colors, self.color_mode).is_err() {
+                self.recording = None;
+            }
+        }
+        self.prev_screen.copy_from(&self.screen);
+        self.prev_chars.clone_from(&self.chars);
+        self.dirty.clear();
+        Ok(self.frame_buffer.len())
+    }
+
+
+    /// Writes the escape sequences drawing the difference between the current and the previous frame
+    /// to `frame_buffer`. If regions were marked as dirty, only their cells are compared, or written without
+    /// comparing them in damage only mode.
+    fn encode_frame(&mut self) -> io::Result<()> {
+        // colors that are sent to the terminal
+        let dithered = if self.dithering && self.color_mode != ColorMode::TrueColor {
+            let mut dithered = self.pool.take(self.screen.size());
+            self.screen.dither_into(self.color_mode, &mut dithered);
+            Some(dithered)
+        } else {
+            None
+        };
+        let cell_size = self.render_mode.cell_size();
+        let grid = self.grid_size();
+        let background = self.clear_color.unwrap_or(Color::BLACK);
+        let screen = &self.screen;
+        let prev_screen = &self.prev_screen;
+        let colors = dithered.as_ref().unwrap_or(screen);
+
+        let out = &mut self.frame_buffer;
+        out.clear();
+
+        if self.synchronized {
+            write!(out, "{}", csi!("?2026h"))?;
+        }
+
+        // position cursor
+        write!(out, "\x1b[H")?;
+
+        // cell where the terminal cursor is, None if unknown
+        let mut cursor = Some(Vec2::ZERO);
+
+        // the dirty regions are ignored when the whole screen has to be drawn
+        let dirty = if screen.size() == prev_screen.size() { &self.dirty[..] } else { &[] };
+        let damage_only = self.damage_only && screen.size() == prev_screen.size();
+        self.examined_cells = 0;
+
+        let px = |img: &Image, p: Vec2| img.get_pixel(p).unwrap_or(Color::BLACK);
+        // colors compared with the ones set in the terminal: the colors shown the same way in the color mode
+        // are equal, so that a run of them is written with a single color sequence
+        let mode = self.color_mode;
+        let shown = |p: Vec2| mode.nearest(px(colors, p));
+
+        // in damage only mode, only the cells of the dirty regions are visited
+        let mut spans = mem::take(&mut self.spans);
+        if damage_only {
+            dirty_spans(dirty, cell_size, grid, &mut spans);
+        } else {
+            spans.clear();
+            spans.extend((0..grid.y).map(|row| (row, 0..grid.x)));
+        }
+
+        for (row, cols) in spans.iter().cloned() {
+            // the rows of cells that did not change are skipped without comparing their cells, the cursor
+            // is moved at the next cell written
+            let pixels = row * cell_size.y..(row + 1) * cell_size.y;
+            let cells = (row * grid.x) as usize..((row + 1) * grid.x) as usize;
+            if dirty.is_empty() && !damage_only && screen.size() == prev_screen.size()
+                && screen.rows(pixels.clone()) == prev_screen.rows(pixels)
+                && self.chars.get(cells.clone()) == self.prev_chars.get(cells) {
+                continue;
+            }
+
+            for col in cols {
+                let origin = vec2!(col * cell_size.x, row * cell_size.y);
+                let cell = (row * grid.x + col) as usize;
+                let ch = self.chars.get(cell).copied().flatten();
+
+                let is_dirty = |&(p, s): &(Vec2, Vec2)| {
+                    origin.x < p.x + s.x && p.x < origin.x + cell_size.x
+                        && origin.y < p.y + s.y && p.y < origin.y + cell_size.y
+                };
+                if !damage_only && !dirty.is_empty() && !dirty.iter().any(is_dirty) {
+                    continue;
+                }
+                self.examined_cells += 1;
+
+                let unchanged = !damage_only && screen.size() == prev_screen.size()
+                    && (0..cell_size.y).all(|j| (0..cell_size.x).all(|i| {
+                        px(screen, origin + vec2!(i, j)) == px(prev_screen, origin + vec2!(i, j))
+                    }));
+                if unchanged && self.prev_chars.get(cell) == Some(&ch) {
+                    continue;
+                }
+
+                // each run of changed cells starts with a cursor move, the last column of a row is
+                // never relied on to wrap to the next row
+                if cursor != Some(vec2!(col, row)) {
+                    write!(out, "\x1b[{};{}H", row + 1, col + 1)?;
+                }
+                cursor = if col + 1 < grid.x { Some(vec2!(col + 1, row)) } else { None };
+
+                let (fore, back) = (&mut self.fore, &mut self.back);
+                match (ch, self.render_mode) {
+                    (Some((ch, c)), _) => write_char_cell(out, ch, mode.nearest(c), shown(origin), fore, back, mode)?,
+                    (None, RenderMode::HalfBlock) => {
+                        write_cell(out, shown(origin), shown(origin + Vec2::UNIY), fore, back, mode)?
+                    }
+                    (None, RenderMode::Quadrant) => {
+                        let quadrant = [
+                            shown(origin), shown(origin + Vec2::UNIX),
+                            shown(origin + Vec2::UNIY), shown(origin + vec2!(1, 1))
+                        ];
+                        write_quadrant_cell(out, quadrant, fore, back, mode)?
+                    }
+                    (None, RenderMode::Braille) => {
+                        let mut dots = [Color::BLACK; 8];
+                        for (k, dot) in dots.iter_mut().enumerate() {
+                            *dot = shown(origin + vec2!(k as i32 % 2, k as i32 / 2));
+                        }
+                        write_braille_cell(out, dots, mode.nearest(background), fore, back, mode)?
+                    }
+                }
+            }
+        }
+
+        if self.synchronized {
+            write!(out, "{}", csi!("?2026l"))?;
+        }
+        self.spans = spans;
+        if let Some(dithered) = dithered {
+            self.pool.recycle(dithered);
+        }
+        Ok(())
+    }
+}
+
+
+/// Terminal to reset if the program panics, registered with `restore_on_panic`.
+struct PanicRestore {
+    id: usize,
+    out: Box<dyn Write + Send>,
+    terminal: Option<PlatformTerminal>,
+    shut_down: Arc<AtomicBool>
+}
+
+
+/// Terminals reset by the panic hook, and the identifier of the next one.
+static PANIC_RESTORES: Mutex<(usize, Vec<PanicRestore>)> = Mutex::new((0, Vec::new()));
+
+
+/// Installs the panic hook of `restore_on_panic`.
+static PANIC_HOOK: Once = Once::new();
+
+
+/// Registers a terminal to reset if the program panics, before the previous panic hook is called so that
+/// the panic message is readable: `RESTORE_SEQUENCE` is written to `out` and the settings of `terminal`
+/// are restored. Returns the identifier to give to `forget_on_panic` once the terminal is reset.
+/// 
+/// The panic hook is installed by the first call, and chains to the hook installed before it. Nothing is
+/// done for a terminal whose `shut_down` is already set, it is set by the hook.
+pub(crate) fn restore_on_panic<W>(out: W, terminal: Option<PlatformTerminal>, shut_down: Arc<AtomicBool>) -> usize
+    where W: Write + Send + 'static
+{
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let mut restores = PANIC_RESTORES.lock().unwrap_or_else(PoisonError::into_inner);
+            for restore in restores.1.iter_mut() {
+                if !restore.shut_down.swap(true, Ordering::SeqCst) {
+                    let _ = restore.out.write_all(RESTORE_SEQUENCE.as_bytes()).and_then(|_| restore.out.flush());
+                    if let Some(terminal) = &restore.terminal {
+                        terminal.restore();
+                    }
+                }
+            }
+            drop(restores);
+            previous(info);
+        }));
+    });
+
+    let mut restores = PANIC_RESTORES.lock().unwrap_or_else(PoisonError::into_inner);
+    let id = restores.0;
+    restores.0 += 1;
+    restores.1.push(PanicRestore { id, out: Box::new(out), terminal, shut_down });
+    id
+}
+
+
+/// Stops resetting the terminal registered with `restore_on_panic` under `id` if the program panics.
+pub(crate) fn forget_on_panic(id: usize) {
+    PANIC_RESTORES.lock().unwrap_or_else(PoisonError::into_inner).1.retain(|restore| restore.id != id);
+}
+
+
+/// Quadrant characters, indexed by the mask of the sub-pixels drawn with the foreground color
+/// (1: top left, 2: top right, 4: bottom left, 8: bottom right).
+const QUADRANT_CHARS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█'
+];
+
+
+/// Writes the quadrant glyph showing the 2x2 sub-pixels `pixels` (top left, top right, bottom left,
+/// bottom right). `fore` and `back` are the colors currently set in the terminal.
+/// 
+/// A cell can only show two colors: the two most different colors of the cell are chosen, and each
+/// sub-pixel is drawn with the nearest of them.
+fn write_quadrant_cell<W>(out: &mut W, pixels: [Color; 4], fore: &mut Color, back: &mut Color, mode: ColorMode) -> io::Result<()>
+    where W: Write
+{
+    let mut pair = (pixels[0], pixels[0]);
+    let mut max_distance = 0;
+    for (i, &a) in pixels.iter().enumerate() {
+        for &b in &pixels[i + 1..] {
+            if a.distance2(b) > max_distance {
+                max_distance = a.distance2(b);
+                pair = (a, b);
+            }
+        }
+    }
+    if pair.0 == pair.1 {
+        return write_cell(out, pair.0, pair.0, fore, back, mode);
+    }
+
+    // use the current colors of the terminal if possible
+    let reused = |f: Color, b: Color| (f == *fore) as u8 + (b == *back) as u8;
+    let (new_fore, new_back) = if reused(pair.1, pair.0) > reused(pair.0, pair.1) {
+        (pair.1, pair.0)
+    } else {
+        pair
+    };
+    let mask = pixels.iter().enumerate()
+        .filter(|(_, c)| c.distance2(new_fore) < c.distance2(new_back))
+        .fold(0, |mask, (i, _)| mask | 1 << i);
+
+    if new_fore != *fore {
+        *fore = new_fore;
+        mode.write_escape(out, new_fore, false)?;
+    }
+    if new_back != *back {
+        *back = new_back;
+        mode.write_escape(out, new_back, true)?;
+    }
+    write!(out, "{}", QUADRANT_CHARS[mask])
+}
+
+
+/// Bits of the Braille dots, for the pixels of a 2x4 cell from left to right and top to bottom.
+const BRAILLE_DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+
+/// Writes the Braille pattern showing the 2x4 pixels `dots` (from left to right and top to bottom), the pixels
+/// that are not of color `background` being lit. `fore` and `back` are the colors currently set in the terminal.
+/// 
+/// All the dots of a cell have the same color: the most frequent color of the lit pixels.
+fn write_braille_cell<W>(out: &mut W, dots: [Color; 8], background: Color, fore: &mut Color, back: &mut Color, mode: ColorMode) -> io::Result<()>
+    where W: Write
+{
+    let lit = dots.iter().filter(|&&c| c != background);
+    let color = lit.clone().max_by_key(|&&c| dots.iter().filter(|&&d| d == c).count());
+    let color = match color {
+        Some(&c) => c,
+        None => return write_cell(out, background, background, fore, back, mode)
+    };
+    let pattern = dots.iter().zip(BRAILLE_DOTS)
+        .filter(|(&c, _)| c != background)
+        .fold(0, |pattern, (_, bit)| pattern | bit);
+
+    write_char_cell(out, char::from_u32(0x2800 + pattern).unwrap(), color, background, fore, back, mode)
+}
+
+
+/// Writes the character `ch` of color `c` over the background `back_color`. `fore` and `back` are the colors
+/// currently set in the terminal.
+fn write_char_cell<W>(out: &mut W, ch: char, c: Color, back_color: Color, fore: &mut Color, back: &mut Color, mode: ColorMode) -> io::Result<()>
+    where W: Write
+{
+    if c != *fore {
+        *fore = c;
+        mode.write_escape(out, c, false)?;
+    }
+    if back_color != *back {
+        *back = back_color;
+        mode.write_escape(out, back_color, true)?;
+    }
+    write!(out, "{}", ch)
+}
+
+
+/// File in which the frames are recorded (see `Renderer::start_recording`).
+/// 
+/// Each frame is stored as a header line `frame <milliseconds since the start> <length>`, followed by the
+/// bytes of the frame and a new line.
+pub(crate) struct Recording {
+    file: File,
+    start: Instant,
+    /// set until the first frame is recorded
+    first_frame: bool
+}
+
+
+impl Recording {
+
+    /// Appends a frame written `time` after the start of the recording. `colors` are the foreground and
+    /// background colors of the terminal the frame relies on: they are set at the start of the first frame,
+    /// so that it can be played back on a terminal that does not have them.
+    fn write_frame(&mut self, time: Duration, bytes: &[u8], colors: (Color, Color), mode: ColorMode) -> io::Result<()> {
+        let mut prefix = Vec::new();
+        if self.first_frame {
+            mode.write_escape(&mut prefix, colors.1, true)?;
+            mode.write_escape(&mut prefix, colors.0, false)?;
+        }
+        writeln!(self.file, "frame {} {}", time.as_millis(), prefix.len() + bytes.len())?;
+        self.file.write_all(&prefix)?;
+        self.file.write_all(bytes)?;
+        writeln!(self.file)?;
+        self.first_frame = false;
+        Ok(())
+    }
+}
+
+
+/// Writes the frames of a recording to `out`, waiting between them so that they are shown at
+/// the recorded cadence.
+pub(crate) fn replay<R, W>(recording: R, out: &mut W) -> io::Result<()>
+    where R: Read, W: Write
+{
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid recording: {}", msg));
+
+    let mut recording = BufReader::new(recording);
+    let start = Instant::now();
+    let mut header = String::new();
+    let mut frame = Vec::new();
+    loop {
+        header.clear();
+        if recording.read_line(&mut header)? == 0 {
+            return Ok(());
+        }
+        let mut fields = header.trim_end().split(' ');
+        let (time, len) = match (fields.next(), fields.next(), fields.next()) {
+            (Some("frame"), Some(time), Some(len)) => (
+                time.parse::<u64>().map_err(|_| invalid("bad frame time"))?,
+                len.parse::<usize>().map_err(|_| invalid("bad frame length"))?
+            ),
+            _ => return Err(invalid("expected a frame header"))
+        };
+
+        frame.resize(len + 1, 0);
+        recording.read_exact(&mut frame)?;
+
+        let time = Duration::from_millis(time);
+        thread::sleep(time.saturating_sub(start.elapsed()));
+        out.write_all(&frame[..len])?;
+        out.flush()?;
+    }
+}
+
+
+/// Keeps track of the duration of the frames, and waits at the end of the frames to hold the target frame rate.
+pub(crate) struct FrameLimiter {
+    pub(crate) target_fps: u32,
+    frame_start: Instant,
+    last_end: Option<Instant>,
+    last_frame_time: Duration
+}
+
+
+impl FrameLimiter {
+
+    /// Creates a limiter without target frame rate.
+    pub(crate) fn new() -> Self {
+        Self {
+            target_fps: 0,
+            frame_start: Instant::now(),
+            last_end: None,
+            last_frame_time: Duration::ZERO
+        }
+    }
+
+
+    /// Called when a frame starts.
+    pub(crate) fn begin_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+
+    /// Called when a frame ends, waits until the end of the frame duration if there is a target frame rate.
+    pub(crate) fn end_frame(&mut self) {
+        if self.target_fps > 0 {
+            let deadline = self.last_end.unwrap_or(self.frame_start) + Duration::from_secs(1) / self.target_fps;
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        let now = Instant::now();
+        if let Some(last_end) = self.last_end {
+            self.last_frame_time = now - last_end;
+        }
+        self.last_end = Some(now);
+    }
+
+
+    /// Returns the frame rate computed from the duration of the last frame, 0 before the second frame.
+    pub(crate) fn current_fps(&self) -> f32 {
+        if self.last_frame_time.is_zero() {
+            0.0
+        } else {
+            1.0 / self.last_frame_time.as_secs_f32()
+        }
+    }
+}
+
+
+/// Small pool of images, used to recycle the buffers needed while rendering a frame instead of
+/// allocating new ones every frame.
+pub(crate) struct BufferPool {
+    buffers: Vec<Image>,
+    capacity: usize
+}
+
+
+impl BufferPool {
+
+    /// Creates a pool keeping at most `capacity` unused images.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            buffers: Vec::with_capacity(capacity),
+            capacity
+        }
+    }
+
+
+    /// Returns an image of size `size`, recycled if possible. Its content is unspecified.
+    pub(crate) fn take(&mut self, size: Vec2) -> Image {
+        match self.buffers.iter().position(|img| img.size() == size) {
+            Some(i) => self.buffers.swap_remove(i),
+            None => {
+                let mut img = self.buffers.pop().unwrap_or_else(|| Image::new(0, 0));
+                img.resize(size.x as usize, size.y as usize);
+                img
+            }
+        }
+    }
+
+
+    /// Gives back an image that is not used anymore. It is dropped if the pool is full.
+    pub(crate) fn recycle(&mut self, img: Image) {
+        if self.buffers.len() < self.capacity {
+            self.buffers.push(img);
+        }
+    }
+
+
+    /// Changes the maximum number of unused images kept by the pool.
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.buffers.truncate(capacity);
+        self.buffers.reserve(capacity - self.buffers.len());
+    }
+}
+
+
+/// Puts in `spans` the cells covered by the regions `dirty` (in pixels) of a grid of `grid` cells of `cell_size`
+/// pixels, as ranges of columns of each row, sorted and without overlaps.
+fn dirty_spans(dirty: &[(Vec2, Vec2)], cell_size: Vec2, grid: Vec2, spans: &mut Vec<(i32, Range<i32>)>) {
+    spans.clear();
+    for &(p, s) in dirty {
+        if s.x <= 0 || s.y <= 0 {
+            continue;
+        }
+        let start = vec2!(p.x.div_euclid(cell_size.x).max(0), p.y.div_euclid(cell_size.y).max(0));
+        let end = vec2!(
+            ((p.x + s.x - 1).div_euclid(cell_size.x) + 1).min(grid.x),
+            ((p.y + s.y - 1).div_euclid(cell_size.y) + 1).min(grid.y)
+        );
+        if start.x < end.x {
+            spans.extend((start.y..end.y).map(|row| (row, start.x..end.x)));
+        }
+    }
+    spans.sort_unstable_by_key(|(row, cols)| (*row, cols.start));
+
+    // merge the overlapping ranges in place
+    let mut len = 0;
+    for i in 0..spans.len() {
+        let (row, cols) = spans[i].clone();
+        match spans[..len].last_mut() {
+            Some((last_row, last)) if *last_row == row && cols.start <= last.end => last.end = last.end.max(cols.end),
+            _ => {
+                spans[len] = (row, cols);
+                len += 1;
+            }
+        }
+    }
+    spans.truncate(len);
+}
+
+
+/// Writes the half-block glyph showing `top` over `bottom`. `fore` and `back` are the colors currently
+/// set in the terminal: a color sequence is only emitted when the cell cannot be drawn with them.
+fn write_cell<W>(out: &mut W, top: Color, bottom: Color, fore: &mut Color, back: &mut Color, mode: ColorMode) -> io::Result<()>
+    where W: Write
+{
+    if top == bottom {
+        if top == *fore {
+            return write!(out, "█");
+        }
+        if top != *back {
+            *back = top;
+            mode.write_escape(out, top, true)?;
+        }
+        return write!(out, " ");
+    }
+
+    // find the colors for the upper half block, reusing the current ones if possible
+    let (new_fore, new_back) = if top == *back || bottom == *fore {
+        (bottom, top)
+    } else {
+        (top, bottom)
+    };
+    if new_fore != *fore {
+        *fore = new_fore;
+        mode.write_escape(out, new_fore, false)?;
+    }
+    if new_back != *back {
+        *back = new_back;
+        mode.write_escape(out, new_back, true)?;
+    }
+
+    if *fore == top {
+        write!(out, "▀")
+    } else {
+        write!(out, "▄")
+    }
+}
+
+
+/// This is the core of the library. It will send commands to the rendering server to print on screen.
+/// 
+/// # Usage
+/// 
+/// ```
+/// // get the renderer
+/// let rdr = Renderer::get();
+/// 
+/// ...
+/// 
+/// // start drawing on a frame
+/// rdr.begin_draw();
+/// 
+/// ... // use drawing functions (eg. draw_rect, draw_point...)
+/// 
+/// rdr.end_draw(); // this pushes the frame to the screen
+/// 
+/// ...
+/// 
+/// Renderer::exit(); // to quit the program and reset terminal settings
+/// ```
+/// 
+/// Screen coordinates start in the top left at (0, 0)
+/// 
+/// `Renderer::new` creates a renderer that is not the singleton, for programs that prefer owning it.
+pub struct Renderer {
+    /// resets the terminal when the renderer shuts down
+    reset: TerminalReset,
+    /// set for the renderer singleton, whose render mode is also kept in `SINGLETON_RENDER_MODE`
+    singleton: bool,
+
+    building_frame: bool,
+    prev_screen_size: Vec2,
+    /// size of the screen of a headless renderer, the size of the terminal is used if None
+    fixed_size: Option<Vec2>,
+    priority: Priority,
+    render_mode: RenderMode,
+    cell_aspect: f32,
+    camera: Vec2,
+    layer_count: usize,
+    layer: Option<LayerId>,
+    limiter: FrameLimiter,
+
+    _server_handle: Option<thread::JoinHandle<()>>,
+    sender: mpsc::Sender<RenderingDirective>,
+
+    frame_barrier: Arc<Barrier>
+}
+
+
+/// Resets the terminal a renderer draws on. It can be used without the renderer, so that `Renderer::exit`
+/// works while the singleton is locked.
+#[derive(Clone)]
+struct TerminalReset {
+    sender: mpsc::Sender<RenderingDirective>,
+    /// settings of the terminal used by the renderer, None if it does not draw on the terminal
+    terminal: Option<PlatformTerminal>,
+    /// set once the terminal is reset, by `reset` or by the panic hook
+    shut_down: Arc<AtomicBool>,
+    /// identifier of the terminal in the panic hook (see `restore_on_panic`)
+    panic_restore: Option<usize>
+}
+
+
+impl TerminalReset {
+
+    /// Writes `RESTORE_SEQUENCE` with the rendering thread and restores the settings of the terminal.
+    /// Calling it more than once does nothing.
+    fn reset(&self) {
+        if let Some(id) = self.panic_restore {
+            forget_on_panic(id);
+        }
+        if self.shut_down.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let (response, done) = mpsc::channel();
+        if self.sender.send(RenderingDirective::Shutdown(response)).is_ok() {
+            let _ = done.recv();
+        }
+
+        if let Some(terminal) = &self.terminal {
+            terminal.restore();
+        }
+    }
+}
+
+
+/// Platform-specific control of the terminal the renderer draws on.
+pub(crate) trait TerminalBackend: Sized {
+
+    /// Saves the settings of the terminal and puts it in raw mode: no echo, no line buffering and no
+    /// signal on Ctrl+C. Fails if stdin or stdout is not a terminal.
+    fn set_raw_mode() -> Result<Self, RendererError>;
+
+    /// Saves the settings of the terminal and enables its line editing and echo, without the end of file
+    /// key (see `Input::read_line`). Fails if stdin is not a terminal.
+    fn set_line_mode() -> Result<Self, RendererError>;
+
+    /// Returns the number of columns and rows of the terminal, zero if it is unknown.
+    fn get_size() -> Vec2;
+
+    /// Restores the settings saved by `set_raw_mode`.
+    fn restore(&self);
+}
+
+
+/// Terminal backend of the current platform.
+#[cfg(unix)]
+pub(crate) type PlatformTerminal = UnixTerminal;
+
+
+/// Terminal backend of the current platform.
+#[cfg(windows)]
+pub(crate) type PlatformTerminal = WindowsTerminal;
+
+
+/// Settings of a Unix terminal, changed with termios.
+#[cfg(unix)]
+#[derive(Clone, Copy)]
+pub(crate) struct UnixTerminal {
+    /// settings of the terminal before the renderer took control of it
+    default: Termios
+}
+
+
+#[cfg(unix)]
+impl TerminalBackend for UnixTerminal {
+
+    fn set_raw_mode() -> Result<Self, RendererError> {
+        let stdinfd = stdin().as_raw_fd();
+        check_tty(stdinfd)?;
+        check_tty(stdout().as_raw_fd())?;
+
+        let default = Termios::from_fd(stdinfd).map_err(|_| RendererError::TerminalSettings)?;
+
+        let mut termios = default;
+        termios.c_lflag &= !(ECHO | ICANON | ISIG);
+        termios.c_cc[VMIN] = 1;
+        termios.c_cc[VTIME] = 0;
+
+        tcsetattr(stdinfd, TCSANOW, &termios).map_err(|_| RendererError::TerminalSettings)?;
+        Ok(UnixTerminal { default })
+    }
+
+
+    fn set_line_mode() -> Result<Self, RendererError> {
+        let stdinfd = stdin().as_raw_fd();
+        let default = Termios::from_fd(stdinfd).map_err(|_| RendererError::TerminalSettings)?;
+
+        let mut termios = default;
+        termios.c_lflag |= ICANON | ECHO;
+        termios.c_cc[VEOF] = 0;
+
+        tcsetattr(stdinfd, TCSANOW, &termios).map_err(|_| RendererError::TerminalSettings)?;
+        Ok(UnixTerminal { default })
+    }
+
+
+    fn get_size() -> Vec2 {
+        unsafe {
+            let mut size: TermSize = mem::zeroed();
+            match libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size as *mut _) {
+                0 => vec2!(size.col as i32, size.row as i32),
+                _ => Vec2::ZERO
+            }
+        }
+    }
+
+
+    fn restore(&self) {
+        let _ = tcsetattr(stdin().as_raw_fd(), TCSANOW, &self.default);
+    }
+}
+
+
+/// Modes of a Windows console, changed with the console API.
+#[cfg(windows)]
+#[derive(Clone, Copy)]
+pub(crate) struct WindowsTerminal {
+    /// modes of the console before the renderer took control of it
+    input: u32,
+    output: u32
+}
+
+
+#[cfg(windows)]
+impl TerminalBackend for WindowsTerminal {
+
+    fn set_raw_mode() -> Result<Self, RendererError> {
+        if !stdin().is_terminal() {
+            return Err(RendererError::NotATerminal(0));
+        }
+        if !stdout().is_terminal() {
+            return Err(RendererError::NotATerminal(1));
+        }
+
+        let (input, output) = (console_mode(STD_INPUT_HANDLE)?, console_mode(STD_OUTPUT_HANDLE)?);
+        // the keys are read as the escape sequences of a Unix terminal, parsed by the Input
+        let raw = input & !(ENABLE_PROCESSED_INPUT | ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT) | ENABLE_VIRTUAL_TERMINAL_INPUT;
+        set_console_mode(STD_INPUT_HANDLE, raw)?;
+        set_console_mode(STD_OUTPUT_HANDLE, output | ENABLE_VIRTUAL_TERMINAL_PROCESSING)?;
+        Ok(WindowsTerminal { input, output })
+    }
+
+
+    fn set_line_mode() -> Result<Self, RendererError> {
+        let (input, output) = (console_mode(STD_INPUT_HANDLE)?, console_mode(STD_OUTPUT_HANDLE)?);
+        set_console_mode(STD_INPUT_HANDLE, input | ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT)?;
+        Ok(WindowsTerminal { input, output })
+    }
+
+
+    fn get_size() -> Vec2 {
+        unsafe {
+            let mut info: ConsoleScreenBufferInfo = mem::zeroed();
+            match GetConsoleScreenBufferInfo(GetStdHandle(STD_OUTPUT_HANDLE), &mut info) {
+                0 => Vec2::ZERO,
+                _ => vec2!(
+                    (info.window.right - info.window.left + 1) as i32,
+                    (info.window.bottom - info.window.top + 1) as i32
+                )
+            }
+        }
+    }
+
+
+    fn restore(&self) {
+        let _ = set_console_mode(STD_INPUT_HANDLE, self.input);
+        let _ = set_console_mode(STD_OUTPUT_HANDLE, self.output);
+    }
+}
+
+
+/// Sequences written when the terminal is reset: show the cursor, use the main screen buffer and disable
+/// the mouse, focus and bracketed paste events.
+const RESTORE_SEQUENCE: &str = concat!(
+    csi!("?25h"),
+    csi!("?1049l"),
+    csi!("?1006l"), csi!("?1015l"), csi!("?1003l"), csi!("?1002l"), csi!("?1000l"),
+    csi!("?1004l"),
+    csi!("?2004l")
+);
+
+
+/// Duration of the flash of `Renderer::visual_bell`.
+pub const VISUAL_BELL_DURATION: Duration = Duration::from_millis(100);
+
+
+/// Time `Renderer::query_cursor_position` waits for the reply of the terminal.
+pub const CURSOR_QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+
+/// Renderer singleton.
+static RENDERER: Mutex<Option<Renderer>> = Mutex::new(None);
+
+
+/// Resets the terminal of the renderer singleton, kept apart so that `Renderer::exit` does not lock the renderer.
+static SINGLETON_RESET: Mutex<Option<TerminalReset>> = Mutex::new(None);
+
+
+/// Render mode of the renderer singleton, read by `Renderer::get_size` without locking the renderer.
+static SINGLETON_RENDER_MODE: Mutex<RenderMode> = Mutex::new(RenderMode::HalfBlock);
+
+
+thread_local! {
+    /// True while the current thread holds a `RendererGuard`.
+    static RENDERER_BORROWED: Cell<bool> = const { Cell::new(false) };
+}
+
+
+/// Locks the singleton `mutex`, waiting for the other threads to release it. `borrowed` is true while the
+/// current thread holds it.
+/// 
+/// Panics if the current thread already holds it, as waiting would never end.
+pub(crate) fn lock_singleton<T>(mutex: &'static Mutex<T>, borrowed: &'static LocalKey<Cell<bool>>, name: &str)
+    -> MutexGuard<'static, T>
+{
+    let guard = match mutex.try_lock() {
+        Ok(guard) => guard,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            if borrowed.with(Cell::get) {
+                panic!("{} already borrowed on this thread: drop the previous guard before calling get again", name);
+            }
+            mutex.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    };
+    borrowed.with(|b| b.set(true));
+    guard
+}
+
+
+/// Exclusive access to the renderer singleton, returned by `Renderer::get`. The singleton stays locked until
+/// the guard is dropped: calling `Renderer::get` again on the same thread before that panics.
+pub struct RendererGuard(MutexGuard<'static, Option<Renderer>>);
+
+
+impl Drop for RendererGuard {
+
+    fn drop(&mut self) {
+        RENDERER_BORROWED.with(|b| b.set(false));
+    }
+}
+
+
+impl Deref for RendererGuard {
+
+    type Target = Renderer;
+
+    fn deref(&self) -> &Renderer {
+        self.0.as_ref().expect("Renderer was just created")
+    }
+}
+
+
+impl DerefMut for RendererGuard {
+
+    fn deref_mut(&mut self) -> &mut Renderer {
+        self.0.as_mut().expect("Renderer was just created")
+    }
+}
+
+
+impl Renderer {
+
+    /// Creates a renderer drawing on the terminal, independent from the singleton (see `get`).
+    /// 
+    /// Panics if stdin or stdout is not a terminal (see `try_new`). There is no `Default` implementation,
+    /// as creating a renderer changes the settings of the terminal.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Renderer {
+        Renderer::new_with(RendererConfig::default())
+    }
+
+
+    /// Same as `new` but uses `config`.
+    pub fn new_with(config: RendererConfig) -> Renderer {
+        Renderer::try_new_with(config).unwrap_or_else(|e| panic!("Could not create the renderer: {}", e))
+    }
+
+
+    /// Same as `new` but returns an error instead of panicking if the renderer could not be created.
+    pub fn try_new() -> Result<Renderer, RendererError> {
+        Renderer::try_new_with(RendererConfig::default())
+    }
+
+
+    /// Same as `new_with` but returns an error instead of panicking if the renderer could not be created.
+    /// 
+    /// Fails without writing anything if stdin or stdout is not a terminal.
+    pub fn try_new_with(config: RendererConfig) -> Result<Renderer, RendererError> {
+        let terminal = PlatformTerminal::set_raw_mode()?;
+
+        print!("{}{}", 
+            csi!("?25l"),                                   // hide cursor
+            csi!("?1049h")                                 // use alternate screen buffer
+        );
+        stdout().flush().expect("Could not write to stdout"); 
+
+        Ok(Renderer::start(stdout(), config, Some(terminal)))
+    }
+
+
+    /// Creates a renderer writing its frames to `out` instead of the terminal, eg. to test the output
+    /// or to send it elsewhere. The terminal settings are left untouched.
+    /// 
+    /// ```
+    /// # use termkan::rds::{Renderer, RendererConfig};
+    /// let mut rdr = Renderer::with_output(Vec::new(), RendererConfig::new());
+    /// ```
+    pub fn with_output<W>(out: W, config: RendererConfig) -> Renderer
+        where W: Write + Send + 'static
+    {
+        Renderer::start(out, config, None)
+    }
+
+
+    /// Creates a renderer with a screen of `size` pixels that is never shown: nothing is written and the
+    /// terminal settings are left untouched, so that it can be used without a terminal, eg. in tests.
+    /// 
+    /// The screen buffer can be inspected with `pixel_at` or `capture_frame`, and the commands received
+    /// by the rendering thread with `captured_directives`. The screen has its size from the start, and the
+    /// colors are in `ColorMode::TrueColor` whatever the environment.
+    pub fn new_headless<A>(size: A) -> Renderer
+        where A: Into<Vec2>
+    {
+        let size = size.into();
+        let config = RendererConfig {
+            capture_directives: true,
+            color_mode: Some(ColorMode::TrueColor),
+            screen_size: Some(size),
+            ..RendererConfig::default()
+        };
+        let mut renderer = Renderer::start(io::sink(), config, None);
+        renderer.fixed_size = Some(size);
+        renderer.prev_screen_size = size;
+        renderer
+    }
+
+
+    /// Starts the rendering thread, writing the frames to `out`.
+    fn start<W>(out: W, config: RendererConfig, terminal: Option<PlatformTerminal>) -> Renderer
+        where W: Write + Send + 'static
+    {
+        let (rx, tx) = mpsc::channel();
+        let barrier = Arc::new(Barrier::new(2));
+        let frame_barrier = Arc::clone(&barrier);
+
+        let shut_down = Arc::new(AtomicBool::new(false));
+        let panic_restore = terminal.map(|_| restore_on_panic(stdout(), terminal, Arc::clone(&shut_down)));
+
+        let handle = thread::spawn(move || {
+            let mut server = RenderingServer::new(out, &config);
+            server.start().expect("Could not write to stdout");
+
+            // the loop ends when the renderer is dropped
+            while let Ok(directive) = tx.recv() {
+                server.capture(&directive);
+                match directive {
+                    RenderingDirective::BeginFrame => {
+                        server.execute(RenderingDirective::BeginFrame);
+                        frame_barrier.wait();
+                    }
+                    directive => server.execute(directive)
+                }
+            }
+        });
+
+        Renderer {
+            reset: TerminalReset { sender: rx.clone(), terminal, shut_down, panic_restore },
+            singleton: false,
+
+            building_frame: false,
+            prev_screen_size: Vec2::ZERO,
+            fixed_size: None,
+            priority: Priority::High,
+            render_mode: RenderMode::HalfBlock,
+            cell_aspect: DEFAULT_CELL_ASPECT,
+            camera: Vec2::ZERO,
+            layer_count: 0,
+            layer: None,
+            limiter: FrameLimiter::new(),
+
+            _server_handle: Some(handle),
+            sender: rx,
+
+            frame_barrier: barrier
+        }
+    }
+
+
+    /// Exits the program and reset terminal setttings (should be called before the program ends).
+    /// 
+    /// The renderer singleton does not need to be unlocked: the terminal is reset without it.
+    pub fn exit() {
+        let reset = SINGLETON_RESET.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(reset) = reset {
+            reset.reset();
+        }
+        std::process::exit(0);
+    }
+
+
+    /// Resets the terminal: restores its settings, shows the cursor, leaves the alternate screen buffer
+    /// and disables the mouse. The program keeps running, eg. to print a summary once the interface is
+    /// closed. Nothing should be drawn with the renderer afterward.
+    /// 
+    /// It is called when the renderer is droped, calling it more than once does nothing. The terminal is also
+    /// reset if the program panics, before the panic message is printed.
+    pub fn shutdown(&mut self) {
+        self.reset.reset();
+    }
+
+
+    /// Returns the Renderer instance, locked until the returned guard is dropped.
+    /// 
+    /// Panics if stdin or stdout is not a terminal (see `try_get`), or if the current thread already holds
+    /// the guard.
+    pub fn get() -> RendererGuard {
+        Renderer::get_with(RendererConfig::default())
+    }
+
+
+    /// Returns the Renderer instance, creating it with `config` if it does not exist yet.
+    /// 
+    /// `config` is ignored if the renderer was already created.
+    /// Panics if stdin or stdout is not a terminal (see `try_get_with`).
+    pub fn get_with(config: RendererConfig) -> RendererGuard {
+        Renderer::try_get_with(config).unwrap_or_else(|e| panic!("Could not create the renderer: {}", e))
+    }
+
+
+    /// Same as `get` but returns an error instead of panicking if the renderer could not be created.
+    pub fn try_get() -> Result<RendererGuard, RendererError> {
+        Renderer::try_get_with(RendererConfig::default())
+    }
+
+
+    /// Same as `get_with` but returns an error instead of panicking if the renderer could not be created.
+    pub fn try_get_with(config: RendererConfig) -> Result<RendererGuard, RendererError> {
+        let mut renderer = lock_singleton(&RENDERER, &RENDERER_BORROWED, "Renderer");
+        if renderer.is_none() {
+            let mut created = Renderer::try_new_with(config)?;
+            created.singleton = true;
+            *SINGLETON_RESET.lock().unwrap_or_else(PoisonError::into_inner) = Some(created.reset.clone());
+            *renderer = Some(created);
+        }
+        Ok(RendererGuard(renderer))
+    }
+
+
+    /// Enables or disables synchronized output. When enabled, each frame is wrapped in the
+    /// synchronized update sequences (`CSI ? 2026 h` and `CSI ? 2026 l`) so that the terminal displays
+    /// it at once, preventing tearing.
+    /// 
+    /// Enabled by default: the terminals that do not support it ignore these sequences, like any
+    /// unknown private mode.
+    pub fn set_synchronized_output(&mut self, enabled: bool) {
+        self.sender.send(RenderingDirective::SetSynchronized(enabled)).expect("Rendering thread stoped");
+    }
+
+
+    /// Alias of `set_synchronized_output`, kept so that `set_synchronized(true)` forces synchronized
+    /// output as before.
+    pub fn set_synchronized(&mut self, enabled: bool) {
+        self.set_synchronized_output(enabled);
+    }
+
+
+    /// Returns the color mode supported by the terminal, based on the environment variables:
+    /// 
+    /// 1. `COLORTERM` set to `truecolor` or `24bit` gives `ColorMode::TrueColor`
+    /// 2. otherwise `TERM` ending with `256color` (eg. `xterm-256color`) gives `ColorMode::Ansi256`
+    /// 3. otherwise `ColorMode::Ansi16`
+    /// 
+    /// This is the color mode used by default, it can be overridden with `set_color_mode`.
+    pub fn detect_color_mode() -> ColorMode {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        color_mode_from(&colorterm, &term)
+    }
+
+
+    /// Sets the escape sequences used to output colors (see `detect_color_mode` for the default).
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.sender.send(RenderingDirective::SetColorMode(mode)).expect("Rendering thread stoped");
+    }
+
+
+    /// Enables or disables ordered dithering of the colors that are not available in the
+    /// 256 and 16 color modes (see `Image::dither`). Disabled by default.
+    pub fn set_dithering(&mut self, enabled: bool) {
+        self.sender.send(RenderingDirective::SetDithering(enabled)).expect("Rendering thread stoped");
+    }
+
+
+    /// Sets the time budget of a frame, measured from `begin_draw`. Once a frame is over its budget,
+    /// the drawing commands sent with a low priority are skipped (see `draw_with_priority`).
+    /// 
+    /// No budget is set by default.
+    pub fn set_frame_budget(&mut self, budget: Option<Duration>) {
+        self.sender.send(RenderingDirective::SetFrameBudget(budget)).expect("Rendering thread stoped");
+    }
+
+
+    /// Sets the maximum number of unused off-screen buffers kept by the rendering thread to be reused
+    /// by the next frames. Defaults to 4.
+    pub fn set_buffer_pool_size(&mut self, size: usize) {
+        self.sender.send(RenderingDirective::SetBufferPoolSize(size)).expect("Rendering thread stoped");
+    }
+
+
+    /// Starts appending the bytes of each frame written to the terminal to the file at `path`, with the
+    /// time at which they were written. The recording can be played back with `replay_ansi`.
+    /// 
+    /// The next frame is written whole, with the colors it relies on, so that the recording does not depend
+    /// on what was shown before. The recording stops if the file cannot be written.
+    pub fn start_recording<P>(&mut self, path: P) -> io::Result<()>
+        where P: AsRef<Path>
+    {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.sender.send(RenderingDirective::StartRecording(file)).expect("Rendering thread stoped");
+        Ok(())
+    }
+
+
+    /// Stops the recording started with `start_recording`.
+    pub fn stop_recording(&mut self) {
+        self.sender.send(RenderingDirective::StopRecording).expect("Rendering thread stoped");
+    }
+
+
+    /// Plays back a recording made with `start_recording` on the terminal, at the recorded cadence.
+    /// 
+    /// The renderer should not be drawing frames at the same time.
+    pub fn replay_ansi<P>(path: P) -> io::Result<()>
+        where P: AsRef<Path>
+    {
+        replay(File::open(path)?, &mut stdout())
+    }
+
+
+    /// Sets an image that is drawn at the top left corner of the screen at the beginning of each frame,
+    /// so that a static backdrop does not have to be drawn every frame.
+    pub fn set_background(&mut self, img: Image) {
+        self.sender.send(RenderingDirective::SetBackground(Some(img))).expect("Rendering thread stoped");
+    }
+
+
+    /// Removes the image set with `set_background`.
+    pub fn clear_background(&mut self) {
+        self.sender.send(RenderingDirective::SetBackground(None)).expect("Rendering thread stoped");
+    }
+
+
+    /// Restricts the drawing to the rectangle of position `p` and size `s`, until the matching `pop_clip`.
+    /// Nested clip rectangles intersect: drawing is restricted to all of them.
+    /// 
+    /// ```
+    /// # use termkan::vec2;
+    /// # use termkan::math::Vec2;
+    /// # use termkan::img::Color;
+    /// # use termkan::rds::Renderer;
+    /// # let mut rdr = Renderer::new_headless((40, 20));
+    /// # rdr.begin_draw();
+    /// rdr.push_clip(vec2!(10, 10), vec2!(20, 8));
+    /// rdr.clear_screen(Color::NAVY_BLUE); // only clears the panel
+    /// rdr.pop_clip();
+    /// # rdr.end_draw();
+    /// ```
+    pub fn push_clip<A, B>(&mut self, p: A, s: B)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.sender.send(RenderingDirective::PushClip(p.into(), s.into())).expect("Rendering thread stoped");
+    }
+
+
+    /// Removes the last clip rectangle set with `push_clip`.
+    pub fn pop_clip(&mut self) {
+        self.sender.send(RenderingDirective::PopClip).expect("Rendering thread stoped");
+    }
+
+
+    /// Tells the renderer that only the rectangle of position `p` and size `s` (in screen coordinates) changed
+    /// during this frame. When regions are marked, the other cells are not compared with the previous frame
+    /// and keep their content on the terminal, which makes pushing the frame faster on large terminals.
+    /// 
+    /// If nothing is marked, the whole screen is compared.
+    pub fn mark_dirty<A, B>(&mut self, p: A, s: B)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.sender.send(RenderingDirective::MarkDirty(p.into(), s.into())).expect("Rendering thread stoped");
+    }
+
+
+    /// In damage only mode, the frames are not compared with the previous ones: only the cells of the regions
+    /// marked with `mark_dirty` are written, and nothing is written if no region is marked. Pushing a frame
+    /// then costs nothing on the rest of the screen, but what is drawn outside of the marked regions is
+    /// not shown until they cover it, so every change must be marked.
+    /// 
+    /// The whole screen is still written when its size changes. Disabled by default.
+    pub fn set_damage_only(&mut self, enabled: bool) {
+        self.sender.send(RenderingDirective::SetDamageOnly(enabled)).expect("Rendering thread stoped");
+    }
+
+
+    /// Shows a cursor over the cell of the screen containing the pixel `pos`, or hides it if `pos` is None.
+    /// The cursor inverts the colors of the pixels it covers, it is drawn when the frames are pushed and does
+    /// not change the screen buffer. The camera does not apply to it.
+    /// 
+    /// The terminal cursor stays hidden, this one can be used to show where the text is typed.
+    pub fn set_cursor(&mut self, pos: Option<Vec2>, style: CursorStyle) {
+        self.sender.send(RenderingDirective::SetCursor(pos, style)).expect("Rendering thread stoped");
+    }
+
+
+    /// Makes the cursor blink: it is shown during the first half of each `period`, starting when it is
+    /// placed. It does not blink if `period` is None.
+    /// 
+    /// The cursor is only updated when a frame is pushed, so frames must be pushed often enough for it
+    /// to blink regularly.
+    pub fn set_cursor_blink(&mut self, period: Option<Duration>) {
+        self.sender.send(RenderingDirective::SetCursorBlink(period)).expect("Rendering thread stoped");
+    }
+
+
+    /// Creates a layer drawn over the screen and the layers created before it. The pixels of color
+    /// `colorkey` are transparent, the layer starts filled with it.
+    /// 
+    /// Layers keep their content between frames until drawn over (see `draw_on_layer`), they are cleared
+    /// when the size of the screen changes.
+    pub fn create_layer(&mut self, colorkey: Color) -> LayerId {
+        self.sender.send(RenderingDirective::CreateLayer(colorkey)).expect("Rendering thread stoped");
+        self.layer_count += 1;
+        LayerId(self.layer_count - 1)
+    }
+
+
+    /// Calls `f`, the drawing commands it sends draw on `layer` instead of the screen.
+    /// 
+    /// ```
+    /// # use termkan::vec2;
+    /// # use termkan::math::Vec2;
+    /// # use termkan::img::Color;
+    /// # use termkan::rds::Renderer;
+    /// # let mut rdr = Renderer::new_headless((40, 20));
+    /// # rdr.begin_draw();
+    /// let overlay = rdr.create_layer(Color::BLACK);
+    /// rdr.draw_on_layer(overlay, |rdr| {
+    ///     rdr.clear_screen(Color::BLACK); // clears the layer, showing what is under it
+    ///     rdr.draw_text(vec2!(1, 1), "score: 10", Color::WHITE);
+    /// });
+    /// # rdr.end_draw();
+    /// ```
+    pub fn draw_on_layer<F>(&mut self, layer: LayerId, f: F)
+        where F: FnOnce(&mut Renderer)
+    {
+        let prev_layer = self.layer;
+        self.layer = Some(layer);
+        self.sender.send(RenderingDirective::SetLayer(Some(layer))).expect("Rendering thread stoped");
+
+        f(self);
+
+        self.layer = prev_layer;
+        self.sender.send(RenderingDirective::SetLayer(prev_layer)).expect("Rendering thread stoped");
+    }
+
+
+    /// Calls `f`, the drawing commands it sends have the priority `priority`.
+    /// 
+    /// ```
+    /// # use termkan::vec2;
+    /// # use termkan::math::Vec2;
+    /// # use termkan::img::Color;
+    /// # use termkan::rds::Renderer;
+    /// # let mut rdr = Renderer::new_headless((40, 20));
+    /// # use termkan::rds::Priority;
+    /// # rdr.begin_draw();
+    /// rdr.draw_with_priority(Priority::Low, |rdr| {
+    ///     rdr.draw_rect(vec2!(0, 0), vec2!(10, 10), Color::GRAY); // skipped if the frame is too long
+    /// });
+    /// # rdr.end_draw();
+    /// ```
+    pub fn draw_with_priority<F>(&mut self, priority: Priority, f: F)
+        where F: FnOnce(&mut Renderer)
+    {
+        let prev_priority = self.priority;
+        self.priority = priority;
+        self.sender.send(RenderingDirective::SetPriority(priority)).expect("Rendering thread stoped");
+
+        f(self);
+
+        self.priority = prev_priority;
+        self.sender.send(RenderingDirective::SetPriority(prev_priority)).expect("Rendering thread stoped");
+    }
+
+
+    /// Returns the screen dimension of the renderer singleton, in pixels. It depends on the render mode
+    /// (see `set_render_mode`).
+    /// ```
+    /// let size = Renderer::get_size();
+    /// 
+    /// size.x // width of the screen
+    /// size.y // height of the screen
+    /// ```
+    /// 
+    /// The other renderers can have another render mode or size, use `screen_size` for them.
+    pub fn get_size() -> Vec2 {
+        let mode = *SINGLETON_RENDER_MODE.lock().unwrap_or_else(PoisonError::into_inner);
+        mode.screen_size(Renderer::get_terminal_size())
+    }
+
+
+    /// Returns the dimension of the screen of this renderer, in pixels: the size given to `new_headless`,
+    /// or the size of the terminal in the render mode of the renderer.
+    pub fn screen_size(&self) -> Vec2 {
+        self.fixed_size.unwrap_or_else(|| self.render_mode.screen_size(Renderer::get_terminal_size()))
+    }
 
-        Renderer {
-            termios: termios,
-            default_c_lflags: default_c_lflags,
-            default_c_cc: default_c_cc,
 
-            building_frame: false,
-            prev_screen_size: Vec2::ZERO,
+    /// Returns the pixel at the top left corner of the cell `cell` in the render mode of this renderer,
+    /// eg. to draw at the position of a `MouseEvent`.
+    pub fn cell_to_pixel<A>(&self, cell: A) -> Vec2
+        where A: Into<Vec2>
+    {
+        self.render_mode.cell_to_pixel(cell.into())
+    }
 
-            _server_handle: Some(handle),
-            sender: rx,
 
-            frame_barrier: barrier
-        }
+    /// Returns the number of columns and rows of the terminal.
+    /// 
+    /// When the size is unknown (eg. stdout is not a terminal), `DEFAULT_TERMINAL_SIZE` is returned instead
+    /// of an empty size on which nothing could be drawn.
+    pub fn get_terminal_size() -> Vec2 {
+        size_or_default(PlatformTerminal::get_size())
     }
 
 
-    /// Exits the program and reset terminal setttings (should be called before the program ends).
-    pub fn exit() {
-        unsafe {
-            RENDERER = None;
+    /// Sets how the pixels are packed in the cells of the terminal. The size of the screen (see `get_size`)
+    /// changes accordingly from the next frame.
+    /// 
+    /// Defaults to `RenderMode::HalfBlock`.
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.render_mode = mode;
+        if self.singleton {
+            *SINGLETON_RENDER_MODE.lock().unwrap_or_else(PoisonError::into_inner) = mode;
         }
+        self.sender.send(RenderingDirective::SetRenderMode(mode)).expect("Rendering thread stoped");
     }
 
 
-    /// Returns the Renderer instance.
-    pub fn get() -> &'static mut Renderer {
-        unsafe {
-            match &mut RENDERER {
-                None => { // construct the renderer, and initialize
-                    RENDERER = Some(Renderer::init());
-                    Renderer::get()
-                }
-                Some(r) => r
-            }
-        }
+    /// Sets the height of the cells of the terminal divided by their width, used to draw circles that look
+    /// round (see `draw_circle`).
+    /// 
+    /// Defaults to `DEFAULT_CELL_ASPECT`.
+    pub fn set_cell_aspect(&mut self, aspect: f32) {
+        self.cell_aspect = aspect;
     }
 
 
-    /// Returns the screen dimension.
-    /// ```
-    /// let size = Renderer::get_size();
+    /// Sets the position of the camera: `offset` is subtracted from the coordinates of everything drawn
+    /// afterwards, so the world point `offset` is drawn at the top left of the screen.
     /// 
-    /// size.x // width of the screen
-    /// size.y // height of the screen
-    /// ```
-    pub fn get_size() -> Vec2 {
-        unsafe {
-            let mut size: TermSize = mem::zeroed();
-            libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size as *mut _);
-            vec2!(size.col as i32, 2 * size.row as i32)
-        }
+    /// Clip rectangles and the functions using cell coordinates (eg. `draw_box_frame`) are not moved.
+    pub fn set_camera<A>(&mut self, offset: A)
+        where A: Into<Vec2>
+    {
+        self.camera = offset.into();
+        self.sender.send(RenderingDirective::SetCamera(self.camera)).expect("Rendering thread stoped");
+    }
+
+
+    /// Returns the position of the camera (see `set_camera`).
+    pub fn camera(&self) -> Vec2 {
+        self.camera
+    }
+
+
+    /// Converts a position on the screen (eg. of a mouse event) to world coordinates.
+    pub fn screen_to_world<A>(&self, p: A) -> Vec2
+        where A: Into<Vec2>
+    {
+        p.into() + self.camera
+    }
+
+
+    /// Converts world coordinates to a position on the screen.
+    pub fn world_to_screen<A>(&self, p: A) -> Vec2
+        where A: Into<Vec2>
+    {
+        p.into() - self.camera
     }
 
     
@@ -298,23 +2568,40 @@ impl Renderer {
 
     /// Starts drawing a frame.
     /// 
+    /// If the terminal was resized, the screen buffer is resized before this function returns, so the
+    /// drawing commands of the frame always apply to a buffer of the new size.
+    /// 
     /// Will panic if called twice before an end_draw
     pub fn begin_draw(&mut self) {
+        self.begin_draw_sized(self.screen_size());
+    }
+
+
+    /// Starts drawing a frame on a screen of size `new_size`.
+    fn begin_draw_sized(&mut self, new_size: Vec2) {
         if self.building_frame {
             panic!("begin_draw called when already building a frame");
         }
         self.building_frame = true;
-        let new_size = Renderer::get_size();
         if self.prev_screen_size != new_size {
             self.sender.send(RenderingDirective::UpdateScreenSize(new_size)).expect("Rendering thread stoped");
             self.prev_screen_size = new_size;
         }
 
+        self.limiter.begin_frame();
+        // the resize was sent before, it is done once the server reaches the barrier
         self.sender.send(RenderingDirective::BeginFrame).expect("Rendering thread stoped");
         self.frame_barrier.wait();
     }
 
 
+    /// Same as `begin_draw` with a terminal of size `size`.
+    #[cfg(test)]
+    pub(crate) fn begin_draw_with_size(&mut self, size: Vec2) {
+        self.begin_draw_sized(size);
+    }
+
+
     /// Ends drawing a frame and pushes it to the screen.
     pub fn end_draw(&mut self) {
         if !self.building_frame {
@@ -322,6 +2609,127 @@ impl Renderer {
         }
         self.building_frame = false;
         self.sender.send(RenderingDirective::PushFrame).expect("Rendering thread stoped");
+        self.limiter.end_frame();
+    }
+
+
+    /// Draws a frame directly on the screen buffer with the methods of `Image`, instead of sending each drawing
+    /// command to the rendering thread. The frame is pushed once `f` returns.
+    /// 
+    /// The rendering thread waits while `f` runs. The camera, the clip rectangles and the layers do not apply
+    /// to what `f` draws. Will panic if called while building a frame.
+    /// 
+    /// ```
+    /// # use termkan::vec2;
+    /// # use termkan::math::Vec2;
+    /// # use termkan::img::Color;
+    /// # use termkan::rds::Renderer;
+    /// # let mut rdr = Renderer::new_headless((40, 20));
+    /// # let particles = vec![(vec2!(1, 2), Color::RED)];
+    /// rdr.with_frame(|screen| {
+    ///     screen.clear(Color::BLACK);
+    ///     for &(p, c) in &particles {
+    ///         screen.set_pixel(p, c);
+    ///     }
+    /// });
+    /// ```
+    pub fn with_frame<F>(&mut self, f: F)
+        where F: FnOnce(&mut Image)
+    {
+        self.begin_draw();
+        let (lend, lent) = mpsc::channel();
+        let (back, returned) = mpsc::channel();
+        self.sender.send(RenderingDirective::LendScreen(lend, returned)).expect("Rendering thread stoped");
+
+        let mut screen = lent.recv().expect("Rendering thread stoped");
+        f(&mut screen);
+        back.send(screen).expect("Rendering thread stoped");
+        self.end_draw();
+    }
+
+
+    /// Limits the frame rate to `fps` frames per second: `end_draw` waits for the remainder of the frame
+    /// duration. 0 means unlimited, which is the default.
+    pub fn set_target_fps(&mut self, fps: u32) {
+        self.limiter.target_fps = fps;
+    }
+
+
+    /// Returns the time between the ends of the last two frames.
+    pub fn last_frame_time(&self) -> Duration {
+        self.limiter.last_frame_time
+    }
+
+
+    /// Returns the frame rate, computed from `last_frame_time`.
+    pub fn current_fps(&self) -> f32 {
+        self.limiter.current_fps()
+    }
+
+
+    /// Same as `end_draw` but waits for the frame to be written to the terminal and returns
+    /// the number of bytes that were written.
+    pub fn present(&mut self) -> usize {
+        self.end_draw();
+
+        let (response, bytes) = mpsc::channel();
+        self.sender.send(RenderingDirective::GetFrameBytes(response)).expect("Rendering thread stoped");
+        bytes.recv().expect("Rendering thread stoped")
+    }
+
+
+    /// Returns a copy of the screen buffer, with everything drawn so far.
+    /// 
+    /// Waits for the rendering server to execute all the previous drawing commands.
+    pub fn capture_frame(&self) -> Image {
+        let (response, frame) = mpsc::channel();
+        self.sender.send(RenderingDirective::CaptureFrame(response)).expect("Rendering thread stoped");
+        frame.recv().expect("Rendering thread stoped")
+    }
+
+
+    /// Returns the color of the pixel `p` of the screen buffer, None if it is outside of the screen.
+    /// 
+    /// Waits for the rendering server to execute all the previous drawing commands.
+    pub fn pixel_at<A>(&self, p: A) -> Option<Color>
+        where A: Into<Vec2>
+    {
+        self.capture_frame().get_pixel(p.into())
+    }
+
+
+    /// Returns the commands received by the rendering thread of a headless renderer (see `new_headless`).
+    /// The positions are the ones given to the renderer, before the camera is applied, and the commands
+    /// of a batch are listed one by one.
+    /// 
+    /// Returns an empty list for the other renderers.
+    pub fn captured_directives(&self) -> Vec<CapturedDirective> {
+        self.get_captured_directives(false)
+    }
+
+
+    /// Same as `captured_directives`, but the commands are removed from the renderer, so that the next
+    /// call only returns the commands received after this one.
+    pub fn take_captured_directives(&self) -> Vec<CapturedDirective> {
+        self.get_captured_directives(true)
+    }
+
+
+    fn get_captured_directives(&self, take: bool) -> Vec<CapturedDirective> {
+        let (response, directives) = mpsc::channel();
+        self.sender.send(RenderingDirective::GetCapturedDirectives(response, take)).expect("Rendering thread stoped");
+        directives.recv().expect("Rendering thread stoped")
+    }
+
+
+    /// Returns the escape sequences that `end_draw` would write for the frame drawn so far, without
+    /// writing them.
+    /// 
+    /// Like the frames written to the output, it only contains the cells that changed since the last frame.
+    pub fn render_to_string(&self) -> String {
+        let (response, frame) = mpsc::channel();
+        self.sender.send(RenderingDirective::RenderToString(response)).expect("Rendering thread stoped");
+        frame.recv().expect("Rendering thread stoped")
     }
 
 
@@ -332,12 +2740,73 @@ impl Renderer {
     }
 
 
+    /// Fills the whole screen with a gradient going from `a` at `start` to `b` at `end` (see `Image::gradient_linear`).
+    pub fn clear_gradient_linear<A, B>(&mut self, start: A, end: B, a: Color, b: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::GradientLinear(start.into(), end.into(), a, b))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Fills the whole screen with a gradient going from `inner` at `center` to `outer` at distance `radius`
+    /// of the center (see `Image::gradient_radial`).
+    pub fn clear_gradient_radial<A>(&mut self, center: A, radius: i32, inner: Color, outer: Color)
+        where A: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::GradientRadial(center.into(), radius, inner, outer))
+            .expect("Rendering thread stoped");
+    }
+
+
     /// Draws a line of color `c` between `p1` and `p2`.
     pub fn draw_line<A, B>(&mut self, p1: A, p2: B, c: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawLine(p1.into(), p2.into(), c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws an anti-aliased line of color `c` between `p1` and `p2` (see `Image::line_aa`).
+    pub fn draw_line_aa<A, B>(&mut self, p1: A, p2: B, c: Color) 
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawLineAa(p1.into(), p2.into(), c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws a line between `p1` and `p2` whose color goes from `c1` at `p1` to `c2` at `p2`.
+    pub fn draw_line_gradient<A, B>(&mut self, p1: A, p2: B, c1: Color, c2: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawLineGradient(p1.into(), p2.into(), c1, c2))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws a quadratic Bezier curve of color `c` from `p0` to `p2` with control point `p1`.
+    pub fn draw_bezier_quad<A, B, C>(&mut self, p0: A, p1: B, p2: C, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>, C: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawBezierQuad(p0.into(), p1.into(), p2.into(), c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws a cubic Bezier curve of color `c` from `p0` to `p3` with control points `p1` and `p2`.
+    pub fn draw_bezier_cubic<A, B, C, D>(&mut self, p0: A, p1: B, p2: C, p3: D, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>, C: Into<Vec2>, D: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawLine(*p1.as_ref(), *p2.as_ref(), c))
+        self.sender.send(RenderingDirective::DrawBezierCubic(p0.into(), p1.into(), p2.into(), p3.into(), c))
             .expect("Rendering thread stoped");
     }
 
@@ -345,20 +2814,41 @@ impl Renderer {
     /// Draws a rectangle of color `c` and of size `s`. 
     /// `p` is the coordinate of the top left corner of the rectangle.
     pub fn draw_rect<A, B>(&mut self, p: A, s: B, c: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawRect(*p.as_ref(), *s.as_ref(), c))
+        self.sender.send(RenderingDirective::DrawRect(p.into(), s.into(), c))
             .expect("Rendering thread stoped");
     }
 
 
     /// Same as `draw_rect` but draws only the four sides of the rectangle.
     pub fn draw_rect_boundary<A, B>(&mut self, p: A, s: B, c: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawRectBoudary(*p.as_ref(), *s.as_ref(), c))
+        self.sender.send(RenderingDirective::DrawRectBoudary(p.into(), s.into(), c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws a rectangle of color `c` and of size `s` with corners rounded by quarter ellipses of radius `radius`.
+    /// `p` is the coordinate of the top left corner of the rectangle.
+    pub fn draw_rounded_rect<A, B>(&mut self, p: A, s: B, radius: i32, c: Color) 
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawRoundedRect(p.into(), s.into(), radius, c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Same as `draw_rounded_rect` but draws only the outline of the rectangle.
+    pub fn draw_rounded_rect_boundary<A, B>(&mut self, p: A, s: B, radius: i32, c: Color) 
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawRoundedRectBoundary(p.into(), s.into(), radius, c))
             .expect("Rendering thread stoped");
     }
 
@@ -366,20 +2856,123 @@ impl Renderer {
     /// Draws an ellipse of color `col`. `c` is the center of the ellipse and `s` is the size of the rectangle
     /// in which the ellipse is inscribed.
     pub fn draw_ellipse_boundary<A, B>(&mut self, c: A, s: B, col: Color) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawEllipseBoudary(c.into(), s.into(), col))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Same as `draw_ellipse_boundary` but fills the ellipse.
+    pub fn draw_ellipse<A, B>(&mut self, c: A, s: B, col: Color) 
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawEllipse(c.into(), s.into(), col))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws a circle of color `col` centered on `c`, of radius `radius` in horizontal pixels.
+    /// 
+    /// The pixels are not square in every render mode (eg. with `RenderMode::Quadrant` they are twice as high
+    /// as wide), so the circle is drawn as an ellipse that looks round, assuming cells twice as high as wide
+    /// (see `set_cell_aspect`).
+    pub fn draw_circle<A>(&mut self, c: A, radius: i32, col: Color)
+        where A: Into<Vec2>
+    {
+        let size = self.render_mode.circle_size(radius, self.cell_aspect);
+        self.draw_ellipse_boundary(c, size, col);
+    }
+
+
+    /// Draws the part of the outline of an ellipse between the angles `start_deg` and `end_deg` (see `Image::arc`).
+    pub fn draw_arc<A, B>(&mut self, c: A, s: B, start_deg: f32, end_deg: f32, col: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawEllipseBoudary(*c.as_ref(), *s.as_ref(), col))
+        self.sender.send(RenderingDirective::DrawArc(c.into(), s.into(), start_deg, end_deg, col))
             .expect("Rendering thread stoped");
     }
 
 
+    /// Draws the polygon with vertices `points` filled with `c`, using the even-odd rule.
+    pub fn draw_polygon(&mut self, points: &[Vec2], c: Color) {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawPolygon(points.to_vec(), c)).expect("Rendering thread stoped");
+    }
+
+
+    /// Draws the outline of the polygon with vertices `points`.
+    pub fn draw_polygon_boundary(&mut self, points: &[Vec2], c: Color) {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawPolygonBoundary(points.to_vec(), c)).expect("Rendering thread stoped");
+    }
+
+
     /// Sets the color of the pixel at `p` to `c`.
     pub fn draw_point<A>(&mut self, p: A, c: Color) 
-        where A: AsRef<Vec2>
+        where A: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawPoint(p.into(), c)).expect("Rendering thread stoped");
+    }
+
+
+    /// Draws the points `points`, each with its color. They are sent at once to the rendering thread, which
+    /// is faster than calling `draw_point` for each of them.
+    pub fn draw_points(&mut self, points: &[(Vec2, Color)]) {
+        self.can_draw();
+        let directives = points.iter().map(|&(p, c)| RenderingDirective::DrawPoint(p, c)).collect();
+        self.sender.send(RenderingDirective::Batch(directives)).expect("Rendering thread stoped");
+    }
+
+
+    /// Draws the commands of `batch` in order. They are sent at once to the rendering thread.
+    pub fn draw_batch(&mut self, batch: DrawBatch) {
+        self.can_draw();
+        self.sender.send(RenderingDirective::Batch(batch.directives)).expect("Rendering thread stoped");
+    }
+
+
+    /// Draws `text` with the built-in 5x7 font, `p` being the top left corner of the first character
+    /// (see `Image::draw_text`).
+    pub fn draw_text<A>(&mut self, p: A, text: &str, c: Color)
+        where A: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawText(p.into(), text.to_string(), c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws a frame with the box-drawing characters of the terminal (see `boxchars`), around the cells from
+    /// `p` to `p + s - 1`. Unlike the other drawing functions, `p` and `s` are in cells, whose size in pixels
+    /// depends on the render mode (see `RenderMode::cell_size`).
+    /// 
+    /// The characters replace the pixels of their cells until the screen is cleared. In every render mode,
+    /// the background of a character is the color of the top left pixel of its cell: the upper pixel in
+    /// `HalfBlock` mode, and the top left one of the 2x2 or 2x4 pixels in `Quadrant` and `Braille` modes.
+    pub fn draw_box_frame<A, B>(&mut self, p: A, s: B, style: BoxStyle, c: Color)
+        where A: Into<Vec2>, B: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawBoxFrame(p.into(), s.into(), style, c))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws the frame `frame` of a spinner in the cell `cell_pos` (see `boxchars::SPINNER`). Incrementing
+    /// `frame` every frame animates it.
+    /// 
+    /// Like `draw_box_frame`, the position is in cells and the character stays until the screen is cleared.
+    pub fn draw_spinner<A>(&mut self, cell_pos: A, frame: u32, fg: Color)
+        where A: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawPoint(*p.as_ref(), c)).expect("Rendering thread stoped");
+        self.sender.send(RenderingDirective::DrawChar(cell_pos.into(), boxchars::spinner_char(frame), fg))
+            .expect("Rendering thread stoped");
     }
 
 
@@ -388,10 +2981,37 @@ impl Renderer {
     /// Negative size results in flipped image. Alpha is used to ignore a given color while drawing.
     pub fn draw_image<A, B, C>(&mut self, 
         img: Arc<Mutex<Image>>, pos: A, size: B, offset: C, alpha: Option<Color>) 
-        where A: AsRef<Vec2>, B: AsRef<Vec2>, C: AsRef<Vec2>
+        where A: Into<Vec2>, B: Into<Vec2>, C: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawImage(img, pos.into(), size.into(), offset.into(), alpha))
+            .expect("Rendering thread stoped");
+    }
+
+
+    /// Draws the region `src_rect` (position and size) of `img` at `dst`, ignoring the color `colorkey`
+    /// (see `Image::blit`).
+    /// 
+    /// ```
+    /// # use termkan::vec2;
+    /// # use termkan::math::Vec2;
+    /// # use termkan::img::Color;
+    /// # use termkan::rds::Renderer;
+    /// # let mut rdr = Renderer::new_headless((40, 20));
+    /// # use std::sync::{Arc, Mutex};
+    /// # use termkan::img::Image;
+    /// # let (sheet, i, pos) = (Arc::new(Mutex::new(Image::new(64, 16))), 2, vec2!(5, 5));
+    /// # rdr.begin_draw();
+    /// // draws the frame `i` of a sprite sheet of 16x16 sprites
+    /// rdr.draw_image_region(sheet.clone(), (vec2!(16 * i, 0), vec2!(16, 16)), pos, Some(Color::BLACK));
+    /// # rdr.end_draw();
+    /// ```
+    pub fn draw_image_region<A>(&mut self, 
+        img: Arc<Mutex<Image>>, src_rect: (Vec2, Vec2), dst: A, colorkey: Option<Color>)
+        where A: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawImage(img, *pos.as_ref(), *size.as_ref(), *offset.as_ref(), alpha))
+        self.sender.send(RenderingDirective::DrawImageRegion(img, src_rect, dst.into(), colorkey))
             .expect("Rendering thread stoped");
     }
 
@@ -403,10 +3023,10 @@ impl Renderer {
     /// rdr.image(img, pos, img.size(), Vec2::ZERO, Some(alpha));
     /// ```
     pub fn draw_whole_image_alpha<A>(&mut self, img: Arc<Mutex<Image>>, pos: A, alpha: Color) 
-        where A: AsRef<Vec2>
+        where A: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawWholeImageAlpha(img, *pos.as_ref(), alpha))
+        self.sender.send(RenderingDirective::DrawWholeImageAlpha(img, pos.into(), alpha))
             .expect("Rendering thread stoped");
     }
 
@@ -418,47 +3038,175 @@ impl Renderer {
     /// rdr.image(img, pos, img.size(), Vec2::ZERO, None);
     /// ```
     pub fn draw_whole_image<A>(&mut self, img: Arc<Mutex<Image>>, pos: A) 
-        where A: AsRef<Vec2>
+        where A: Into<Vec2>
+    {
+        self.can_draw();
+        self.sender.send(RenderingDirective::DrawWholeImage(img, pos.into())).expect("Rendering thread stoped");
+    }
+
+
+    /// Draws the screen-sized region of `img` starting at `src_offset` over the whole screen.
+    /// This is the rendering side of a scroll view over an image larger than the screen.
+    /// 
+    /// The offset is clamped so that the region stays inside the image (see `Image::view`). The camera does
+    /// not move the region.
+    pub fn present_region<A>(&mut self, img: &Image, src_offset: A)
+        where A: Into<Vec2>
     {
         self.can_draw();
-        self.sender.send(RenderingDirective::DrawWholeImage(img, *pos.as_ref())).expect("Rendering thread stoped");
+        let region = img.view(src_offset, self.prev_screen_size);
+        // the server translates by the camera, the region is drawn at the top left corner of the screen
+        self.sender.send(RenderingDirective::DrawWholeImage(Arc::new(Mutex::new(region)), self.camera))
+            .expect("Rendering thread stoped");
     }
 
 
 
-    /// Rings the terminal bell. Can only be called during the creation of a frame
+
+    /// Sets the title of the terminal window. Control characters of `title` are removed.
     /// 
-    /// Technical note: the bell will ring when calling `end_draw`
+    /// It can be called outside of a frame. The title is not restored when the renderer exits, most
+    /// terminals keep it until another program changes it.
+    pub fn set_title(&self, title: &str) {
+        let title = title.chars().filter(|c| !c.is_control()).collect();
+        self.sender.send(RenderingDirective::SetTitle(title)).expect("Rendering thread stoped");
+    }
+
+
+    /// Rings the terminal bell. It can be called outside of a frame, the bell rings right away.
     pub fn ring_bell(&self) {
-        self.can_draw();
-        print!("\x07");
+        self.sender.send(RenderingDirective::RingBell).expect("Rendering thread stoped");
+    }
+
+
+    /// Asks the terminal the position of the cursor, as a column and a row starting from 0, eg. to check
+    /// the size of the terminal by moving the cursor to the bottom right corner.
+    /// 
+    /// The reply is read by `input`, which must be the one reading stdin (eg. `Input::get()`), the other events
+    /// received in the meantime are kept. Returns None if the terminal did not answer within `CURSOR_QUERY_TIMEOUT`.
+    pub fn query_cursor_position(&self, input: &mut Input) -> Option<Vec2> {
+        input.wait_cursor_position(CURSOR_QUERY_TIMEOUT, || {
+            self.sender.send(RenderingDirective::QueryCursorPosition).expect("Rendering thread stoped");
+        })
+    }
+
+
+    /// Flashes the screen in reverse video, for terminals with the bell disabled. The flash ends with the
+    /// first frame begun or pushed once `VISUAL_BELL_DURATION` has passed, the rendering thread never waits.
+    pub fn visual_bell(&self) {
+        self.sender.send(RenderingDirective::VisualBell).expect("Rendering thread stoped");
     }
 }
 
 
-impl Drop for Renderer {
+impl Canvas for Renderer {
 
-    /// When the renderer singleton is droped, reset terminal settings and exit.
-    fn drop(&mut self) {
-        // return settings to default
-        self.termios.c_cc = self.default_c_cc;
-        self.termios.c_lflag = self.default_c_lflags;
+    fn size(&self) -> Vec2 {
+        self.screen_size()
+    }
 
-        print!("{}{}",
-            csi!("?25h"),                                   // show cursor
-            csi!("?1049l")                                  // use main screen buffer
-        );
-        stdout().flush().expect("Could not write to stdout");
-        Input::disable_mouse();
+    fn draw_point(&mut self, p: Vec2, c: Color) {
+        Renderer::draw_point(self, p, c);
+    }
 
-        std::process::exit(0);
+    fn draw_line(&mut self, p1: Vec2, p2: Vec2, c: Color) {
+        Renderer::draw_line(self, p1, p2, c);
+    }
+
+    fn draw_rect(&mut self, p: Vec2, s: Vec2, c: Color) {
+        Renderer::draw_rect(self, p, s, c);
+    }
+
+    fn draw_rect_boundary(&mut self, p: Vec2, s: Vec2, c: Color) {
+        Renderer::draw_rect_boundary(self, p, s, c);
     }
 }
 
 
+impl Drop for Renderer {
+
+    /// When a renderer is droped, reset terminal settings.
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+
+#[cfg(unix)]
 struct TermSize {
     row: libc::c_ushort,
     col: libc::c_ushort,
     _x : libc::c_ushort,
     _y : libc::c_ushort
+}
+
+
+// console API of Windows, used by `WindowsTerminal`
+
+#[cfg(windows)]
+const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+#[cfg(windows)]
+const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+#[cfg(windows)]
+const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+#[cfg(windows)]
+const ENABLE_LINE_INPUT: u32 = 0x0002;
+#[cfg(windows)]
+const ENABLE_ECHO_INPUT: u32 = 0x0004;
+#[cfg(windows)]
+const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+#[cfg(windows)]
+const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+
+#[cfg(windows)]
+#[repr(C)]
+struct ConsoleScreenBufferInfo {
+    size: [i16; 2],
+    cursor_position: [i16; 2],
+    attributes: u16,
+    window: SmallRect,
+    maximum_window_size: [i16; 2]
+}
+
+
+#[cfg(windows)]
+#[repr(C)]
+struct SmallRect {
+    left: i16,
+    top: i16,
+    right: i16,
+    bottom: i16
+}
+
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetStdHandle(std_handle: u32) -> *mut c_void;
+    fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+    fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    fn GetConsoleScreenBufferInfo(console: *mut c_void, info: *mut ConsoleScreenBufferInfo) -> i32;
+}
+
+
+/// Returns the mode of the console of the standard stream `handle`.
+#[cfg(windows)]
+fn console_mode(handle: u32) -> Result<u32, RendererError> {
+    let mut mode = 0;
+    match unsafe { GetConsoleMode(GetStdHandle(handle), &mut mode) } {
+        0 => Err(RendererError::TerminalSettings),
+        _ => Ok(mode)
+    }
+}
+
+
+/// Sets the mode of the console of the standard stream `handle`.
+#[cfg(windows)]
+fn set_console_mode(handle: u32, mode: u32) -> Result<(), RendererError> {
+    match unsafe { SetConsoleMode(GetStdHandle(handle), mode) } {
+        0 => Err(RendererError::TerminalSettings),
+        _ => Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,234 @@
+/*
+
+    MIT License
+
+    Copyright (c) 2022 Siandfrance
+
+    Permission is hereby granted, free of charge, to any person obtaining a copy
+    of this software and associated documentation files (the "Software"), to deal
+    in the Software without restriction, including without limitation the rights
+    to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+    copies of the Software, and to permit persons to whom the Software is
+    furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in all
+    copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+    OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+    SOFTWARE.
+
+*/
+
+
+use crate::math::Vec2;
+use crate::img::{Color, Canvas};
+use crate::input::{InputEvent, KeyEvent, MouseEvent, ScrollDirection};
+use crate::rds::RenderMode;
+
+
+/// A scrollable list of items with a selected item, drawn in a rectangle of the screen.
+/// 
+/// # Usage
+/// 
+/// ```no_run
+/// use termkan::vec2;
+/// use termkan::math::Vec2;
+/// use termkan::img::Color;
+/// use termkan::input::Input;
+/// use termkan::rds::Renderer;
+/// use termkan::widgets::List;
+/// 
+/// let mut list = List::new(vec!["a", "b", "c"], vec2!(0, 0), vec2!(20, 10), 2);
+/// let mut rdr = Renderer::get();
+/// 
+/// if let Some(event) = Input::get().get_event() {
+///     list.handle_event(&event); // arrow keys, Home, End, PageUp, PageDown and mouse
+/// }
+/// 
+/// rdr.begin_draw();
+/// list.draw(&mut *rdr, |rdr, item, pos, _size, selected| {
+///     // draw the item in the row at `pos` of size `size`
+///     rdr.draw_text(pos, item, if selected {Color::WHITE} else {Color::GRAY});
+/// });
+/// rdr.end_draw();
+/// ```
+pub struct List<T> {
+    items: Vec<T>,
+    selected: usize,
+    scroll: usize,
+
+    pos: Vec2,
+    size: Vec2,
+    row_height: i32,
+    /// render mode used to find the pixels of the cells given by the mouse events
+    render_mode: RenderMode,
+
+    background: Color,
+    highlight: Color
+}
+
+
+impl<T> List<T> {
+
+    /// Creates a list showing `items` in the rectangle at `pos` of size `size`.
+    /// Each item takes `row_height` pixels.
+    pub fn new(items: Vec<T>, pos: Vec2, size: Vec2, row_height: i32) -> Self {
+        if row_height < 1 {
+            panic!("List row height must be positive");
+        }
+        Self {
+            items,
+            selected: 0,
+            scroll: 0,
+
+            pos,
+            size,
+            row_height,
+            render_mode: RenderMode::default(),
+
+            background: Color::BLACK,
+            highlight: Color::DIM_GRAY
+        }
+    }
+
+
+    /// Sets the color of the rows and the color of the selected row.
+    pub fn set_colors(&mut self, background: Color, highlight: Color) {
+        self.background = background;
+        self.highlight = highlight;
+    }
+
+
+    /// Sets the render mode of the renderer the list is drawn with, so that the cells of the mouse events
+    /// are matched with the right rows. The default is `RenderMode::HalfBlock`.
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.render_mode = mode;
+    }
+
+
+    /// Moves the list to the rectangle at `pos` of size `size`.
+    pub fn set_rect(&mut self, pos: Vec2, size: Vec2) {
+        self.pos = pos;
+        self.size = size;
+        self.select(self.selected);
+    }
+
+
+    /// Returns the items of the list.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+
+    /// Replaces the items of the list, the selection is kept in range.
+    pub fn set_items(&mut self, items: Vec<T>) {
+        self.items = items;
+        self.select(self.selected);
+    }
+
+
+    /// Returns the index of the selected item.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+
+    /// Returns the selected item, None if the list is empty.
+    pub fn selected_item(&self) -> Option<&T> {
+        self.items.get(self.selected)
+    }
+
+
+    /// Returns the index of the first visible item.
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+
+    /// Returns the number of rows that fit in the rectangle of the list.
+    pub fn visible_rows(&self) -> usize {
+        (self.size.y / self.row_height).max(1) as usize
+    }
+
+
+    /// Returns true if `p` is inside the rectangle of the list.
+    fn contains(&self, p: Vec2) -> bool {
+        p.x >= self.pos.x && p.y >= self.pos.y && p.x < self.pos.x + self.size.x && p.y < self.pos.y + self.size.y
+    }
+
+
+    /// Selects the item at `index` (clamped to the items), scrolling so that it is visible.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+
+        let visible = self.visible_rows();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + visible {
+            self.scroll = self.selected + 1 - visible;
+        }
+    }
+
+
+    /// Updates the selection according to `event`. Returns true if the event was used by the list.
+    pub fn handle_event(&mut self, event: &InputEvent) -> bool {
+        let page = self.visible_rows();
+        match event {
+            InputEvent::Key(key) => match key {
+                KeyEvent::Up       => self.select(self.selected.saturating_sub(1)),
+                KeyEvent::Down     => self.select(self.selected + 1),
+                KeyEvent::Home     => self.select(0),
+                KeyEvent::End      => self.select(self.items.len()),
+                KeyEvent::PageUp   => self.select(self.selected.saturating_sub(page)),
+                KeyEvent::PageDown => self.select(self.selected + page),
+                _ => return false
+            }
+            InputEvent::Mouse(MouseEvent::ButtonPressed(_, cell, _)) => {
+                let p = self.render_mode.cell_to_pixel(*cell);
+                if !self.contains(p) {
+                    return false;
+                }
+                let row = self.scroll + ((p.y - self.pos.y) / self.row_height) as usize;
+                if row >= self.items.len() {
+                    return false;
+                }
+                self.selected = row;
+            }
+            InputEvent::Mouse(MouseEvent::Scroll(direction, cell, _)) => {
+                if !self.contains(self.render_mode.cell_to_pixel(*cell)) {
+                    return false;
+                }
+                match direction {
+                    ScrollDirection::Up   => self.scroll = self.scroll.saturating_sub(1),
+                    ScrollDirection::Down => self.scroll = (self.scroll + 1).min(self.items.len().saturating_sub(page)),
+                }
+            }
+            _ => return false
+        }
+        true
+    }
+
+
+    /// Draws the visible rows on `canvas`. The background of each row is drawn, then `draw_item` is called
+    /// with the item, the position and size of its row and weather it is selected.
+    pub fn draw<C, F>(&self, canvas: &mut C, mut draw_item: F)
+        where C: Canvas, F: FnMut(&mut C, &T, Vec2, Vec2, bool)
+    {
+        canvas.draw_rect(self.pos, self.size, self.background);
+
+        let row_size = vec2!(self.size.x, self.row_height);
+        for (i, item) in self.items.iter().enumerate().skip(self.scroll).take(self.visible_rows()) {
+            let row_pos = self.pos + vec2!(0, (i - self.scroll) as i32 * self.row_height);
+            let selected = i == self.selected;
+            if selected {
+                canvas.draw_rect(row_pos, row_size, self.highlight);
+            }
+            draw_item(canvas, item, row_pos, row_size, selected);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Checks that the rendering thread does not allocate in steady state, thanks to the buffer pool.
+//!
+//! It has its own test binary because the counting allocator replaces the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use termkan::vec2;
+use termkan::math::Vec2;
+use termkan::img::{Color, ColorMode};
+use termkan::rds::{Renderer, RendererConfig};
+
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+
+thread_local! {
+    static IGNORED: Cell<bool> = const { Cell::new(false) };
+}
+
+
+/// Allocator counting the allocations made by the threads that are not ignored.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+
+fn count() {
+    if !IGNORED.try_with(Cell::get).unwrap_or(true) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+
+/// Returns the number of allocations made by the other threads while running `f`.
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+
+/// Draws a frame that changes with `n`.
+fn frame(rdr: &mut Renderer, n: i32) {
+    rdr.begin_draw();
+    rdr.clear_screen(Color::rgb(10, 20, 30));
+    rdr.draw_rect(vec2!(n % 60, 4), vec2!(20, 20), Color::rgb(200, 100, 50));
+    rdr.draw_line(vec2!(0, 0), vec2!(79, n % 48), Color::WHITE);
+    rdr.end_draw();
+}
+
+
+#[test]
+fn frame_allocations() {
+    // only the rendering thread is counted, not the one sending the commands
+    IGNORED.with(|ignored| ignored.set(true));
+
+    let mut rdr = Renderer::with_output(io::sink(), RendererConfig::new());
+    rdr.set_synchronized_output(true);
+    rdr.set_color_mode(ColorMode::Ansi256);
+    rdr.set_dithering(true);
+
+    // warm up
+    for n in 0..5 {
+        frame(&mut rdr, n);
+    }
+    rdr.begin_draw();
+    rdr.present();
+
+    // waiting for the frame to be written allocates the channel of the answer, and nothing more once
+    // the frames are rendered
+    let sync = count_allocations(|| {
+        rdr.begin_draw();
+        rdr.present();
+    });
+    let frames = count_allocations(|| {
+        for n in 5..105 {
+            frame(&mut rdr, n);
+        }
+        rdr.begin_draw();
+        rdr.present();
+    });
+    assert_eq!(frames, sync);
+}